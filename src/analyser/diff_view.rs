@@ -0,0 +1,133 @@
+use crate::{
+    diff::{Change, TreeDiff, diff},
+    snapshot::{Snapshot, WinData},
+};
+use egui::{Color32, Ui};
+
+/// Compares a snapshot against another snapshot or the live windows, side by side.
+#[derive(Default)]
+pub(crate) struct DiffView {
+    old_path: String,
+    /// When empty the live windows are compared against.
+    new_path: String,
+
+    compared: Option<Compared>,
+}
+
+struct Compared {
+    old: WinData,
+    new: WinData,
+    diff: TreeDiff,
+}
+
+#[derive(Clone, Copy)]
+enum Side {
+    Old,
+    New,
+}
+
+impl DiffView {
+    pub(crate) fn show(&mut self, ui: &mut Ui, live: &WinData, log: &mut String) {
+        ui.horizontal(|ui| {
+            ui.label("Old Snapshot");
+            ui.text_edit_singleline(&mut self.old_path);
+            ui.label("New Snapshot (Empty For Live)");
+            ui.text_edit_singleline(&mut self.new_path);
+
+            if ui.button("Compare").clicked() {
+                match self.compare(live) {
+                    Ok(compared) => {
+                        *log = format!("Found {} changes", compared.diff.changes.len());
+                        self.compared = Some(compared);
+                    }
+                    Err(err) => *log = err,
+                }
+            }
+        });
+
+        let Some(compared) = &self.compared else {
+            return;
+        };
+
+        ui.collapsing(format!("Changes ({})", compared.diff.changes.len()), |ui| {
+            egui::ScrollArea::vertical()
+                .id_salt("changes")
+                .max_height(200.0)
+                .show(ui, |ui| {
+                    for change in compared.diff.changes.iter() {
+                        ui.colored_label(colour(change), change.to_string());
+                    }
+                });
+        });
+
+        ui.separator();
+        ui.columns(2, |columns| {
+            for (ui, (side, data)) in columns
+                .iter_mut()
+                .zip([(Side::Old, &compared.old), (Side::New, &compared.new)])
+            {
+                let salt = match side {
+                    Side::Old => "old",
+                    Side::New => "new",
+                };
+                egui::ScrollArea::both().id_salt(salt).show(ui, |ui| {
+                    display(ui, data, side, &compared.diff);
+                });
+            }
+        });
+    }
+
+    fn compare(&self, live: &WinData) -> Result<Compared, String> {
+        let load = |path: &str| {
+            Snapshot::load(path)
+                .map(|snapshot| snapshot.root)
+                .map_err(|err| format!("Unable to load '{path}': {err}"))
+        };
+
+        let old = load(&self.old_path)?;
+        let new = match self.new_path.trim() {
+            "" => live.clone(),
+            path => load(path)?,
+        };
+        let diff = diff(&old, &new);
+
+        Ok(Compared { old, new, diff })
+    }
+}
+
+/// Displays one side of the diff, colouring the windows that changed.
+fn display(ui: &mut Ui, data: &WinData, side: Side, diff: &TreeDiff) {
+    let changes: Vec<&Change> = match side {
+        Side::Old => diff.at_old_path(&data.path).collect(),
+        Side::New => diff.at_new_path(&data.path).collect(),
+    };
+
+    ui.horizontal(|ui| {
+        ui.add_space((data.depth * 20) as f32);
+        ui.vertical(|ui| {
+            if let Some(change) = changes.first() {
+                ui.visuals_mut().override_text_color = Some(colour(change));
+            }
+
+            ui.label(format!("{:?} '{}' '{}'", data.path, data.name, data.text));
+            for change in changes.iter() {
+                ui.small(change.to_string());
+            }
+
+            ui.visuals_mut().override_text_color = None;
+        });
+    });
+
+    for child in data.children.iter() {
+        display(ui, child, side, diff);
+    }
+}
+
+fn colour(change: &Change) -> Color32 {
+    match change {
+        Change::Added { .. } => Color32::LIGHT_GREEN,
+        Change::Removed { .. } => Color32::LIGHT_RED,
+        Change::Moved { .. } => Color32::LIGHT_BLUE,
+        Change::Text { .. } | Change::Class { .. } | Change::Rect { .. } => Color32::YELLOW,
+    }
+}
@@ -1,7 +1,16 @@
+mod diff_view;
 mod win_info;
 
 use super::window::get_windows;
-use crate::{analyser::win_info::WinData, attach};
+use crate::{
+    analyser::diff_view::DiffView,
+    attach,
+    backend::win32::Win32Backend,
+    config, report,
+    screen::{Recognition, Recognizer},
+    snapshot::{Snapshot, WinData},
+    wait::CancelToken,
+};
 use eframe::{App, EventLoopBuilderHook};
 use egui::{CentralPanel, Id, Layout, SidePanel, TopBottomPanel};
 use std::{thread, time::Duration};
@@ -69,6 +78,16 @@ struct MyApp {
 
     /// Window information
     window_data: Cache<10, WinData>,
+    /// The frontend screen currently shown.
+    screen: Cache<10, Recognition>,
+    recognizer: Recognizer,
+
+    /// Stops the mod started by this analyser.
+    running: Option<CancelToken>,
+
+    /// Show the comparison between snapshots instead of the live windows.
+    show_diff: bool,
+    diff_view: DiffView,
 
     show_key_pressed: bool,
     key_index_modal: bool,
@@ -94,7 +113,14 @@ impl App for MyApp {
 
         let window_data = self
             .window_data
-            .get(|| win_info::get_window_data(&get_windows(self.window_id)));
+            .get(|| win_info::get_window_data(&get_windows(&Win32Backend, self.window_id.into())));
+
+        let screen = self.screen.get(|| {
+            self.recognizer.recognise(
+                &Win32Backend,
+                &get_windows(&Win32Backend, self.window_id.into()),
+            )
+        });
 
         let pressed = self.keyboard_state.get(|| {
             let state = unsafe {
@@ -126,32 +152,75 @@ impl App for MyApp {
         }
 
         if pressed.as_slice() == LOG_INFO_KEYBIND {
-            let info = window_data.last_child_containing(&cursor_pos).map(|data| {
-                format!(
-                    "Selected Text '{}'; Selected Path '{:?}';",
-                    data.text, data.path
-                )
-            });
+            let info = window_data
+                .last_child_containing((cursor_pos.x, cursor_pos.y))
+                .map(|data| {
+                    format!(
+                        "Selected Text '{}'; Selected Path '{:?}';",
+                        data.text, data.path
+                    )
+                });
             self.log = format!("Information> {info:?} Mouse Position '{cursor_pos:?}';",)
         }
 
         TopBottomPanel::top("tap").show(ctx, |ui| {
             ui.label(format!("Cursor Position: {cursor_pos:?}"));
+            ui.label(format!("Screen: {screen}"));
 
             ui.checkbox(&mut self.only_containing, "Only Show Containing Cursor");
             ui.checkbox(&mut self.show_info, "Show Long Info");
             ui.checkbox(&mut self.show_key_pressed, "Show Pressed Key Indexes");
+            ui.checkbox(&mut self.show_diff, "Show Snapshot Diff");
 
             ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.button("Spawn Mod").clicked() {
-                    attach();
+                // Only one run at a time, as runs would fight over the lobby
+                if ui
+                    .add_enabled(self.running.is_none(), egui::Button::new("Spawn Mod"))
+                    .clicked()
+                {
+                    self.running = Some(attach());
                 };
+
+                if let Some(running) = &self.running
+                    && ui.button("Stop Mod").clicked()
+                {
+                    running.cancel();
+                    self.running = None;
+                }
+
+                if ui.button("Write Report").clicked() {
+                    self.log = match report::generate(config::get()) {
+                        Ok([markdown, html]) => format!(
+                            "Wrote report to '{}' and '{}'",
+                            markdown.display(),
+                            html.display()
+                        ),
+                        Err(err) => format!("Unable to write report: {err}"),
+                    };
+                }
+
+                if ui.button("Save Snapshot").clicked() {
+                    let snapshot = Snapshot::new(window_data.clone());
+                    let config = config::get();
+                    let path = config
+                        .resolve(&config.analyser.snapshot_dir)
+                        .join(format!("{}.json", snapshot.captured_at));
+                    self.log = match snapshot.save(&path) {
+                        Ok(()) => format!("Saved snapshot to '{}'", path.display()),
+                        Err(err) => format!("Unable to save snapshot: {err}"),
+                    };
+                }
             })
         });
 
         TopBottomPanel::bottom("battam").show(ctx, |ui| ui.label(&self.log));
 
         CentralPanel::default().show(ctx, |ui| {
+            if self.show_diff {
+                self.diff_view.show(ui, window_data, &mut self.log);
+                return;
+            }
+
             egui::ScrollArea::vertical().animated(false).show(ui, |ui| {
                 win_info::display(
                     &window_data,
@@ -163,7 +232,7 @@ impl App for MyApp {
             });
         });
 
-        ctx.request_repaint_after(Duration::from_millis(20));
+        ctx.request_repaint_after(config::get().analyser.repaint_interval());
     }
 }
 
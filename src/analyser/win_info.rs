@@ -1,10 +1,11 @@
 use super::super::window::Found;
+use crate::snapshot::{WinData, WinInfo};
 use egui::{Color32, Ui};
 use windows::Win32::{
-    Foundation::POINT,
+    Foundation::{HWND, POINT},
     UI::WindowsAndMessaging::{
-        GET_CLASS_LONG_INDEX, GetClassLongA, GetClassNameA, GetWindowInfo, GetWindowTextW,
-        RealGetWindowClassA, WINDOWINFO,
+        GET_CLASS_LONG_INDEX, GetClassLongA, GetClassNameA, GetDlgCtrlID, GetWindowInfo,
+        GetWindowTextW, RealGetWindowClassA, WINDOWINFO,
     },
 };
 
@@ -16,14 +17,8 @@ pub(crate) fn display(
     only_containing: bool,
     show_extra_info: bool,
 ) {
-    let pos = data.info.rcWindow;
-
     // If window contains cursor
-    if pos.bottom > cursor_pos.y
-        && pos.top < cursor_pos.y
-        && pos.left < cursor_pos.x
-        && pos.right > cursor_pos.x
-    {
+    if data.info.window.contains((cursor_pos.x, cursor_pos.y)) {
         ui.visuals_mut().override_text_color = Some(Color32::LIGHT_RED);
     } else if only_containing {
         return;
@@ -55,75 +50,13 @@ pub(crate) fn display(
     }
 }
 
-/// Contains information about a window
-pub(crate) struct WinData {
-    pub(crate) text: Box<str>,
-    pub(crate) name: Box<str>,
-    pub(crate) w_type: Box<str>,
-    pub(crate) info: WINDOWINFO,
-    pub(crate) atom: u32,
-
-    pub(crate) depth: usize,
-    pub(crate) path: Vec<usize>,
-
-    pub(crate) children: Vec<WinData>,
-}
-
-impl WinData {
-    pub(crate) fn new(
-        text: impl Into<Box<str>>,
-        name: impl Into<Box<str>>,
-        w_type: impl Into<Box<str>>,
-        info: WINDOWINFO,
-        atom: u32,
-        depth: usize,
-        path: Vec<usize>,
-    ) -> Self {
-        Self {
-            text: text.into(),
-            name: name.into(),
-            w_type: w_type.into(),
-            info,
-            atom,
-            children: Vec::new(),
-            depth,
-            path,
-        }
-    }
-
-    pub(crate) fn add_child(&mut self, child: WinData) {
-        self.children.push(child);
-    }
-
-    pub fn last_child_containing(&self, cursor_pos: &POINT) -> Option<&WinData> {
-        let pos = self.info.rcWindow;
-
-        // If window does not contain cursor
-        if !(pos.bottom > cursor_pos.y
-            && pos.top < cursor_pos.y
-            && pos.left < cursor_pos.x
-            && pos.right > cursor_pos.x)
-        {
-            return None;
-        }
-
-        for data in self.children.iter() {
-            if let Some(contains) = data.last_child_containing(cursor_pos) {
-                return Some(contains);
-            };
-        }
-
-        return Some(self);
-    }
-}
-
 /// Gets the [`WinData`] for found and all child windows.
 pub(crate) fn get_window_data(found: &Found) -> WinData {
     get_window_data_rec(found, 0, Vec::new())
 }
 
 fn get_window_data_rec(found: &Found, depth: usize, path: Vec<usize>) -> WinData {
-    let id = found.value();
+    let id = HWND::from(found.value());
     let mut text = [' ' as u16; 255];
     unsafe { GetWindowTextW(id, &mut text) };
     let text = String::from_utf16(&text).expect("Unable to get window title");
@@ -141,11 +74,24 @@ fn get_window_data_rec(found: &Found, depth: usize, path: Vec<usize>) -> WinData
 
     let atom = unsafe { GetClassLongA(id, GET_CLASS_LONG_INDEX(-32)) };
 
+    let control_id = unsafe { GetDlgCtrlID(id) };
+
     let mut data = WinData::new(
-        text.trim(),
-        name.trim(),
-        w_type.trim(),
-        info,
+        until_nul(&text),
+        until_nul(&name),
+        until_nul(&w_type),
+        control_id,
+        WinInfo {
+            window: info.rcWindow.into(),
+            client: info.rcClient.into(),
+            style: info.dwStyle.0,
+            ex_style: info.dwExStyle.0,
+            status: info.dwWindowStatus,
+            border_width: info.cxWindowBorders,
+            border_height: info.cyWindowBorders,
+            atom_type: info.atomWindowType,
+            creator_version: info.wCreatorVersion,
+        },
         atom,
         depth,
         path.clone(),
@@ -161,3 +107,8 @@ fn get_window_data_rec(found: &Found, depth: usize, path: Vec<usize>) -> WinData
 
     data
 }
+
+/// Gets the text before the null terminator the windows API writes into buffers.
+fn until_nul(text: &str) -> &str {
+    text.split('\0').next().unwrap_or_default().trim()
+}
@@ -0,0 +1,262 @@
+//! Drives the WA frontend through rounds.
+//!
+//! Moving between screens is left to [`crate::navigation`] and reading the results to
+//! [`crate::results`].
+
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    backend::{Backend, WindowId},
+    config,
+    game::{self, GameSetup, MapRotation},
+    lobby,
+    navigation::{Goal, NavigationError, Navigator},
+    results::{self, RoundResult},
+    screen::Screen,
+    selector::{Selector, quote},
+    wait::{CancelToken, Deadline},
+};
+
+/// The title of the main WA window.
+pub const WINDOW_TITLE: &str = "Worms Armageddon";
+
+// The default text the frontend is recognised by, see [`crate::config::MarkerText`]
+pub const CREATE_GAME_MENU: &str = "(1)Create single or multiplayer game";
+pub const ADD_TEAM_MESSAGE: &str = "Left click a team to add it to the game. Right click to edit.";
+pub const ROUND_RESULTS_TEXT: &str = "(1) ROUND RESULTS";
+pub const WINNER_TEXT: &str = "wins";
+pub const DRAW_TEXT: &str = "draw";
+
+pub(crate) static CREATE_GAME_BUTTON: LazyLock<Selector> = LazyLock::new(|| {
+    let text = &config::get().automation.markers.create_game_menu;
+    selector(&format!("[text^={}]", quote(text)))
+});
+pub(crate) static PLAY_BUTTON: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(2)"));
+pub(crate) static ROUND_RESULTS_EXIT: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(1)"));
+
+/// Parses a selector that is part of this crate, so it should always be valid.
+pub(crate) fn selector(source: &str) -> Selector {
+    Selector::parse(source).unwrap_or_else(|err| panic!("{err}"))
+}
+
+/// How long each wait in the automation may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Waiting for a frontend screen to appear.
+    pub screen: Duration,
+    /// Waiting for a round to be played out.
+    pub round: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            screen: Duration::from_secs(30),
+            round: Duration::from_secs(2 * 60 * 60),
+        }
+    }
+}
+
+/// How many rounds [`play_rounds`] plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounds {
+    Count(u32),
+    /// Keep playing until cancelled.
+    UntilStopped,
+}
+
+impl Rounds {
+    fn includes(&self, round: u32) -> bool {
+        match self {
+            Rounds::Count(count) => round <= *count,
+            Rounds::UntilStopped => true,
+        }
+    }
+}
+
+/// From any known screen, sets up a game and plays a single round.
+pub fn play_round(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    setup: &GameSetup,
+    timeouts: &Timeouts,
+    cancel: &CancelToken,
+) -> Result<RoundResult, NavigationError> {
+    play_one(
+        &Navigator::default(),
+        backend,
+        window_id,
+        1,
+        setup,
+        timeouts,
+        cancel,
+    )
+}
+
+/// The same setup for a number of rounds, each on the next map of a rotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundPlan {
+    pub setup: GameSetup,
+    /// The setup's map is used when the rotation is empty.
+    pub maps: MapRotation,
+    pub rounds: Rounds,
+}
+
+impl RoundPlan {
+    /// The setup for the round, counting from `1`, or `None` once every round has been played.
+    pub fn setup(&self, round: u32) -> Option<GameSetup> {
+        self.rounds.includes(round).then(|| GameSetup {
+            map: self.maps.map(round).or_else(|| self.setup.map.clone()),
+            ..self.setup.clone()
+        })
+    }
+}
+
+/// Plays rounds one after another, logging a summary of each, until `next_setup` has no setup
+/// for the next round.
+///
+/// Before every round the lobby is reset if configured, and the scheme, options and teams in it are
+/// checked and set again if they don't match.
+/// Each result is passed to `on_result` as soon as the round is over.
+/// A round that fails is played again, until `max_failed_rounds` fail in a row and the last error
+/// is returned. Cancelling stops the loop; the rounds completed until then are returned.
+pub fn play_rounds(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    timeouts: &Timeouts,
+    cancel: &CancelToken,
+    mut next_setup: impl FnMut(u32) -> Option<GameSetup>,
+    mut on_result: impl FnMut(&RoundResult),
+) -> Result<Vec<RoundResult>, NavigationError> {
+    let navigator = Navigator::default();
+    let mut played = Vec::new();
+
+    let max_failed = config::get().automation.max_failed_rounds;
+    let mut failed = 0;
+    let mut round = 1;
+    while let Some(setup) = next_setup(round) {
+        let result = play_one(
+            &navigator, backend, window_id, round, &setup, timeouts, cancel,
+        );
+        match result {
+            Ok(result) => {
+                log::info!("{result}");
+                on_result(&result);
+                played.push(result);
+                failed = 0;
+            }
+            Err(err) if err.is_cancelled() => {
+                log::info!("Stopped during round {round}");
+                break;
+            }
+            Err(err) => {
+                failed += 1;
+                if failed >= max_failed {
+                    log::error!("Round {round} failed {failed} times in a row, stopping");
+                    return Err(err);
+                }
+                log::error!("Round {round} failed, playing it again: {err}");
+                continue;
+            }
+        }
+        round += 1;
+    }
+
+    let total: Duration = played.iter().map(|result| result.duration).sum();
+    log::info!("Played {} rounds in {total:.0?}", played.len());
+
+    Ok(played)
+}
+
+/// Sets up the lobby, plays the round out and leaves the round results.
+fn play_one(
+    navigator: &Navigator,
+    backend: &dyn Backend,
+    window_id: WindowId,
+    round: u32,
+    setup: &GameSetup,
+    timeouts: &Timeouts,
+    cancel: &CancelToken,
+) -> Result<RoundResult, NavigationError> {
+    if config::get().automation.reset_lobby {
+        navigator.navigate(
+            backend,
+            window_id,
+            &Goal::Screen(Screen::MultiplayerLobby),
+            timeouts,
+            cancel,
+        )?;
+        // The round's teams, on the configured scheme and options
+        let defaults = GameSetup {
+            lineup: setup.lineup.clone(),
+            ..config::get().automation.setup()
+        };
+        lobby::reset(
+            backend,
+            window_id,
+            &defaults,
+            &Deadline::after(timeouts.screen),
+            cancel,
+        )?;
+    }
+
+    navigator.navigate(
+        backend,
+        window_id,
+        &Goal::Lobby(setup.clone()),
+        timeouts,
+        cancel,
+    )?;
+    let map = game::current_map(backend, window_id)
+        .inspect_err(|err| log::warn!("Unable to read the map: {err}"))
+        .ok();
+    let scheme = game::current_scheme(backend, window_id).or_else(|| setup.scheme.clone());
+    let lineup = lobby::selected_teams(
+        backend,
+        window_id,
+        &Deadline::after(timeouts.screen),
+        cancel,
+    )?;
+
+    log::debug!("Playing round {round}");
+    let started = Instant::now();
+    navigator.navigate(
+        backend,
+        window_id,
+        &Goal::Screen(Screen::RoundResults),
+        timeouts,
+        cancel,
+    )?;
+    let duration = started.elapsed();
+    let finished_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    log::debug!("Round {round} finished");
+    let outcome = results::read(backend, window_id, &lineup);
+
+    // Exit the round over screen
+    navigator.navigate(
+        backend,
+        window_id,
+        &Goal::Screen(Screen::MultiplayerLobby),
+        timeouts,
+        cancel,
+    )?;
+
+    Ok(RoundResult {
+        round,
+        finished_at,
+        duration,
+        lineup,
+        scheme,
+        options: setup.options,
+        map,
+        outcome,
+    })
+}
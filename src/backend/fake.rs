@@ -0,0 +1,643 @@
+//! An in-memory window tree that can be scripted to react to the automation.
+
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    sync::{Mutex, MutexGuard},
+};
+
+use super::{Backend, Rect, WindowId};
+use crate::events::ChangeSignal;
+
+/// Reacts to a window being clicked by changing the desktop.
+pub type ClickHandler = Box<dyn FnMut(&mut FakeDesktop, WindowId) + Send>;
+/// Reacts to a list box item being clicked by changing the desktop.
+pub type ItemHandler = Box<dyn FnMut(&mut FakeDesktop, WindowId, usize) + Send>;
+
+/// The height of each list box item.
+pub const ITEM_HEIGHT: i32 = 16;
+
+/// A [`Backend`] over a fake desktop of windows.
+#[derive(Default)]
+pub struct FakeBackend {
+    desktop: Mutex<FakeDesktop>,
+    handlers: Mutex<Handlers>,
+    changes: ChangeSignal,
+}
+
+#[derive(Default)]
+struct Handlers {
+    click: HashMap<WindowId, ClickHandler>,
+    item_click: HashMap<WindowId, ItemHandler>,
+    /// For windows without their own handler.
+    any_click: Option<ClickHandler>,
+    any_item_click: Option<ItemHandler>,
+}
+
+impl FakeBackend {
+    pub fn new(desktop: FakeDesktop) -> Self {
+        Self {
+            desktop: Mutex::new(desktop),
+            handlers: Default::default(),
+            changes: Default::default(),
+        }
+    }
+
+    /// Gives access to the desktop, for changing it during a test.
+    /// Anything waiting for a change is notified once the guard is dropped.
+    pub fn desktop(&self) -> DesktopGuard<'_> {
+        DesktopGuard {
+            desktop: self.lock(),
+            changes: &self.changes,
+            changed: false,
+        }
+    }
+
+    /// Calls the handler whenever the window is clicked, replacing any previous handler.
+    pub fn on_click(
+        &self,
+        id: WindowId,
+        handler: impl FnMut(&mut FakeDesktop, WindowId) + Send + 'static,
+    ) {
+        self.handlers().click.insert(id, Box::new(handler));
+    }
+
+    /// Calls the handler whenever an item of the list box is clicked, or an item of the combo box
+    /// is selected, replacing any previous handler.
+    pub fn on_item_click(
+        &self,
+        id: WindowId,
+        handler: impl FnMut(&mut FakeDesktop, WindowId, usize) + Send + 'static,
+    ) {
+        self.handlers().item_click.insert(id, Box::new(handler));
+    }
+
+    /// Calls the handler whenever a window without its own handler is clicked, such as a window
+    /// added by another handler, replacing any previous handler.
+    pub fn on_any_click(&self, handler: impl FnMut(&mut FakeDesktop, WindowId) + Send + 'static) {
+        self.handlers().any_click = Some(Box::new(handler));
+    }
+
+    /// Calls the handler whenever an item is clicked or selected in a list box or combo box
+    /// without its own handler, replacing any previous handler.
+    pub fn on_any_item_click(
+        &self,
+        handler: impl FnMut(&mut FakeDesktop, WindowId, usize) + Send + 'static,
+    ) {
+        self.handlers().any_item_click = Some(Box::new(handler));
+    }
+
+    /// Every action performed on this backend, oldest first.
+    pub fn actions(&self) -> Vec<FakeAction> {
+        self.lock().actions.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FakeDesktop> {
+        self.desktop.lock().expect("Fake desktop lock poisoned")
+    }
+
+    fn handlers(&self) -> MutexGuard<'_, Handlers> {
+        self.handlers.lock().expect("Fake handlers lock poisoned")
+    }
+
+    /// Reads a property of an existing window.
+    fn read<T: Default>(&self, id: WindowId, func: impl FnOnce(&FakeWindow) -> T) -> T {
+        self.lock().window(id).map(func).unwrap_or_default()
+    }
+}
+
+impl Handlers {
+    fn click(&mut self, id: WindowId) -> Option<&mut ClickHandler> {
+        self.click.get_mut(&id).or(self.any_click.as_mut())
+    }
+
+    fn item_click(&mut self, id: WindowId) -> Option<&mut ItemHandler> {
+        self.item_click
+            .get_mut(&id)
+            .or(self.any_item_click.as_mut())
+    }
+}
+
+impl Backend for FakeBackend {
+    fn find_window(&self, title: &str) -> Option<WindowId> {
+        let desktop = self.lock();
+        desktop.roots.iter().copied().find(|id| {
+            desktop
+                .window(*id)
+                .is_some_and(|window| window.text == title)
+        })
+    }
+
+    fn children(&self, parent: WindowId) -> Vec<WindowId> {
+        self.read(parent, |window| window.children.clone())
+    }
+
+    /// Every window of a fake desktop belongs to the same process.
+    fn process_windows(&self, window: WindowId) -> Vec<WindowId> {
+        let desktop = self.lock();
+        desktop
+            .roots
+            .iter()
+            .copied()
+            .filter(|root| *root != window)
+            .collect()
+    }
+
+    fn text(&self, id: WindowId) -> String {
+        self.read(id, |window| window.text.clone())
+    }
+
+    fn class_name(&self, id: WindowId) -> String {
+        self.read(id, |window| window.class_name.clone())
+    }
+
+    fn control_id(&self, id: WindowId) -> i32 {
+        self.read(id, |window| window.control_id)
+    }
+
+    fn rect(&self, id: WindowId) -> Option<Rect> {
+        self.lock().window(id).map(|window| window.rect)
+    }
+
+    fn set_text(&self, id: WindowId, text: &str) {
+        let mut desktop = self.desktop();
+        desktop
+            .actions
+            .push(FakeAction::SetText(id, text.to_owned()));
+        desktop.set_text(id, text);
+    }
+
+    fn click(&self, id: WindowId) {
+        let mut handlers = self.handlers();
+        let mut desktop = self.desktop();
+        desktop.actions.push(FakeAction::Click(id));
+
+        if desktop.window(id).is_some()
+            && let Some(handler) = handlers.click(id)
+        {
+            handler(&mut desktop, id);
+        }
+    }
+
+    fn scroll(&self, id: WindowId, delta: i32) {
+        self.lock().actions.push(FakeAction::Scroll(id, delta));
+    }
+
+    fn click_at(&self, id: WindowId, position: (i32, i32)) {
+        let mut handlers = self.handlers();
+        let mut desktop = self.desktop();
+        desktop.actions.push(FakeAction::ClickAt(id, position));
+
+        let item = desktop
+            .window(id)
+            .and_then(|window| window.item_at(position));
+        if let Some(index) = item
+            && let Some(handler) = handlers.item_click(id)
+        {
+            handler(&mut desktop, id, index);
+        }
+    }
+
+    fn list_items(&self, id: WindowId) -> Vec<String> {
+        self.read(id, |window| window.items.clone())
+    }
+
+    fn scroll_list_to(&self, id: WindowId, index: usize) {
+        let mut desktop = self.desktop();
+        if let Some(window) = desktop.window_mut(id) {
+            window.top_index = index.min(window.items.len().saturating_sub(1));
+        }
+    }
+
+    fn list_item_rect(&self, id: WindowId, index: usize) -> Option<Rect> {
+        self.lock().window(id)?.item_rect(index)
+    }
+
+    fn list_item_at(&self, id: WindowId, position: (i32, i32)) -> Option<usize> {
+        self.lock().window(id)?.item_at(position)
+    }
+
+    fn combo_items(&self, id: WindowId) -> Vec<String> {
+        self.read(id, |window| window.items.clone())
+    }
+
+    fn selected_combo_item(&self, id: WindowId) -> Option<usize> {
+        self.read(id, |window| window.selected)
+    }
+
+    fn select_combo_item(&self, id: WindowId, index: usize) {
+        let mut handlers = self.handlers();
+        let mut desktop = self.desktop();
+        desktop.actions.push(FakeAction::Select(id, index));
+
+        let Some(window) = desktop.window_mut(id) else {
+            return;
+        };
+        if index >= window.items.len() {
+            return;
+        }
+        window.selected = Some(index);
+        if let Some(handler) = handlers.item_click(id) {
+            handler(&mut desktop, id, index);
+        }
+    }
+
+    /// The colour of the list box item at the position, if it has one.
+    fn pixel(&self, id: WindowId, position: (i32, i32)) -> Option<u32> {
+        let desktop = self.lock();
+        let window = desktop.window(id)?;
+        window.item_colours.get(window.item_at(position)?).copied()
+    }
+
+    fn changes(&self) -> Option<&ChangeSignal> {
+        Some(&self.changes)
+    }
+}
+
+/// Access to a [`FakeDesktop`] that notifies waiters of any change when dropped.
+pub struct DesktopGuard<'a> {
+    desktop: MutexGuard<'a, FakeDesktop>,
+    changes: &'a ChangeSignal,
+    changed: bool,
+}
+
+impl Deref for DesktopGuard<'_> {
+    type Target = FakeDesktop;
+
+    fn deref(&self) -> &Self::Target {
+        &self.desktop
+    }
+}
+
+impl DerefMut for DesktopGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.changed = true;
+        &mut self.desktop
+    }
+}
+
+impl Drop for DesktopGuard<'_> {
+    fn drop(&mut self) {
+        if self.changed {
+            self.changes.notify();
+        }
+    }
+}
+
+/// An action performed on a [`FakeBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FakeAction {
+    Click(WindowId),
+    Scroll(WindowId, i32),
+    ClickAt(WindowId, (i32, i32)),
+    Select(WindowId, usize),
+    SetText(WindowId, String),
+}
+
+/// A fake window.
+#[derive(Debug, Default, Clone)]
+pub struct FakeWindow {
+    pub text: String,
+    pub class_name: String,
+    pub control_id: i32,
+    pub rect: Rect,
+    /// The items when the window is a list box or combo box.
+    pub items: Vec<String>,
+    /// The selected item when the window is a combo box.
+    pub selected: Option<usize>,
+    /// The index of the first visible item.
+    pub top_index: usize,
+    /// The colour each item is drawn in, as `0xRRGGBB`.
+    pub item_colours: Vec<u32>,
+
+    parent: Option<WindowId>,
+    children: Vec<WindowId>,
+}
+
+impl FakeWindow {
+    pub fn new(class_name: impl Into<String>) -> Self {
+        Self {
+            class_name: class_name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    pub fn control_id(mut self, control_id: i32) -> Self {
+        self.control_id = control_id;
+        self
+    }
+
+    pub fn rect(mut self, rect: Rect) -> Self {
+        self.rect = rect;
+        self
+    }
+
+    pub fn items(mut self, items: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.items = items.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn parent(&self) -> Option<WindowId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[WindowId] {
+        &self.children
+    }
+
+    /// Items are stacked from the top of the window, starting with the first visible item.
+    fn item_rect(&self, index: usize) -> Option<Rect> {
+        if index >= self.items.len() {
+            return None;
+        }
+
+        let top = self.rect.top + (index as i32 - self.top_index as i32) * ITEM_HEIGHT;
+        Some(Rect::new(
+            self.rect.left,
+            top,
+            self.rect.right,
+            top + ITEM_HEIGHT,
+        ))
+    }
+
+    fn item_at(&self, position: (i32, i32)) -> Option<usize> {
+        if !self.rect.contains(position) {
+            return None;
+        }
+
+        (0..self.items.len()).find(|index| {
+            self.item_rect(*index)
+                .is_some_and(|rect| rect.contains(position))
+        })
+    }
+}
+
+/// The windows of a [`FakeBackend`].
+#[derive(Debug, Default)]
+pub struct FakeDesktop {
+    /// Destroyed windows are kept as `None` so IDs are never reused.
+    windows: Vec<Option<FakeWindow>>,
+    roots: Vec<WindowId>,
+    actions: Vec<FakeAction>,
+}
+
+impl FakeDesktop {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a top level window.
+    pub fn add_root(&mut self, window: FakeWindow) -> WindowId {
+        let id = self.insert(window, None);
+        self.roots.push(id);
+        id
+    }
+
+    /// Adds a window as the last child of parent.
+    ///
+    /// # Panics
+    /// If the parent window does not exist.
+    pub fn add_child(&mut self, parent: WindowId, window: FakeWindow) -> WindowId {
+        let id = self.insert(window, Some(parent));
+        self.window_mut(parent)
+            .expect("Parent window does not exist")
+            .children
+            .push(id);
+        id
+    }
+
+    /// Destroys a window and all of its children.
+    pub fn remove(&mut self, id: WindowId) {
+        let Some(window) = self.windows.get_mut(id.0).and_then(Option::take) else {
+            return;
+        };
+
+        match window.parent {
+            Some(parent) => {
+                if let Some(parent) = self.window_mut(parent) {
+                    parent.children.retain(|child| *child != id);
+                }
+            }
+            None => self.roots.retain(|root| *root != id),
+        }
+
+        for child in window.children {
+            self.remove(child);
+        }
+    }
+
+    pub fn window(&self, id: WindowId) -> Option<&FakeWindow> {
+        self.windows.get(id.0).and_then(Option::as_ref)
+    }
+
+    pub fn window_mut(&mut self, id: WindowId) -> Option<&mut FakeWindow> {
+        self.windows.get_mut(id.0).and_then(Option::as_mut)
+    }
+
+    /// Sets the text of a window, if it exists.
+    pub fn set_text(&mut self, id: WindowId, text: impl Into<String>) {
+        if let Some(window) = self.window_mut(id) {
+            window.text = text.into();
+        }
+    }
+
+    fn insert(&mut self, mut window: FakeWindow, parent: Option<WindowId>) -> WindowId {
+        let id = WindowId(self.windows.len());
+        window.parent = parent;
+        window.children.clear();
+        self.windows.push(Some(window));
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// A window with a button and a list box of three items.
+    fn backend() -> (FakeBackend, WindowId, WindowId, WindowId) {
+        let mut desktop = FakeDesktop::new();
+        let root = desktop.add_root(FakeWindow::new("Root").text("Worms Armageddon"));
+        let button = desktop.add_child(root, FakeWindow::new("Button").text("Play"));
+        let list = desktop.add_child(
+            root,
+            FakeWindow::new("ListBox")
+                .rect(Rect::new(0, 0, 100, 2 * ITEM_HEIGHT))
+                .items(["Red", "Blue", "Green"]),
+        );
+        (FakeBackend::new(desktop), root, button, list)
+    }
+
+    #[test]
+    fn reads_the_tree() {
+        let (backend, root, button, list) = backend();
+        assert_eq!(backend.find_window("Worms Armageddon"), Some(root));
+        assert_eq!(backend.find_window("Play"), None);
+        assert_eq!(backend.children(root), [button, list]);
+        assert_eq!(backend.text(button), "Play");
+        assert_eq!(backend.class_name(list), "ListBox");
+        assert!(backend.exists(button));
+    }
+
+    #[test]
+    fn sets_text() {
+        let (backend, _, button, _) = backend();
+        backend.set_text(button, "Stop");
+        assert_eq!(backend.text(button), "Stop");
+        assert_eq!(
+            backend.actions(),
+            [FakeAction::SetText(button, "Stop".to_owned())]
+        );
+    }
+
+    #[test]
+    fn click_calls_the_window_handler() {
+        let (backend, root, button, list) = backend();
+        backend.on_click(button, |desktop, id| desktop.set_text(id, "Clicked"));
+        let others = Arc::new(Mutex::new(Vec::new()));
+        let clicked = others.clone();
+        backend.on_any_click(move |_, id| clicked.lock().unwrap().push(id));
+
+        backend.click(button);
+        backend.click(list);
+        assert_eq!(backend.text(button), "Clicked");
+        assert_eq!(*others.lock().unwrap(), [list]);
+
+        backend.desktop().remove(root);
+        backend.click(list);
+        assert_eq!(*others.lock().unwrap(), [list]);
+        assert_eq!(
+            backend.actions(),
+            [
+                FakeAction::Click(button),
+                FakeAction::Click(list),
+                FakeAction::Click(list)
+            ]
+        );
+    }
+
+    #[test]
+    fn handlers_can_change_the_tree() {
+        let (backend, root, button, _) = backend();
+        backend.on_click(button, move |desktop, id| {
+            desktop.remove(id);
+            desktop.add_child(root, FakeWindow::new("Button").text("Again"));
+        });
+
+        backend.click(button);
+        assert!(!backend.exists(button));
+        let added = *backend.children(root).last().unwrap();
+        assert_ne!(added, button);
+        assert_eq!(backend.text(added), "Again");
+    }
+
+    #[test]
+    fn lists_items_by_position() {
+        let (backend, _, _, list) = backend();
+        assert_eq!(backend.list_items(list), ["Red", "Blue", "Green"]);
+        assert_eq!(
+            backend.list_item_rect(list, 1),
+            Some(Rect::new(0, ITEM_HEIGHT, 100, 2 * ITEM_HEIGHT))
+        );
+        assert_eq!(backend.list_item_at(list, (50, ITEM_HEIGHT + 1)), Some(1));
+        // The third item is below the bottom of the list until it is scrolled to
+        assert_eq!(backend.list_item_at(list, (50, 2 * ITEM_HEIGHT + 8)), None);
+
+        backend.scroll_list_to(list, 2);
+        assert_eq!(
+            backend.list_item_rect(list, 2),
+            Some(Rect::new(0, 0, 100, ITEM_HEIGHT))
+        );
+        assert_eq!(backend.list_item_at(list, (50, 8)), Some(2));
+        assert_eq!(backend.list_item_rect(list, 3), None);
+
+        backend.scroll_list_to(list, 10);
+        assert_eq!(backend.list_item_at(list, (50, 8)), Some(2));
+    }
+
+    #[test]
+    fn clicking_an_item_calls_the_item_handler() {
+        let (backend, _, _, list) = backend();
+        let clicked = Arc::new(Mutex::new(Vec::new()));
+        let items = clicked.clone();
+        backend.on_item_click(list, move |_, _, index| items.lock().unwrap().push(index));
+
+        backend.click_at(list, (50, ITEM_HEIGHT + 8));
+        backend.click_at(list, (500, 8));
+        assert_eq!(*clicked.lock().unwrap(), [1]);
+        assert_eq!(
+            backend.actions(),
+            [
+                FakeAction::ClickAt(list, (50, ITEM_HEIGHT + 8)),
+                FakeAction::ClickAt(list, (500, 8))
+            ]
+        );
+    }
+
+    #[test]
+    fn item_colours_are_the_pixels() {
+        let (backend, _, _, list) = backend();
+        backend.desktop().window_mut(list).unwrap().item_colours = vec![0xFF0000, 0x0000FF];
+        assert_eq!(backend.pixel(list, (4, 8)), Some(0xFF0000));
+        assert_eq!(backend.pixel(list, (4, ITEM_HEIGHT + 8)), Some(0x0000FF));
+        assert_eq!(backend.pixel(list, (500, 8)), None);
+    }
+
+    #[test]
+    fn selects_combo_items() {
+        let mut desktop = FakeDesktop::new();
+        let root = desktop.add_root(FakeWindow::new("Root"));
+        let combo = desktop.add_child(root, FakeWindow::new("ComboBox").items(["A", "B"]));
+        let backend = FakeBackend::new(desktop);
+        let selected = Arc::new(Mutex::new(Vec::new()));
+        let items = selected.clone();
+        backend.on_any_item_click(move |_, id, index| items.lock().unwrap().push((id, index)));
+
+        assert_eq!(backend.combo_items(combo), ["A", "B"]);
+        assert_eq!(backend.selected_combo_item(combo), None);
+        backend.select_combo_item(combo, 1);
+        assert_eq!(backend.selected_combo_item(combo), Some(1));
+        backend.select_combo_item(combo, 2);
+        assert_eq!(backend.selected_combo_item(combo), Some(1));
+        assert_eq!(*selected.lock().unwrap(), [(combo, 1)]);
+        assert_eq!(
+            backend.actions(),
+            [FakeAction::Select(combo, 1), FakeAction::Select(combo, 2)]
+        );
+    }
+
+    #[test]
+    fn removing_destroys_the_children() {
+        let (backend, root, button, list) = backend();
+        let mut desktop = backend.desktop();
+        desktop.remove(root);
+        assert!(desktop.window(button).is_none());
+        assert!(desktop.window(list).is_none());
+
+        let added = desktop.add_root(FakeWindow::new("Root"));
+        assert!(added.0 > list.0);
+        drop(desktop);
+        assert_eq!(backend.find_window("Worms Armageddon"), None);
+    }
+
+    #[test]
+    fn changing_the_desktop_signals_a_change() {
+        let (backend, _, button, _) = backend();
+        let changes = backend.changes().unwrap();
+        let seen = changes.generation();
+
+        drop(backend.desktop());
+        assert_eq!(changes.generation(), seen);
+        backend.desktop().set_text(button, "Changed");
+        assert_eq!(changes.generation(), seen + 1);
+        backend.click(button);
+        assert_eq!(changes.generation(), seen + 2);
+    }
+}
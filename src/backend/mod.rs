@@ -0,0 +1,130 @@
+//! Abstracts over the window system so the automation can run against a fake window tree.
+
+use serde::{Deserialize, Serialize};
+
+use crate::events::ChangeSignal;
+
+pub mod fake;
+#[cfg(windows)]
+pub mod win32;
+
+/// The amount one notch of the mouse wheel scrolls by.
+pub const WHEEL_DELTA: i32 = 120;
+
+/// Identifies a window within a [`Backend`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowId(pub usize);
+
+/// The position of a window in screen coordinates.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    pub fn new(left: i32, top: i32, right: i32, bottom: i32) -> Self {
+        Self {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+
+    /// The point in the middle of the rect.
+    pub fn center(&self) -> (i32, i32) {
+        (self.left + self.width() / 2, self.top + self.height() / 2)
+    }
+
+    /// Whether the point is strictly inside the rect.
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        self.bottom > y && self.top < y && self.left < x && self.right > x
+    }
+}
+
+/// The window system operations used by the automation.
+pub trait Backend: Send + Sync {
+    /// Finds the top level window with the given title.
+    fn find_window(&self, title: &str) -> Option<WindowId>;
+
+    /// Gets the direct children of a window, in z-order.
+    fn children(&self, parent: WindowId) -> Vec<WindowId>;
+
+    /// The other visible top level windows of the process that owns the window, such as the
+    /// dialogs it has open. Dialogs aren't children of the window that opened them.
+    fn process_windows(&self, window: WindowId) -> Vec<WindowId>;
+
+    /// Gets the text attribute for a window.
+    fn text(&self, id: WindowId) -> String;
+
+    /// Gets the registered class name for a window.
+    fn class_name(&self, id: WindowId) -> String;
+
+    /// Gets the control ID for a window, or `0` if it does not have one.
+    fn control_id(&self, id: WindowId) -> i32;
+
+    /// Gets the position of a window, or `None` if it no longer exists.
+    fn rect(&self, id: WindowId) -> Option<Rect>;
+
+    /// Whether the window still exists.
+    fn exists(&self, id: WindowId) -> bool {
+        self.rect(id).is_some()
+    }
+
+    /// Replaces the text of a window, such as an edit box, notifying its parent of the change.
+    fn set_text(&self, id: WindowId, text: &str);
+
+    /// Performs a left click on a window without moving the cursor.
+    fn click(&self, id: WindowId);
+
+    /// Scrolls the mouse wheel over a window. Negative values scroll down.
+    /// See [`WHEEL_DELTA`] for the size of one notch.
+    fn scroll(&self, id: WindowId, delta: i32);
+
+    /// Performs a left click at a position in screen coordinates within a window,
+    /// without moving the cursor.
+    fn click_at(&self, id: WindowId, position: (i32, i32));
+
+    /// Gets the text of each item of a list box, or nothing if the window isn't one.
+    fn list_items(&self, id: WindowId) -> Vec<String>;
+
+    /// Scrolls a list box so the item at the index is the first visible item.
+    fn scroll_list_to(&self, id: WindowId, index: usize);
+
+    /// Gets the position of a list box item in screen coordinates, or `None` if there is no such item.
+    /// Items scrolled out of view are positioned outside of the list box.
+    fn list_item_rect(&self, id: WindowId, index: usize) -> Option<Rect>;
+
+    /// Gets the index of the visible list box item at a position in screen coordinates.
+    fn list_item_at(&self, id: WindowId, position: (i32, i32)) -> Option<usize>;
+
+    /// Gets the text of each item of a combo box, or nothing if the window isn't one.
+    fn combo_items(&self, id: WindowId) -> Vec<String>;
+
+    /// Gets the index of the selected combo box item, or `None` if nothing is selected.
+    fn selected_combo_item(&self, id: WindowId) -> Option<usize>;
+
+    /// Selects a combo box item and notifies its parent, as if the user had picked it.
+    fn select_combo_item(&self, id: WindowId, index: usize);
+
+    /// Gets the colour shown at a position in screen coordinates within a window, as `0xRRGGBB`.
+    /// `None` if it can't be read, such as when the position is outside of the window.
+    fn pixel(&self, id: WindowId, position: (i32, i32)) -> Option<u32>;
+
+    /// Notified whenever windows are created, destroyed, shown, hidden or have their text changed.
+    /// `None` if the backend can't report changes, in which case the windows have to be polled.
+    fn changes(&self) -> Option<&ChangeSignal> {
+        None
+    }
+}
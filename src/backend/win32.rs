@@ -0,0 +1,414 @@
+use std::{
+    ffi::CString,
+    sync::{OnceLock, mpsc},
+};
+
+use windows::{
+    Win32::{
+        Foundation::{HWND, LPARAM, POINT, RECT, WPARAM},
+        Graphics::Gdi::{CLR_INVALID, GetDC, GetPixel, MapWindowPoints, ReleaseDC},
+        System::{SystemServices::MK_LBUTTON, Threading::GetCurrentProcessId},
+        UI::{
+            Accessibility::{HWINEVENTHOOK, SetWinEventHook, UnhookWinEvent},
+            WindowsAndMessaging::{
+                CB_ERR, CB_GETCOUNT, CB_GETCURSEL, CB_GETLBTEXT, CB_GETLBTEXTLEN, CB_SETCURSEL,
+                CBN_SELCHANGE, CHILDID_SELF, DispatchMessageA, EVENT_OBJECT_CREATE,
+                EVENT_OBJECT_HIDE, EVENT_OBJECT_NAMECHANGE, EnumWindows, FindWindowA,
+                FindWindowExA, GetClassNameA, GetDlgCtrlID, GetMessageA, GetParent, GetWindowRect,
+                GetWindowTextA, GetWindowTextLengthA, GetWindowThreadProcessId, IsWindow,
+                IsWindowVisible, LB_ERR, LB_GETCOUNT, LB_GETITEMRECT, LB_GETTEXT, LB_GETTEXTLEN,
+                LB_ITEMFROMPOINT, LB_SETTOPINDEX, MSG, OBJID_WINDOW, SendMessageA,
+                WINEVENT_OUTOFCONTEXT, WM_COMMAND, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEWHEEL,
+                WM_SETTEXT,
+            },
+        },
+    },
+    core::{BOOL, PCSTR},
+};
+
+use super::{Backend, Rect, WindowId};
+use crate::events::ChangeSignal;
+
+/// Notified by the win event hooks. `None` if the hooks could not be set.
+static WIN_EVENTS: OnceLock<Option<ChangeSignal>> = OnceLock::new();
+
+/// Performs all operations on the real windows of this desktop.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Win32Backend;
+
+impl From<HWND> for WindowId {
+    fn from(value: HWND) -> Self {
+        Self(value.0 as usize)
+    }
+}
+
+impl From<WindowId> for HWND {
+    fn from(value: WindowId) -> Self {
+        HWND(value.0 as *mut _)
+    }
+}
+
+impl From<RECT> for Rect {
+    fn from(value: RECT) -> Self {
+        Self::new(value.left, value.top, value.right, value.bottom)
+    }
+}
+
+impl Backend for Win32Backend {
+    fn find_window(&self, title: &str) -> Option<WindowId> {
+        let title = CString::new(title).ok()?;
+        unsafe { FindWindowA(None, PCSTR(title.as_ptr() as *const u8)) }
+            .ok()
+            .map(WindowId::from)
+    }
+
+    fn children(&self, parent: WindowId) -> Vec<WindowId> {
+        let mut children = Vec::new();
+        let mut child_id = None;
+
+        loop {
+            child_id = unsafe { FindWindowExA(Some(parent.into()), child_id, None, None) }.ok();
+
+            let Some(id) = child_id else {
+                break;
+            };
+            children.push(id.into());
+        }
+
+        children
+    }
+
+    fn process_windows(&self, window: WindowId) -> Vec<WindowId> {
+        let mut found = ProcessWindows {
+            process: 0,
+            owner: window.into(),
+            windows: Vec::new(),
+        };
+        unsafe { GetWindowThreadProcessId(found.owner, Some(&mut found.process)) };
+        if found.process == 0 {
+            return Vec::new();
+        }
+
+        // Only fails if the callback does, which it never does
+        let _ = unsafe {
+            EnumWindows(
+                Some(add_process_window),
+                LPARAM(&mut found as *mut ProcessWindows as isize),
+            )
+        };
+        found.windows
+    }
+
+    /// This will return an empty string if the windows is not part of the current process.
+    fn text(&self, id: WindowId) -> String {
+        let id = HWND::from(id);
+        let min_buff_len = unsafe { GetWindowTextLengthA(id) }.unsigned_abs() /* It doesn't make sense for the text buffer to have a negative length */ as usize
+            + 1; // The given length is 1 indexed
+
+        let mut window_text = vec![0u8; min_buff_len];
+        unsafe { GetWindowTextA(id, window_text.as_mut_slice()) };
+        String::from_utf8_lossy(&window_text)
+            .trim_matches(|c: char| c.is_whitespace() || c == '\0')
+            .to_owned()
+    }
+
+    fn class_name(&self, id: WindowId) -> String {
+        // Class names are limited to 256 characters
+        let mut name = [0u8; 257];
+        let len = unsafe { GetClassNameA(id.into(), &mut name) }.max(0) as usize;
+        String::from_utf8_lossy(&name[..len]).into_owned()
+    }
+
+    fn control_id(&self, id: WindowId) -> i32 {
+        unsafe { GetDlgCtrlID(id.into()) }
+    }
+
+    fn rect(&self, id: WindowId) -> Option<Rect> {
+        let mut place = RECT::default();
+        unsafe { GetWindowRect(id.into(), &mut place as *mut RECT) }.ok()?;
+        Some(place.into())
+    }
+
+    fn exists(&self, id: WindowId) -> bool {
+        unsafe { IsWindow(Some(id.into())) }.as_bool()
+    }
+
+    fn set_text(&self, id: WindowId, text: &str) {
+        // Interior nul bytes can't be sent, so the text is cut off at the first
+        let text = CString::new(text.split('\0').next().unwrap_or_default()).unwrap_or_default();
+        unsafe {
+            SendMessageA(
+                id.into(),
+                WM_SETTEXT,
+                WPARAM(0),
+                LPARAM(text.as_ptr() as isize),
+            )
+        };
+    }
+
+    fn click(&self, id: WindowId) {
+        // SAFETY, I'm passing the expected arguments to the function. The windows API docs did not mention any edge cases that i have to handle
+        unsafe { SendMessageA(id.into(), WM_LBUTTONDOWN, WPARAM(0), LPARAM(0)) };
+        unsafe { SendMessageA(id.into(), WM_LBUTTONUP, WPARAM(0), LPARAM(0)) };
+    }
+
+    fn scroll(&self, id: WindowId, delta: i32) {
+        unsafe {
+            SendMessageA(
+                id.into(),
+                WM_MOUSEWHEEL,
+                // The wheel delta is stored in the high word
+                WPARAM(((delta as i64) << 16) as usize),
+                LPARAM(0),
+            )
+        };
+    }
+
+    fn click_at(&self, id: WindowId, position: (i32, i32)) {
+        let position = to_client(id, position);
+        unsafe {
+            SendMessageA(
+                id.into(),
+                WM_LBUTTONDOWN,
+                WPARAM(MK_LBUTTON.0 as usize),
+                position,
+            );
+            SendMessageA(id.into(), WM_LBUTTONUP, WPARAM(0), position);
+        };
+    }
+
+    fn list_items(&self, id: WindowId) -> Vec<String> {
+        item_texts(id, LB_GETCOUNT, LB_GETTEXTLEN, LB_GETTEXT)
+    }
+
+    fn scroll_list_to(&self, id: WindowId, index: usize) {
+        unsafe { SendMessageA(id.into(), LB_SETTOPINDEX, WPARAM(index), LPARAM(0)) };
+    }
+
+    fn list_item_rect(&self, id: WindowId, index: usize) -> Option<Rect> {
+        let mut place = RECT::default();
+        let result = unsafe {
+            SendMessageA(
+                id.into(),
+                LB_GETITEMRECT,
+                WPARAM(index),
+                LPARAM(&mut place as *mut RECT as isize),
+            )
+        };
+        if result.0 == LB_ERR as isize {
+            return None;
+        }
+
+        // The item is positioned within the list box
+        let mut corners = [
+            POINT {
+                x: place.left,
+                y: place.top,
+            },
+            POINT {
+                x: place.right,
+                y: place.bottom,
+            },
+        ];
+        unsafe { MapWindowPoints(Some(id.into()), None, &mut corners) };
+        Some(Rect::new(
+            corners[0].x,
+            corners[0].y,
+            corners[1].x,
+            corners[1].y,
+        ))
+    }
+
+    fn list_item_at(&self, id: WindowId, position: (i32, i32)) -> Option<usize> {
+        let result = unsafe {
+            SendMessageA(
+                id.into(),
+                LB_ITEMFROMPOINT,
+                WPARAM(0),
+                to_client(id, position),
+            )
+        }
+        .0;
+
+        // The low word is the nearest item, the high word is set if the point is outside of it
+        let outside = (result >> 16) & 0xFFFF != 0;
+        (!outside).then_some((result & 0xFFFF) as usize)
+    }
+
+    fn combo_items(&self, id: WindowId) -> Vec<String> {
+        item_texts(id, CB_GETCOUNT, CB_GETLBTEXTLEN, CB_GETLBTEXT)
+    }
+
+    fn selected_combo_item(&self, id: WindowId) -> Option<usize> {
+        let index = unsafe { SendMessageA(id.into(), CB_GETCURSEL, WPARAM(0), LPARAM(0)) }.0;
+        (index != CB_ERR as isize).then_some(index as usize)
+    }
+
+    fn select_combo_item(&self, id: WindowId, index: usize) {
+        unsafe { SendMessageA(id.into(), CB_SETCURSEL, WPARAM(index), LPARAM(0)) };
+
+        // Setting the selection doesn't notify the parent like picking it does
+        let Ok(parent) = (unsafe { GetParent(id.into()) }) else {
+            return;
+        };
+        let control_id = self.control_id(id) as usize & 0xFFFF;
+        unsafe {
+            SendMessageA(
+                parent,
+                WM_COMMAND,
+                WPARAM(((CBN_SELCHANGE as usize) << 16) | control_id),
+                LPARAM(HWND::from(id).0 as isize),
+            )
+        };
+    }
+
+    fn pixel(&self, id: WindowId, (x, y): (i32, i32)) -> Option<u32> {
+        let [POINT { x, y }] = screen_to_client(id, [POINT { x, y }]);
+
+        let dc = unsafe { GetDC(Some(id.into())) };
+        if dc.is_invalid() {
+            return None;
+        }
+        let colour = unsafe { GetPixel(dc, x, y) }.0;
+        unsafe { ReleaseDC(Some(id.into()), dc) };
+
+        // COLORREF is stored as 0x00BBGGRR
+        (colour != CLR_INVALID).then(|| {
+            let [red, green, blue, _] = colour.to_le_bytes();
+            u32::from_be_bytes([0, red, green, blue])
+        })
+    }
+
+    fn changes(&self) -> Option<&ChangeSignal> {
+        WIN_EVENTS.get_or_init(hook_win_events).as_ref()
+    }
+}
+
+/// Reads the text of every item of a list box or combo box with its messages.
+fn item_texts(id: WindowId, count_msg: u32, len_msg: u32, text_msg: u32) -> Vec<String> {
+    let count = unsafe { SendMessageA(id.into(), count_msg, WPARAM(0), LPARAM(0)) }.0;
+
+    (0..count.max(0) as usize)
+        .map(|index| {
+            let len = unsafe { SendMessageA(id.into(), len_msg, WPARAM(index), LPARAM(0)) }
+                .0
+                .max(0) as usize;
+
+            // Room for the nul terminator
+            let mut text = vec![0u8; len + 1];
+            let len = unsafe {
+                SendMessageA(
+                    id.into(),
+                    text_msg,
+                    WPARAM(index),
+                    LPARAM(text.as_mut_ptr() as isize),
+                )
+            }
+            .0
+            .clamp(0, len as isize) as usize;
+
+            String::from_utf8_lossy(&text[..len]).trim().to_owned()
+        })
+        .collect()
+}
+
+/// Converts a position in screen coordinates to the packed client coordinates of a window,
+/// as used by mouse messages.
+fn to_client(id: WindowId, (x, y): (i32, i32)) -> LPARAM {
+    let [POINT { x, y }] = screen_to_client(id, [POINT { x, y }]);
+    LPARAM((((y as u16 as u32) << 16) | x as u16 as u32) as isize)
+}
+
+/// Converts points in screen coordinates to the client coordinates of a window.
+fn screen_to_client<const N: usize>(id: WindowId, mut points: [POINT; N]) -> [POINT; N] {
+    unsafe { MapWindowPoints(None, Some(id.into()), &mut points) };
+    points
+}
+
+/// Starts a thread that listens for windows of this process changing.
+fn hook_win_events() -> Option<ChangeSignal> {
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::spawn(move || {
+        let process = unsafe { GetCurrentProcessId() };
+        let hook = |min, max| unsafe {
+            SetWinEventHook(
+                min,
+                max,
+                None,
+                Some(on_win_event),
+                process,
+                0,
+                WINEVENT_OUTOFCONTEXT,
+            )
+        };
+
+        // Location changes are excluded as they are sent whenever the cursor moves
+        let hooks = [
+            hook(EVENT_OBJECT_CREATE, EVENT_OBJECT_HIDE),
+            hook(EVENT_OBJECT_NAMECHANGE, EVENT_OBJECT_NAMECHANGE),
+        ];
+
+        let hooked = hooks.iter().all(|hook| !hook.is_invalid());
+        let _ = sender.send(hooked);
+        if !hooked {
+            for hook in hooks.into_iter().filter(|hook| !hook.is_invalid()) {
+                let _ = unsafe { UnhookWinEvent(hook) };
+            }
+            return;
+        }
+
+        // Out of context events are delivered through this thread's message loop
+        let mut message = MSG::default();
+        while unsafe { GetMessageA(&mut message, None, 0, 0) }.as_bool() {
+            unsafe { DispatchMessageA(&message) };
+        }
+    });
+
+    if receiver.recv().unwrap_or(false) {
+        log::info!("Listening for window changes");
+        Some(ChangeSignal::new())
+    } else {
+        log::warn!("Unable to listen for window changes; falling back to polling");
+        None
+    }
+}
+
+/// The windows of a process gathered by [`add_process_window`].
+struct ProcessWindows {
+    process: u32,
+    /// The window the others are wanted for, which is left out.
+    owner: HWND,
+    windows: Vec<WindowId>,
+}
+
+unsafe extern "system" fn add_process_window(id: HWND, found: LPARAM) -> BOOL {
+    // SAFETY, the pointer is to the `ProcessWindows` borrowed for the whole of `EnumWindows`
+    let found = unsafe { &mut *(found.0 as *mut ProcessWindows) };
+
+    let mut process = 0;
+    unsafe { GetWindowThreadProcessId(id, Some(&mut process)) };
+    if process == found.process && id != found.owner && unsafe { IsWindowVisible(id) }.as_bool() {
+        found.windows.push(id.into());
+    }
+    true.into()
+}
+
+unsafe extern "system" fn on_win_event(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    _id: HWND,
+    object: i32,
+    child: i32,
+    _event_thread: u32,
+    _event_time: u32,
+) {
+    // Only changes to whole windows are relevant, not their scroll bars, carets etc.
+    if object != OBJID_WINDOW.0 || child != CHILDID_SELF as i32 {
+        return;
+    }
+
+    if let Some(Some(signal)) = WIN_EVENTS.get() {
+        signal.notify();
+    }
+}
@@ -0,0 +1,54 @@
+//! Writes the report of a results store, so results can be looked at away from the game.
+//!
+//! ```text
+//! worms_report [RESULTS] [REPORT]
+//! ```
+//!
+//! The results file and the report path default to those in the config in the current directory.
+//! The report is written as `REPORT.md` and `REPORT.html`.
+
+use std::process::ExitCode;
+
+use worms_ai_loop::{config::Config, report::Report, store::ResultStore};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() > 2 || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        eprintln!("Usage: worms_report [RESULTS] [REPORT]");
+        return ExitCode::FAILURE;
+    }
+
+    let config = match Config::load_from_dir(".") {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("Unable to load the config: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let results = args
+        .first()
+        .map_or_else(|| config.resolve(&config.results.file), Into::into);
+    let report = args
+        .get(1)
+        .map_or_else(|| config.resolve(&config.results.report), Into::into);
+
+    let records = match ResultStore::new(&results).load() {
+        Ok(records) => records,
+        Err(err) => {
+            eprintln!("Unable to read '{}': {err}", results.display());
+            return ExitCode::FAILURE;
+        }
+    };
+    match Report::new(&records).save(&report) {
+        Ok(paths) => {
+            for path in paths {
+                println!("Wrote {}", path.display());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
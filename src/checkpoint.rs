@@ -0,0 +1,370 @@
+//! Saves the progress of a tournament after every game, so that when WA crashes it carries on from
+//! where it was on the next DLL load rather than starting again from the first game.
+//!
+//! The checkpoint is a single JSON file that is replaced in one step each time it is saved, so a
+//! crash leaves either the old progress or the new. The game being played when the crash happened
+//! is still the tournament's current pairing, so it is simply played again.
+
+use std::{
+    fmt::Display,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    rating::{RatingConfig, Ratings},
+    results::RoundResult,
+    store::StoredResult,
+    tournament::{Tournament, TournamentConfig},
+};
+
+/// The version written to new checkpoints. Checkpoints of other versions aren't resumed.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// The progress of a tournament.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub version: u32,
+    /// Seconds since the unix epoch when it was saved.
+    pub saved_at: u64,
+    /// The settings the tournament was started with. It isn't resumed if they have changed.
+    pub config: TournamentConfig,
+    pub tournament: Tournament,
+    /// The settings the ratings were made with. They are rated again if these have changed.
+    pub rating_config: RatingConfig,
+    pub ratings: Ratings,
+    /// The seed the maps are shuffled from, so the rotation carries on in the same order.
+    pub shuffle_seed: Option<u64>,
+    /// The rounds played in the tournament so far, including undecided ones.
+    pub rounds: u32,
+    /// The number of results stored when the last round counted was played. Any after these were
+    /// stored after the last save, so are recorded again when resuming.
+    pub results: usize,
+}
+
+impl Checkpoint {
+    /// The start of a tournament, with the ratings from the results stored so far.
+    pub fn start(
+        config: &Config,
+        tournament: Tournament,
+        history: &[StoredResult],
+        shuffle_seed: Option<u64>,
+    ) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            saved_at: 0,
+            config: config.tournament.clone(),
+            tournament,
+            rating_config: config.ratings,
+            ratings: Ratings::from_history(&config.ratings, history),
+            shuffle_seed,
+            rounds: 0,
+            results: history.len(),
+        }
+    }
+
+    /// Whether the tournament can carry on from here with the config.
+    pub fn resumes(&self, config: &Config) -> bool {
+        self.version == CHECKPOINT_VERSION
+            && self.config == config.tournament
+            && !self.tournament.is_finished()
+    }
+
+    /// Catches up with the results stored after the checkpoint was saved, and rates every result
+    /// again if the rating settings have changed.
+    pub fn catch_up(&mut self, config: &Config, history: &[StoredResult]) {
+        let rerate = self.rating_config != config.ratings;
+        if rerate {
+            log::info!("The rating settings have changed, rating every result again");
+            self.rating_config = config.ratings;
+            self.ratings = Ratings::from_history(&config.ratings, history);
+        }
+
+        for record in history.iter().skip(self.results) {
+            if !rerate {
+                self.ratings.record(&record.result);
+            }
+            if self.tournament.record(&record.result) {
+                self.rounds += 1;
+            }
+        }
+        self.results = self.results.max(history.len());
+    }
+
+    /// Records a round that was played, with the ratings after it and the number of results in
+    /// the store once it was stored.
+    /// Returns `false` if the round wasn't between the tournament's current pairing, leaving the
+    /// checkpoint as it was so catching up reads the round again.
+    pub fn record(&mut self, result: &RoundResult, ratings: &Ratings, stored: usize) -> bool {
+        if !self.tournament.record(result) {
+            return false;
+        }
+        self.rounds += 1;
+        self.ratings = ratings.clone();
+        self.results = stored;
+        true
+    }
+}
+
+/// Where the checkpoint is saved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckpointFile {
+    path: PathBuf,
+}
+
+impl CheckpointFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The checkpoint that was last saved, or `None` if there isn't one.
+    pub fn load(&self) -> Result<Option<Checkpoint>, CheckpointError> {
+        let text = match std::fs::read_to_string(&self.path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let checkpoint: Checkpoint = serde_json::from_str(&text)?;
+        match checkpoint.version {
+            CHECKPOINT_VERSION => Ok(Some(checkpoint)),
+            version => Err(CheckpointError::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Replaces the checkpoint, syncing it to disk before returning.
+    pub fn save(&self, checkpoint: &mut Checkpoint) -> Result<(), CheckpointError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        checkpoint.saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let temporary = self.path.with_extension("saving");
+        let mut file = File::create(&temporary)?;
+        file.write_all(serde_json::to_string_pretty(checkpoint)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+
+    /// Carries on the tournament saved in the checkpoint, or starts a new one when there is
+    /// nothing to carry on. Returns `None` if no tournament is configured.
+    pub fn resume_or_start(
+        &self,
+        config: &Config,
+        history: &[StoredResult],
+        shuffle_seed: Option<u64>,
+    ) -> Option<Checkpoint> {
+        let tournament = config.tournament.tournament()?;
+
+        let saved = self.load().unwrap_or_else(|err| {
+            log::error!("Unable to read the tournament checkpoint, starting again: {err}");
+            None
+        });
+        match saved {
+            Some(mut checkpoint) if checkpoint.resumes(config) => {
+                checkpoint.catch_up(config, history);
+                log::info!(
+                    "Resuming the tournament after {} rounds and {} games",
+                    checkpoint.rounds,
+                    checkpoint.tournament.matches().len()
+                );
+                Some(checkpoint)
+            }
+            saved => {
+                if saved.is_some() {
+                    log::info!("The saved tournament is over or has changed, starting a new one");
+                }
+                Some(Checkpoint::start(config, tournament, history, shuffle_seed))
+            }
+        }
+    }
+}
+
+/// The reason the checkpoint could not be saved or read.
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    /// The checkpoint was written by a different version of this crate.
+    UnsupportedVersion(u32),
+}
+
+impl Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckpointError::Io(err) => write!(f, "Unable to access the checkpoint: {err}"),
+            CheckpointError::Format(err) => write!(f, "Invalid checkpoint: {err}"),
+            CheckpointError::UnsupportedVersion(version) => write!(
+                f,
+                "Checkpoint version {version} is not supported, the current version is {CHECKPOINT_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl From<std::io::Error> for CheckpointError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for CheckpointError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{result, stored, won},
+        tournament::{Pairing, TournamentFormat},
+    };
+
+    /// A checkpoint file in a directory of its own.
+    fn file(name: &str) -> CheckpointFile {
+        let dir = std::env::temp_dir().join(format!(
+            "worms_ai_loop_checkpoint_{}_{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        CheckpointFile::new(dir.join("tournament.json"))
+    }
+
+    fn config() -> Config {
+        let mut config = Config::default();
+        config.tournament.format = Some(TournamentFormat::RoundRobin);
+        config.tournament.teams = vec!["Alpha".into(), "Bravo".into(), "Cats".into()];
+        config
+    }
+
+    /// Plays the current game of the checkpoint as the next round, storing the result.
+    fn play(checkpoint: &mut Checkpoint, history: &mut Vec<StoredResult>, ratings: &mut Ratings) {
+        let pairing = checkpoint.tournament.current().unwrap().clone();
+        let result = result(history.len() as u32 + 1, &pairing, won(&pairing.first));
+        history.push(stored(&result));
+        ratings.record(&result);
+        assert!(checkpoint.record(&result, ratings, history.len()));
+    }
+
+    #[test]
+    fn resumes_where_it_was_saved() {
+        let config = config();
+        let file = file("resume");
+        let mut history = Vec::new();
+        let mut checkpoint = file.resume_or_start(&config, &history, Some(7)).unwrap();
+        let mut ratings = checkpoint.ratings.clone();
+        play(&mut checkpoint, &mut history, &mut ratings);
+        play(&mut checkpoint, &mut history, &mut ratings);
+        file.save(&mut checkpoint).unwrap();
+
+        assert_eq!(file.load().unwrap().as_ref(), Some(&checkpoint));
+        let resumed = file.resume_or_start(&config, &history, None).unwrap();
+        assert_eq!(resumed, checkpoint);
+        assert_eq!(resumed.rounds, 2);
+        assert_eq!(resumed.results, 2);
+        assert_eq!(resumed.shuffle_seed, Some(7));
+        assert_eq!(resumed.tournament.matches().len(), 2);
+    }
+
+    #[test]
+    fn catches_up_with_rounds_stored_after_the_save() {
+        let config = config();
+        let file = file("catch_up");
+        let mut history = Vec::new();
+        let mut checkpoint = file.resume_or_start(&config, &history, None).unwrap();
+        let mut ratings = checkpoint.ratings.clone();
+        play(&mut checkpoint, &mut history, &mut ratings);
+        file.save(&mut checkpoint).unwrap();
+        // Stored, but WA crashed before the checkpoint was saved
+        play(&mut checkpoint, &mut history, &mut ratings);
+
+        let resumed = file.resume_or_start(&config, &history, None).unwrap();
+        assert_eq!(resumed.tournament, checkpoint.tournament);
+        assert_eq!(resumed.ratings, ratings);
+        assert_eq!(resumed.rounds, 2);
+        assert_eq!(resumed.results, 2);
+    }
+
+    #[test]
+    fn ignores_rounds_outside_the_tournament() {
+        let config = config();
+        let mut checkpoint =
+            Checkpoint::start(&config, config.tournament.tournament().unwrap(), &[], None);
+        let before = checkpoint.clone();
+        let other = Pairing {
+            stage: 1,
+            first: "Alpha".into(),
+            second: "Nobody".into(),
+        };
+        let result = result(1, &other, won(&other.first));
+        let mut ratings = checkpoint.ratings.clone();
+        ratings.record(&result);
+
+        assert!(!checkpoint.record(&result, &ratings, 1));
+        assert_eq!(checkpoint, before);
+
+        // Read again when catching up, without counting
+        checkpoint.catch_up(&config, &[stored(&result)]);
+        assert_eq!(checkpoint.tournament, before.tournament);
+        assert_eq!(checkpoint.rounds, 0);
+        assert_eq!(checkpoint.results, 1);
+        assert_eq!(checkpoint.ratings, ratings);
+    }
+
+    #[test]
+    fn starts_again_when_the_config_changed() {
+        let config = config();
+        let file = file("changed");
+        let mut history = Vec::new();
+        let mut checkpoint = file.resume_or_start(&config, &history, None).unwrap();
+        let mut ratings = checkpoint.ratings.clone();
+        play(&mut checkpoint, &mut history, &mut ratings);
+        file.save(&mut checkpoint).unwrap();
+
+        let mut changed = config.clone();
+        changed.tournament.legs = 1;
+        assert!(checkpoint.resumes(&config));
+        assert!(!checkpoint.resumes(&changed));
+        let started = file.resume_or_start(&changed, &history, None).unwrap();
+        assert_eq!(started.config, changed.tournament);
+        assert_eq!(started.rounds, 0);
+        assert!(started.tournament.matches().is_empty());
+        // Rated from every result stored so far
+        assert_eq!(started.results, 1);
+        assert_eq!(started.ratings, ratings);
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let config = config();
+        let file = file("version");
+        let mut checkpoint =
+            Checkpoint::start(&config, config.tournament.tournament().unwrap(), &[], None);
+        checkpoint.version = CHECKPOINT_VERSION + 1;
+        file.save(&mut checkpoint).unwrap();
+
+        let err = file.load().unwrap_err();
+        assert!(
+            matches!(err, CheckpointError::UnsupportedVersion(version) if version == CHECKPOINT_VERSION + 1)
+        );
+        // Started again rather than failing
+        let started = file.resume_or_start(&config, &[], None).unwrap();
+        assert_eq!(started.version, CHECKPOINT_VERSION);
+    }
+}
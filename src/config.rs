@@ -0,0 +1,945 @@
+//! Settings loaded at runtime from a TOML file next to the DLL, so changing them doesn't need a
+//! rebuild.
+//!
+//! Every setting is optional and keeps its default when missing. Relative paths in the config
+//! are resolved against the directory the config was loaded from.
+//!
+//! ```toml
+//! [automation]
+//! teams = ["Team 17", "Bots"]
+//! rounds = 10
+//! team_size = 1
+//! worms = 4
+//! scheme = "Intermediate"
+//! maps = ["random", { seeded = 1234 }, { file = "Arena" }]
+//!
+//! [automation.options]
+//! turn_time_secs = 45
+//!
+//! [logging]
+//! level = "debug"
+//!
+//! [tournament]
+//! format = "double_elimination"
+//! teams = ["Team 17", "Bots", "Worms", "Cats"]
+//! ```
+
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    automation::{
+        ADD_TEAM_MESSAGE, CREATE_GAME_MENU, DRAW_TEXT, ROUND_RESULTS_TEXT, RoundPlan, Rounds,
+        Timeouts, WINNER_TEXT,
+    },
+    events::{EVENT_FALLBACK_INTERVAL, POLL_INTERVAL},
+    experiment::ExperimentConfig,
+    game::{GameOptions, GameSetup, MapChoice, MapRotation},
+    lobby::{Handicap, Lineup, MAX_WORMS, Teams},
+    rating::RatingConfig,
+    sprt::SprtConfig,
+    tournament::TournamentConfig,
+};
+
+/// The name of the config file, in the same directory as the DLL.
+pub const CONFIG_FILE: &str = "worms_ai_loop.toml";
+
+/// WA doesn't allow more teams in a game.
+const MAX_TEAMS: u8 = 6;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// The config in use, which is the defaults until one is set.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Makes the config the one returned by [`get`].
+/// Returns `false` if one is already in use, as it can't change once read.
+pub fn set(config: Config) -> bool {
+    CONFIG.set(config).is_ok()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub automation: AutomationConfig,
+    pub logging: LoggingConfig,
+    pub analyser: AnalyserConfig,
+    pub results: ResultsConfig,
+    pub ratings: RatingConfig,
+    pub tournament: TournamentConfig,
+    pub sprt: SprtConfig,
+    pub experiment: ExperimentConfig,
+
+    /// The directory relative paths are resolved against.
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutomationConfig {
+    /// The names of the teams added to each game, in order.
+    pub teams: Vec<String>,
+    /// When no teams are named, the number of teams added from the bottom of the list.
+    pub num_teams: u8,
+    /// Allies teams in order by giving them colours, with 1 for every team for itself and 2 for
+    /// 2 vs 2 and so on. The colours are left as they are when missing.
+    pub team_size: Option<usize>,
+    /// The number of worms in every team, or left as it is when missing.
+    pub worms: Option<u8>,
+    /// The handicap of every team, or left as it is when missing.
+    pub handicap: Option<Handicap>,
+    /// The name of the scheme to select, or the selected scheme is kept when missing.
+    pub scheme: Option<String>,
+    /// Applied after selecting the scheme.
+    pub options: GameOptions,
+    /// The maps to play on, one per round in turn, or the current map is kept when empty.
+    pub maps: Vec<MapChoice>,
+    /// Play each pass through the maps in a shuffled order.
+    pub shuffle_maps: bool,
+    /// The seed the maps are shuffled from, so a rotation can be repeated.
+    /// A new seed is picked each time the DLL is loaded when missing.
+    pub shuffle_seed: Option<u64>,
+    /// Remove every team from the lobby before each round and restore the scheme and options, so
+    /// each round starts from a clean, verified lineup.
+    pub reset_lobby: bool,
+    /// The number of rounds to play, or every round until stopped when missing.
+    pub rounds: Option<u32>,
+    /// How many times in a row a round may fail, such as by timing out, before the rounds stop.
+    /// A failed round is played again.
+    pub max_failed_rounds: u32,
+    /// Milliseconds between checks of the windows when the backend can't report changes.
+    pub poll_interval_ms: u64,
+    /// Milliseconds between checks of the windows even when the backend reports changes.
+    pub event_fallback_interval_ms: u64,
+    /// Milliseconds to wait after each change in the lobby, such as adding a team, as WA misses
+    /// clicks that come too fast.
+    pub add_team_delay_ms: u64,
+    /// Pixels from the left of a team in the teams in game list to read its colour from.
+    pub team_colour_offset: i32,
+    pub screen_timeout_secs: u64,
+    pub round_timeout_secs: u64,
+    pub markers: MarkerText,
+}
+
+impl Default for AutomationConfig {
+    fn default() -> Self {
+        let timeouts = Timeouts::default();
+        Self {
+            teams: Vec::new(),
+            num_teams: 2,
+            team_size: None,
+            worms: None,
+            handicap: None,
+            scheme: None,
+            options: GameOptions::default(),
+            maps: Vec::new(),
+            shuffle_maps: false,
+            shuffle_seed: None,
+            reset_lobby: true,
+            rounds: None,
+            max_failed_rounds: 3,
+            poll_interval_ms: POLL_INTERVAL.as_millis() as u64,
+            event_fallback_interval_ms: EVENT_FALLBACK_INTERVAL.as_millis() as u64,
+            add_team_delay_ms: 100,
+            team_colour_offset: 4,
+            screen_timeout_secs: timeouts.screen.as_secs(),
+            round_timeout_secs: timeouts.round.as_secs(),
+            markers: MarkerText::default(),
+        }
+    }
+}
+
+impl AutomationConfig {
+    pub fn teams(&self) -> Teams {
+        match self.teams.is_empty() {
+            true => Teams::Last(self.num_teams),
+            false => Teams::Named(self.teams.iter().map(|name| name.as_str().into()).collect()),
+        }
+    }
+
+    /// The teams with the settings given to every team.
+    pub fn lineup(&self) -> Lineup {
+        self.with_team_settings(match self.team_size {
+            Some(team_size) => Lineup::versus(self.teams(), team_size),
+            None => Lineup::new(self.teams()),
+        })
+    }
+
+    /// Gives every team of the lineup the worms and handicap set for every team.
+    pub fn with_team_settings(&self, mut lineup: Lineup) -> Lineup {
+        if let Some(worms) = self.worms {
+            lineup = lineup.with_worms(worms);
+        }
+        if let Some(handicap) = self.handicap {
+            lineup = lineup.with_handicap(handicap);
+        }
+        lineup
+    }
+
+    /// The lineup, scheme and options of every game.
+    pub fn setup(&self) -> GameSetup {
+        GameSetup {
+            lineup: self.lineup(),
+            scheme: self.scheme.as_deref().map(Into::into),
+            options: self.options,
+            map: None,
+        }
+    }
+
+    /// The rounds to play, with the same setup for each.
+    /// The maps are shuffled from `shuffle_seed` when given, such as to carry on a rotation.
+    pub fn plan(&self, shuffle_seed: Option<u64>) -> RoundPlan {
+        RoundPlan {
+            setup: self.setup(),
+            maps: self.maps(shuffle_seed),
+            rounds: self.rounds(),
+        }
+    }
+
+    /// The maps to play on in turn.
+    /// The maps are shuffled from `shuffle_seed` when given, such as to carry on a rotation.
+    pub fn maps(&self, shuffle_seed: Option<u64>) -> MapRotation {
+        match self.shuffle_maps {
+            true => {
+                let seed = shuffle_seed
+                    .or(self.shuffle_seed)
+                    .unwrap_or_else(|| fastrand::u64(..));
+                log::info!("Shuffling the maps with seed {seed}");
+                MapRotation::shuffled(self.maps.clone(), seed)
+            }
+            false => MapRotation::new(self.maps.clone()),
+        }
+    }
+
+    pub fn rounds(&self) -> Rounds {
+        self.rounds.map_or(Rounds::UntilStopped, Rounds::Count)
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            screen: Duration::from_secs(self.screen_timeout_secs),
+            round: Duration::from_secs(self.round_timeout_secs),
+        }
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+
+    pub fn event_fallback_interval(&self) -> Duration {
+        Duration::from_millis(self.event_fallback_interval_ms)
+    }
+
+    pub fn add_team_delay(&self) -> Duration {
+        Duration::from_millis(self.add_team_delay_ms)
+    }
+}
+
+/// Text shown by WA that screens and windows are recognised by.
+/// These may need changing for other languages or versions of WA.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarkerText {
+    /// The start of the main menu button that opens the multiplayer lobby.
+    pub create_game_menu: String,
+    /// The status bar text while the cursor is over a team that can be added.
+    pub add_team_message: String,
+    /// Part of the title of the round results.
+    pub round_results_text: String,
+    /// The start or end of the round results line naming the winning team, ignoring case.
+    /// The rest of the line is the team's name.
+    pub winner_text: String,
+    /// The whole of the round results line when a round is drawn, ignoring case and closing
+    /// punctuation.
+    pub draw_text: String,
+}
+
+impl Default for MarkerText {
+    fn default() -> Self {
+        Self {
+            create_game_menu: CREATE_GAME_MENU.to_owned(),
+            add_team_message: ADD_TEAM_MESSAGE.to_owned(),
+            round_results_text: ROUND_RESULTS_TEXT.to_owned(),
+            winner_text: WINNER_TEXT.to_owned(),
+            draw_text: DRAW_TEXT.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    pub file: String,
+    /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: "worms_ai_loop.log".to_owned(),
+            level: "info".to_owned(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    /// The level to log at, or `info` if the level isn't valid.
+    pub fn level(&self) -> log::LevelFilter {
+        self.level.parse().unwrap_or(log::LevelFilter::Info)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyserConfig {
+    /// Show the analyser window. When disabled the automation starts straight away instead.
+    pub enabled: bool,
+    /// The directory window snapshots are saved to.
+    pub snapshot_dir: String,
+    /// Milliseconds between redraws of the analyser window.
+    pub repaint_interval_ms: u64,
+}
+
+impl Default for AnalyserConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            snapshot_dir: "snapshots".to_owned(),
+            repaint_interval_ms: 20,
+        }
+    }
+}
+
+impl AnalyserConfig {
+    pub fn repaint_interval(&self) -> Duration {
+        Duration::from_millis(self.repaint_interval_ms)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ResultsConfig {
+    /// The JSON Lines file the result of every round is added to.
+    pub file: String,
+    /// Where reports of the results are written, with `.md` added for Markdown and `.html` for
+    /// HTML.
+    pub report: String,
+}
+
+impl Default for ResultsConfig {
+    fn default() -> Self {
+        Self {
+            file: "results.jsonl".to_owned(),
+            report: "report".to_owned(),
+        }
+    }
+}
+
+impl Config {
+    /// The defaults, resolving relative paths against the directory.
+    pub fn default_in(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Parses and validates a config, resolving relative paths against the directory.
+    pub fn from_toml(text: &str, dir: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let config = Self {
+            dir: dir.into(),
+            ..toml::from_str(text)?
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads [`CONFIG_FILE`] from the directory, using the defaults if there is no such file.
+    pub fn load_from_dir(dir: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let dir = dir.into();
+        match std::fs::read_to_string(dir.join(CONFIG_FILE)) {
+            Ok(text) => Self::from_toml(&text, dir),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default_in(dir)),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Joins a relative path onto the config's directory.
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.dir.join(path)
+    }
+
+    /// Checks every setting is usable, listing each that isn't.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |valid: bool, problem: &str| {
+            if !valid {
+                problems.push(problem.into());
+            }
+        };
+
+        let automation = &self.automation;
+        check(
+            (1..=MAX_TEAMS).contains(&automation.num_teams),
+            &format!("automation.num_teams must be between 1 and {MAX_TEAMS}"),
+        );
+        check(
+            automation.teams.len() <= MAX_TEAMS as usize,
+            &format!("automation.teams must name at most {MAX_TEAMS} teams"),
+        );
+        check(
+            automation.teams.iter().all(|name| !name.trim().is_empty()),
+            "automation.teams must not contain empty names",
+        );
+        // Teams are selected in the lobby by name
+        check(
+            automation.teams.iter().enumerate().all(|(index, name)| {
+                automation.teams[..index]
+                    .iter()
+                    .all(|other| !other.trim().eq_ignore_ascii_case(name.trim()))
+            }),
+            "automation.teams must not name a team more than once",
+        );
+        check(
+            automation
+                .team_size
+                .is_none_or(|size| (1..=MAX_TEAMS as usize).contains(&size)),
+            &format!("automation.team_size must be between 1 and {MAX_TEAMS}"),
+        );
+        check(
+            automation
+                .worms
+                .is_none_or(|worms| (1..=MAX_WORMS).contains(&worms)),
+            &format!("automation.worms must be between 1 and {MAX_WORMS}"),
+        );
+        check(
+            automation
+                .scheme
+                .as_ref()
+                .is_none_or(|scheme| !scheme.trim().is_empty()),
+            "automation.scheme must not be empty",
+        );
+        let options = &automation.options;
+        for (name, value) in [
+            ("turn_time_secs", options.turn_time_secs),
+            ("round_time_mins", options.round_time_mins),
+            ("worm_energy", options.worm_energy),
+        ] {
+            check(
+                value != Some(0),
+                &format!("automation.options.{name} must be at least 1"),
+            );
+        }
+        check(
+            automation.maps.iter().all(|map| match map {
+                MapChoice::File(name) => !name.trim().is_empty(),
+                _ => true,
+            }),
+            "automation.maps must not contain empty map names",
+        );
+        check(
+            automation.rounds != Some(0),
+            "automation.rounds must be at least 1",
+        );
+        check(
+            automation.max_failed_rounds > 0,
+            "automation.max_failed_rounds must be at least 1",
+        );
+        check(
+            automation.poll_interval_ms > 0,
+            "automation.poll_interval_ms must be at least 1",
+        );
+        check(
+            automation.event_fallback_interval_ms > 0,
+            "automation.event_fallback_interval_ms must be at least 1",
+        );
+        check(
+            automation.screen_timeout_secs > 0,
+            "automation.screen_timeout_secs must be at least 1",
+        );
+        check(
+            automation.round_timeout_secs > 0,
+            "automation.round_timeout_secs must be at least 1",
+        );
+
+        let markers = &automation.markers;
+        for (name, text) in [
+            ("create_game_menu", &markers.create_game_menu),
+            ("add_team_message", &markers.add_team_message),
+            ("round_results_text", &markers.round_results_text),
+            ("winner_text", &markers.winner_text),
+            ("draw_text", &markers.draw_text),
+        ] {
+            check(
+                !text.trim().is_empty(),
+                &format!("automation.markers.{name} must not be empty"),
+            );
+        }
+
+        check(
+            !self.logging.file.trim().is_empty(),
+            "logging.file must not be empty",
+        );
+        check(
+            self.logging.level.parse::<log::LevelFilter>().is_ok(),
+            "logging.level must be one of off, error, warn, info, debug or trace",
+        );
+
+        check(
+            !self.analyser.snapshot_dir.trim().is_empty(),
+            "analyser.snapshot_dir must not be empty",
+        );
+        check(
+            self.analyser.repaint_interval_ms > 0,
+            "analyser.repaint_interval_ms must be at least 1",
+        );
+
+        check(
+            !self.results.file.trim().is_empty(),
+            "results.file must not be empty",
+        );
+        check(
+            !self.results.report.trim().is_empty(),
+            "results.report must not be empty",
+        );
+
+        let tournament = &self.tournament;
+        if tournament.format.is_some() {
+            check(
+                tournament.teams.len() >= 2,
+                "tournament.teams must name at least 2 teams",
+            );
+        }
+        check(
+            tournament.teams.iter().all(|name| !name.trim().is_empty()),
+            "tournament.teams must not contain empty names",
+        );
+        check(tournament.legs > 0, "tournament.legs must be at least 1");
+        check(
+            !tournament.checkpoint_file.trim().is_empty(),
+            "tournament.checkpoint_file must not be empty",
+        );
+        check(
+            tournament.swiss_rounds != Some(0),
+            "tournament.swiss_rounds must be at least 1",
+        );
+
+        let sprt = &self.sprt;
+        check(
+            sprt.baseline.is_some() == sprt.candidate.is_some(),
+            "sprt.baseline and sprt.candidate must both be set",
+        );
+        check(
+            [&sprt.baseline, &sprt.candidate]
+                .into_iter()
+                .flatten()
+                .all(|name| !name.trim().is_empty()),
+            "sprt.baseline and sprt.candidate must not be empty",
+        );
+        if let (Some(baseline), Some(candidate)) = (&sprt.baseline, &sprt.candidate) {
+            check(
+                !baseline.trim().eq_ignore_ascii_case(candidate.trim()),
+                "sprt.baseline and sprt.candidate must be different teams",
+            );
+        }
+        check(
+            sprt.elo0.is_finite() && sprt.elo1.is_finite() && sprt.elo0 < sprt.elo1,
+            "sprt.elo0 must be less than sprt.elo1",
+        );
+        check(
+            sprt.alpha > 0.0 && sprt.beta > 0.0 && sprt.alpha + sprt.beta < 1.0,
+            "sprt.alpha and sprt.beta must be more than 0 and add up to less than 1",
+        );
+        check(
+            sprt.max_games != Some(0),
+            "sprt.max_games must be at least 1",
+        );
+
+        check(
+            self.experiment
+                .file
+                .as_ref()
+                .is_none_or(|file| !file.trim().is_empty()),
+            "experiment.file must not be empty",
+        );
+        check(
+            [
+                tournament.format.is_some(),
+                sprt.baseline.is_some(),
+                self.experiment.file.is_some(),
+            ]
+            .into_iter()
+            .filter(|set| *set)
+            .count()
+                <= 1,
+            "only one of a tournament, an SPRT and an experiment can be played",
+        );
+
+        let ratings = &self.ratings;
+        for (name, value) in [
+            ("elo_k", ratings.elo_k),
+            ("glicko_deviation", ratings.glicko_deviation),
+            ("glicko_volatility", ratings.glicko_volatility),
+            ("glicko_tau", ratings.glicko_tau),
+            ("trueskill_deviation", ratings.trueskill_deviation),
+            ("trueskill_beta", ratings.trueskill_beta),
+            ("trueskill_tau", ratings.trueskill_tau),
+        ] {
+            check(
+                value.is_finite() && value > 0.0,
+                &format!("ratings.{name} must be more than 0"),
+            );
+        }
+        check(
+            ratings.initial_rating.is_finite() && ratings.trueskill_mean.is_finite(),
+            "ratings.initial_rating and ratings.trueskill_mean must be numbers",
+        );
+        check(
+            (0.0..1.0).contains(&ratings.draw_probability),
+            "ratings.draw_probability must be at least 0 and less than 1",
+        );
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ConfigError::Invalid(problems)),
+        }
+    }
+}
+
+/// The reason a config could not be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Format(toml::de::Error),
+    /// Each setting that has an unusable value.
+    Invalid(Vec<Box<str>>),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "Unable to read {CONFIG_FILE}: {err}"),
+            ConfigError::Format(err) => write!(f, "Invalid {CONFIG_FILE}: {err}"),
+            ConfigError::Invalid(problems) => {
+                write!(f, "Invalid {CONFIG_FILE}:\n{}", problems.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<std::io::Error> for ConfigError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The problems with the config, or none if it is valid.
+    fn problems(text: &str) -> Vec<String> {
+        match Config::from_toml(text, ".") {
+            Ok(_) => Vec::new(),
+            Err(ConfigError::Invalid(problems)) => {
+                problems.iter().map(|problem| problem.to_string()).collect()
+            }
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    #[test]
+    fn reports_each_invalid_setting() {
+        let cases = [
+            (
+                "automation.num_teams = 0",
+                "automation.num_teams must be between 1 and 6",
+            ),
+            (
+                "automation.num_teams = 7",
+                "automation.num_teams must be between 1 and 6",
+            ),
+            (
+                r#"automation.teams = ["A", "B", "C", "D", "E", "F", "G"]"#,
+                "automation.teams must name at most 6 teams",
+            ),
+            (
+                r#"automation.teams = ["A", " "]"#,
+                "automation.teams must not contain empty names",
+            ),
+            (
+                r#"automation.teams = ["Bots", "Team 17", " bots "]"#,
+                "automation.teams must not name a team more than once",
+            ),
+            (
+                "automation.team_size = 0",
+                "automation.team_size must be between 1 and 6",
+            ),
+            (
+                "automation.team_size = 7",
+                "automation.team_size must be between 1 and 6",
+            ),
+            (
+                "automation.worms = 0",
+                "automation.worms must be between 1 and 8",
+            ),
+            (
+                "automation.worms = 9",
+                "automation.worms must be between 1 and 8",
+            ),
+            (
+                r#"automation.scheme = """#,
+                "automation.scheme must not be empty",
+            ),
+            (
+                "automation.options.turn_time_secs = 0",
+                "automation.options.turn_time_secs must be at least 1",
+            ),
+            (
+                "automation.options.round_time_mins = 0",
+                "automation.options.round_time_mins must be at least 1",
+            ),
+            (
+                "automation.options.worm_energy = 0",
+                "automation.options.worm_energy must be at least 1",
+            ),
+            (
+                r#"automation.maps = ["random", { file = " " }]"#,
+                "automation.maps must not contain empty map names",
+            ),
+            (
+                "automation.rounds = 0",
+                "automation.rounds must be at least 1",
+            ),
+            (
+                "automation.max_failed_rounds = 0",
+                "automation.max_failed_rounds must be at least 1",
+            ),
+            (
+                "automation.poll_interval_ms = 0",
+                "automation.poll_interval_ms must be at least 1",
+            ),
+            (
+                "automation.event_fallback_interval_ms = 0",
+                "automation.event_fallback_interval_ms must be at least 1",
+            ),
+            (
+                "automation.screen_timeout_secs = 0",
+                "automation.screen_timeout_secs must be at least 1",
+            ),
+            (
+                "automation.round_timeout_secs = 0",
+                "automation.round_timeout_secs must be at least 1",
+            ),
+            (
+                r#"automation.markers.create_game_menu = """#,
+                "automation.markers.create_game_menu must not be empty",
+            ),
+            (
+                r#"automation.markers.add_team_message = " ""#,
+                "automation.markers.add_team_message must not be empty",
+            ),
+            (
+                r#"automation.markers.round_results_text = """#,
+                "automation.markers.round_results_text must not be empty",
+            ),
+            (
+                r#"automation.markers.winner_text = """#,
+                "automation.markers.winner_text must not be empty",
+            ),
+            (
+                r#"automation.markers.draw_text = """#,
+                "automation.markers.draw_text must not be empty",
+            ),
+            (r#"logging.file = """#, "logging.file must not be empty"),
+            (
+                r#"logging.level = "loud""#,
+                "logging.level must be one of off, error, warn, info, debug or trace",
+            ),
+            (
+                r#"analyser.snapshot_dir = """#,
+                "analyser.snapshot_dir must not be empty",
+            ),
+            (
+                "analyser.repaint_interval_ms = 0",
+                "analyser.repaint_interval_ms must be at least 1",
+            ),
+            (r#"results.file = """#, "results.file must not be empty"),
+            (r#"results.report = """#, "results.report must not be empty"),
+            (
+                "tournament.format = \"swiss\"\ntournament.teams = [\"Bots\"]",
+                "tournament.teams must name at least 2 teams",
+            ),
+            (
+                r#"tournament.teams = ["Bots", ""]"#,
+                "tournament.teams must not contain empty names",
+            ),
+            ("tournament.legs = 0", "tournament.legs must be at least 1"),
+            (
+                r#"tournament.checkpoint_file = """#,
+                "tournament.checkpoint_file must not be empty",
+            ),
+            (
+                "tournament.swiss_rounds = 0",
+                "tournament.swiss_rounds must be at least 1",
+            ),
+            (
+                r#"sprt.baseline = "Bots""#,
+                "sprt.baseline and sprt.candidate must both be set",
+            ),
+            (
+                "sprt.baseline = \"Bots\"\nsprt.candidate = \" \"",
+                "sprt.baseline and sprt.candidate must not be empty",
+            ),
+            (
+                "sprt.baseline = \"Bots\"\nsprt.candidate = \"BOTS\"",
+                "sprt.baseline and sprt.candidate must be different teams",
+            ),
+            ("sprt.elo0 = 50.0", "sprt.elo0 must be less than sprt.elo1"),
+            ("sprt.elo1 = inf", "sprt.elo0 must be less than sprt.elo1"),
+            (
+                "sprt.alpha = 0.0",
+                "sprt.alpha and sprt.beta must be more than 0 and add up to less than 1",
+            ),
+            (
+                "sprt.alpha = 0.5\nsprt.beta = 0.5",
+                "sprt.alpha and sprt.beta must be more than 0 and add up to less than 1",
+            ),
+            ("sprt.max_games = 0", "sprt.max_games must be at least 1"),
+            (
+                r#"experiment.file = " ""#,
+                "experiment.file must not be empty",
+            ),
+            (
+                "tournament.format = \"swiss\"\ntournament.teams = [\"A\", \"B\"]\n\
+                 experiment.file = \"experiment.toml\"",
+                "only one of a tournament, an SPRT and an experiment can be played",
+            ),
+            ("ratings.elo_k = 0.0", "ratings.elo_k must be more than 0"),
+            (
+                "ratings.glicko_deviation = -1.0",
+                "ratings.glicko_deviation must be more than 0",
+            ),
+            (
+                "ratings.glicko_volatility = 0.0",
+                "ratings.glicko_volatility must be more than 0",
+            ),
+            (
+                "ratings.glicko_tau = nan",
+                "ratings.glicko_tau must be more than 0",
+            ),
+            (
+                "ratings.trueskill_deviation = 0.0",
+                "ratings.trueskill_deviation must be more than 0",
+            ),
+            (
+                "ratings.trueskill_beta = 0.0",
+                "ratings.trueskill_beta must be more than 0",
+            ),
+            (
+                "ratings.trueskill_tau = 0.0",
+                "ratings.trueskill_tau must be more than 0",
+            ),
+            (
+                "ratings.initial_rating = inf",
+                "ratings.initial_rating and ratings.trueskill_mean must be numbers",
+            ),
+            (
+                "ratings.draw_probability = 1.0",
+                "ratings.draw_probability must be at least 0 and less than 1",
+            ),
+        ];
+
+        for (text, problem) in cases {
+            // Dotted keys belong to the root table, so each case can be read on its own
+            assert_eq!(problems(text), [problem], "{text}");
+        }
+    }
+
+    #[test]
+    fn lists_every_problem() {
+        let problems = problems("automation.rounds = 0\nlogging.file = \"\"");
+        assert_eq!(
+            problems,
+            [
+                "automation.rounds must be at least 1",
+                "logging.file must not be empty",
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_the_documented_example() {
+        let text = include_str!("config.rs")
+            .lines()
+            .skip_while(|line| *line != "//! ```toml")
+            .skip(1)
+            .take_while(|line| *line != "//! ```")
+            .map(|line| line.trim_start_matches("//!").trim_start())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let config = Config::from_toml(&text, "dir").unwrap();
+
+        assert_eq!(config.automation.teams, ["Team 17", "Bots"]);
+        assert_eq!(config.automation.rounds, Some(10));
+        assert_eq!(config.automation.options.turn_time_secs, Some(45));
+        assert_eq!(config.logging.level(), log::LevelFilter::Debug);
+        // Everything else keeps its default
+        assert_eq!(config.results, ResultsConfig::default());
+        assert_eq!(
+            config.resolve("results.jsonl"),
+            Path::new("dir").join("results.jsonl")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        for text in [
+            "automation.turns = 3",
+            "[automation.options]\nturn_time = 3",
+            "[extra]",
+        ] {
+            assert!(
+                matches!(Config::from_toml(text, "."), Err(ConfigError::Format(_))),
+                "{text}"
+            );
+        }
+    }
+
+    #[test]
+    fn uses_the_defaults_without_a_file() {
+        let dir = std::env::temp_dir().join(format!("worms_ai_loop_config_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let config = Config::load_from_dir(&dir).unwrap();
+        assert_eq!(config, Config::default_in(&dir));
+        assert_eq!(config.resolve("results.jsonl"), dir.join("results.jsonl"));
+        assert!(config.validate().is_ok());
+
+        std::fs::write(dir.join(CONFIG_FILE), "automation.rounds = 0").unwrap();
+        assert!(matches!(
+            Config::load_from_dir(&dir),
+            Err(ConfigError::Invalid(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
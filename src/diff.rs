@@ -0,0 +1,488 @@
+//! Finds what changed between two captured window trees.
+//!
+//! Windows are paired between the trees by a stable identity where possible, so a window that
+//! moved to a new path is reported as moved rather than removed and added. An identity is the
+//! class name with either the control ID or, for windows without one, the window text.
+//! Windows without a unique identity are paired by their path instead.
+
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{backend::Rect, snapshot::WinData};
+
+/// A single difference between two window trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A window only in the new tree.
+    Added { path: Vec<usize>, name: Box<str> },
+    /// A window only in the old tree.
+    Removed { path: Vec<usize>, name: Box<str> },
+    /// The same window is at a different path.
+    Moved { from: Vec<usize>, to: Vec<usize> },
+    Text {
+        path: Vec<usize>,
+        old: Box<str>,
+        new: Box<str>,
+    },
+    Class {
+        path: Vec<usize>,
+        old: Box<str>,
+        new: Box<str>,
+    },
+    Rect {
+        path: Vec<usize>,
+        old: Rect,
+        new: Rect,
+    },
+}
+
+impl Change {
+    /// The path of the changed window in the old tree, if it exists there.
+    pub fn old_path(&self) -> Option<&[usize]> {
+        match self {
+            Change::Added { .. } => None,
+            Change::Removed { path, .. } => Some(path),
+            Change::Moved { from, .. } => Some(from),
+            // Changed windows are reported at their new path
+            Change::Text { .. } | Change::Class { .. } | Change::Rect { .. } => None,
+        }
+    }
+
+    /// The path of the changed window in the new tree, if it exists there.
+    pub fn new_path(&self) -> Option<&[usize]> {
+        match self {
+            Change::Added { path, .. }
+            | Change::Text { path, .. }
+            | Change::Class { path, .. }
+            | Change::Rect { path, .. } => Some(path),
+            Change::Removed { .. } => None,
+            Change::Moved { to, .. } => Some(to),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added { path, name } => write!(f, "Added '{name}' at {path:?}"),
+            Change::Removed { path, name } => write!(f, "Removed '{name}' from {path:?}"),
+            Change::Moved { from, to } => write!(f, "Moved {from:?} to {to:?}"),
+            Change::Text { path, old, new } => {
+                write!(f, "Text at {path:?} changed from '{old}' to '{new}'")
+            }
+            Change::Class { path, old, new } => {
+                write!(f, "Class at {path:?} changed from '{old}' to '{new}'")
+            }
+            Change::Rect { path, old, new } => {
+                write!(f, "Position at {path:?} changed from {old:?} to {new:?}")
+            }
+        }
+    }
+}
+
+/// All differences between two window trees.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeDiff {
+    pub changes: Vec<Change>,
+}
+
+impl TreeDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The changes involving the window at path in the old tree.
+    pub fn at_old_path<'a>(&'a self, path: &'a [usize]) -> impl Iterator<Item = &'a Change> {
+        self.changes
+            .iter()
+            .filter(move |change| change.old_path() == Some(path))
+    }
+
+    /// The changes involving the window at path in the new tree.
+    pub fn at_new_path<'a>(&'a self, path: &'a [usize]) -> impl Iterator<Item = &'a Change> {
+        self.changes
+            .iter()
+            .filter(move |change| change.new_path() == Some(path))
+    }
+}
+
+/// What makes a window recognisable regardless of where it is in the tree.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Identity<'a> {
+    Control(&'a str, i32),
+    Text(&'a str, &'a str),
+}
+
+impl<'a> Identity<'a> {
+    fn of(data: &'a WinData) -> Option<Self> {
+        if data.control_id != 0 {
+            Some(Identity::Control(&data.name, data.control_id))
+        } else if !data.text.is_empty() {
+            Some(Identity::Text(&data.name, &data.text))
+        } else {
+            None
+        }
+    }
+}
+
+/// Finds the differences between the old and new window trees.
+pub fn diff(old: &WinData, new: &WinData) -> TreeDiff {
+    let old_windows = flatten(old);
+    let new_windows = flatten(new);
+
+    let mut old_paired = vec![false; old_windows.len()];
+    let mut new_paired = vec![false; new_windows.len()];
+    let mut pairs = Vec::new();
+
+    // Pair windows with an identity that is unique in both trees
+    let old_identities = unique_identities(&old_windows);
+    let new_identities = unique_identities(&new_windows);
+    for (identity, old_index) in old_identities.iter() {
+        if let Some(new_index) = new_identities.get(identity) {
+            old_paired[*old_index] = true;
+            new_paired[*new_index] = true;
+            pairs.push((*old_index, *new_index));
+        }
+    }
+
+    // Pair the remaining windows by path
+    let new_paths: HashMap<&[usize], usize> = new_windows
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !new_paired[*index])
+        .map(|(index, data)| (data.path.as_slice(), index))
+        .collect();
+    for (old_index, data) in old_windows.iter().enumerate() {
+        if old_paired[old_index] {
+            continue;
+        }
+        if let Some(new_index) = new_paths.get(data.path.as_slice()) {
+            old_paired[old_index] = true;
+            new_paired[*new_index] = true;
+            pairs.push((old_index, *new_index));
+        }
+    }
+
+    // The identities were paired in hash order
+    pairs.sort_unstable();
+    let mut changes = Vec::new();
+
+    for (old_index, new_index) in pairs {
+        let (old, new) = (old_windows[old_index], new_windows[new_index]);
+
+        if old.path != new.path {
+            changes.push(Change::Moved {
+                from: old.path.clone(),
+                to: new.path.clone(),
+            });
+        }
+        if old.text != new.text {
+            changes.push(Change::Text {
+                path: new.path.clone(),
+                old: old.text.clone(),
+                new: new.text.clone(),
+            });
+        }
+        if old.name != new.name {
+            changes.push(Change::Class {
+                path: new.path.clone(),
+                old: old.name.clone(),
+                new: new.name.clone(),
+            });
+        }
+        if old.info.window != new.info.window {
+            changes.push(Change::Rect {
+                path: new.path.clone(),
+                old: old.info.window,
+                new: new.info.window,
+            });
+        }
+    }
+
+    for (data, _) in old_windows
+        .iter()
+        .zip(old_paired)
+        .filter(|(_, paired)| !paired)
+    {
+        changes.push(Change::Removed {
+            path: data.path.clone(),
+            name: data.name.clone(),
+        });
+    }
+
+    for (data, _) in new_windows
+        .iter()
+        .zip(new_paired)
+        .filter(|(_, paired)| !paired)
+    {
+        changes.push(Change::Added {
+            path: data.path.clone(),
+            name: data.name.clone(),
+        });
+    }
+
+    changes.sort_by(|a, b| {
+        let key = |change: &Change| {
+            change
+                .new_path()
+                .or(change.old_path())
+                .map(<[usize]>::to_vec)
+        };
+        key(a).cmp(&key(b))
+    });
+
+    TreeDiff { changes }
+}
+
+/// Gets every window in the tree, in tree order.
+fn flatten(data: &WinData) -> Vec<&WinData> {
+    let mut windows = vec![data];
+    for child in data.children.iter() {
+        windows.append(&mut flatten(child));
+    }
+    windows
+}
+
+/// Maps each identity that only occurs once to the index of its window.
+fn unique_identities<'a>(windows: &[&'a WinData]) -> HashMap<Identity<'a>, usize> {
+    let mut identities: HashMap<Identity, Option<usize>> = HashMap::new();
+    for (index, data) in windows.iter().enumerate() {
+        if let Some(identity) = Identity::of(data) {
+            identities
+                .entry(identity)
+                .and_modify(|found| *found = None)
+                .or_insert(Some(index));
+        }
+    }
+
+    identities
+        .into_iter()
+        .filter_map(|(identity, index)| Some((identity, index?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snapshot::WinInfo;
+
+    /// A window at the path, placed by its path so windows that move also change position.
+    fn window(name: &str, text: &str, control_id: i32, path: &[usize]) -> WinData {
+        let offset = path
+            .iter()
+            .fold(0, |offset, index| offset * 10 + *index as i32 + 1)
+            * 10;
+        let rect = Rect::new(offset, offset, offset + 100, offset + 20);
+        let info = WinInfo {
+            window: rect,
+            client: rect,
+            ..WinInfo::default()
+        };
+        WinData::new(
+            text,
+            name,
+            name,
+            control_id,
+            info,
+            0,
+            path.len(),
+            path.to_vec(),
+        )
+    }
+
+    fn with_children(mut parent: WinData, children: impl IntoIterator<Item = WinData>) -> WinData {
+        for child in children {
+            parent.add_child(child);
+        }
+        parent
+    }
+
+    /// A menu with a play button, a title and a panel holding an unnamed edit box.
+    fn menu() -> WinData {
+        with_children(
+            window("Root", "Worms Armageddon", 0, &[]),
+            [
+                window("Button", "Play", 1, &[0]),
+                window("Static", "Title", 0, &[1]),
+                with_children(
+                    window("Panel", "", 0, &[2]),
+                    [window("Edit", "", 0, &[2, 0])],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn finds_nothing_in_the_same_tree() {
+        assert!(diff(&menu(), &menu()).is_empty());
+    }
+
+    #[test]
+    fn finds_added_and_removed_windows() {
+        let mut new = menu();
+        new.children[2].children.clear();
+        new.add_child(window("Button", "Exit", 2, &[3]));
+
+        let changes = diff(&menu(), &new).changes;
+        assert_eq!(
+            changes,
+            [
+                Change::Removed {
+                    path: vec![2, 0],
+                    name: "Edit".into()
+                },
+                Change::Added {
+                    path: vec![3],
+                    name: "Button".into()
+                },
+            ]
+        );
+        assert_eq!(
+            diff(&new, &menu()).changes,
+            [
+                Change::Added {
+                    path: vec![2, 0],
+                    name: "Edit".into()
+                },
+                Change::Removed {
+                    path: vec![3],
+                    name: "Button".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_moved_windows_by_identity() {
+        let old = menu();
+        let new = with_children(
+            window("Root", "Worms Armageddon", 0, &[]),
+            [
+                window("Static", "Title", 0, &[0]),
+                window("Button", "Play", 1, &[1]),
+                with_children(
+                    window("Panel", "", 0, &[2]),
+                    [window("Edit", "", 0, &[2, 0])],
+                ),
+            ],
+        );
+
+        let diff = diff(&old, &new);
+        assert_eq!(
+            diff.changes,
+            [
+                Change::Moved {
+                    from: vec![1],
+                    to: vec![0]
+                },
+                Change::Rect {
+                    path: vec![0],
+                    old: old.children[1].info.window,
+                    new: new.children[0].info.window,
+                },
+                Change::Moved {
+                    from: vec![0],
+                    to: vec![1]
+                },
+                Change::Rect {
+                    path: vec![1],
+                    old: old.children[0].info.window,
+                    new: new.children[1].info.window,
+                },
+            ]
+        );
+        assert_eq!(diff.at_old_path(&[0]).count(), 1);
+        assert_eq!(diff.at_new_path(&[0]).count(), 2);
+    }
+
+    #[test]
+    fn finds_text_class_and_rect_changes() {
+        let mut new = menu();
+        new.children[0].info.window = Rect::new(0, 0, 50, 50);
+        // Each loses its identity, so is paired by its path
+        new.children[1].text = "Subtitle".into();
+        new.children[2].children[0].name = "RichEdit".into();
+
+        assert_eq!(
+            diff(&menu(), &new).changes,
+            [
+                Change::Rect {
+                    path: vec![0],
+                    old: menu().children[0].info.window,
+                    new: Rect::new(0, 0, 50, 50),
+                },
+                Change::Text {
+                    path: vec![1],
+                    old: "Title".into(),
+                    new: "Subtitle".into(),
+                },
+                Change::Class {
+                    path: vec![2, 0],
+                    old: "Edit".into(),
+                    new: "RichEdit".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_duplicate_identities_by_path() {
+        let buttons = |texts: &[(&str, &str)]| {
+            with_children(
+                window("Root", "", 0, &[]),
+                texts
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, text))| window(name, text, 0, &[index])),
+            )
+        };
+        let old = buttons(&[("Button", "OK"), ("Button", "OK")]);
+        let new = buttons(&[("Static", "Hi"), ("Button", "OK"), ("Button", "OK")]);
+
+        assert_eq!(
+            diff(&old, &new).changes,
+            [
+                Change::Text {
+                    path: vec![0],
+                    old: "OK".into(),
+                    new: "Hi".into(),
+                },
+                Change::Class {
+                    path: vec![0],
+                    old: "Button".into(),
+                    new: "Static".into(),
+                },
+                Change::Added {
+                    path: vec![2],
+                    name: "Button".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn orders_changes_the_same_every_time() {
+        let tree = |texts: &[&str]| {
+            with_children(
+                window("Root", "", 0, &[]),
+                texts
+                    .iter()
+                    .enumerate()
+                    .map(|(index, text)| window("Button", text, 0, &[index])),
+            )
+        };
+        let names: Vec<String> = (0..20).map(|index| format!("Button {index}")).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let old = tree(&names);
+        let mut reversed = names.clone();
+        reversed.reverse();
+        let new = tree(&reversed);
+
+        let first = diff(&old, &new);
+        assert_eq!(first.changes.len(), 40);
+        let paths: Vec<&[usize]> = first.changes.iter().filter_map(Change::new_path).collect();
+        assert!(paths.is_sorted());
+        for _ in 0..20 {
+            assert_eq!(diff(&old, &new), first);
+        }
+    }
+}
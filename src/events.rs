@@ -0,0 +1,190 @@
+//! Lets waiters sleep until the window tree changes instead of polling it.
+
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    backend::Backend,
+    config,
+    wait::{CancelToken, Deadline},
+};
+
+/// How often to check the windows when the backend can't report changes, unless configured.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often to check the windows even when the backend reports changes,
+/// in case a change happened without an event, unless configured.
+pub const EVENT_FALLBACK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Counts changes to the window tree, waking anything waiting for a change.
+#[derive(Debug, Default)]
+pub struct ChangeSignal {
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl ChangeSignal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that something changed.
+    pub fn notify(&self) {
+        *self.lock() += 1;
+        self.changed.notify_all();
+    }
+
+    /// The number of changes so far.
+    pub fn generation(&self) -> u64 {
+        *self.lock()
+    }
+
+    /// Blocks until there has been a change after the `seen` generation or the timeout elapses.
+    /// Returns the current generation.
+    pub fn wait_for_change(&self, seen: u64, timeout: Duration) -> u64 {
+        let (generation, _) = self
+            .changed
+            .wait_timeout_while(self.lock(), timeout, |generation| *generation == seen)
+            .expect("Change signal lock poisoned");
+        *generation
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, u64> {
+        self.generation.lock().expect("Change signal lock poisoned")
+    }
+}
+
+/// Remembers which changes have been seen, so waiting only wakes for newer changes.
+///
+/// Create it before first checking the windows so changes during the check aren't missed.
+pub struct ChangeWaiter<'a> {
+    signal: Option<&'a ChangeSignal>,
+    seen: u64,
+}
+
+impl<'a> ChangeWaiter<'a> {
+    pub fn new(backend: &'a dyn Backend) -> Self {
+        let signal = backend.changes();
+        Self {
+            signal,
+            seen: signal.map(ChangeSignal::generation).unwrap_or_default(),
+        }
+    }
+
+    /// Blocks until the windows may have changed since the last wait,
+    /// or at most until the deadline or cancellation.
+    pub fn wait(&mut self, deadline: &Deadline, cancel: &CancelToken) {
+        let config = &config::get().automation;
+        let poll_interval = config.poll_interval();
+
+        let Some(signal) = self.signal else {
+            let timeout = deadline
+                .remaining()
+                .map_or(poll_interval, |left| left.min(poll_interval));
+            std::thread::sleep(timeout);
+            return;
+        };
+
+        let timeout = deadline
+            .remaining()
+            .map_or(config.event_fallback_interval(), |left| {
+                left.min(config.event_fallback_interval())
+            });
+        let until = Instant::now() + timeout;
+
+        // Wake up regularly to notice cancellation without waiting for a change
+        loop {
+            let left = until.saturating_duration_since(Instant::now());
+            if left.is_zero() || cancel.is_cancelled() {
+                return;
+            }
+
+            let generation = signal.wait_for_change(self.seen, left.min(poll_interval));
+            if generation != self.seen {
+                self.seen = generation;
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fake::{FakeBackend, FakeDesktop};
+
+    /// Well short of the fallback interval, while leaving room for a slow machine.
+    const PROMPTLY: Duration = Duration::from_millis(500);
+
+    fn backend() -> FakeBackend {
+        FakeBackend::new(FakeDesktop::new())
+    }
+
+    #[test]
+    fn wakes_for_a_change_from_another_thread() {
+        let backend = backend();
+        let signal = backend.changes().unwrap();
+        let mut waiter = ChangeWaiter::new(&backend);
+
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                signal.notify();
+            });
+            waiter.wait(&Deadline::never(), &CancelToken::new());
+        });
+
+        assert!(started.elapsed() < PROMPTLY, "{:?}", started.elapsed());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(waiter.seen, signal.generation());
+    }
+
+    #[test]
+    fn notices_cancellation_while_waiting() {
+        let backend = backend();
+        let mut waiter = ChangeWaiter::new(&backend);
+        let cancel = CancelToken::new();
+
+        let started = Instant::now();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                cancel.cancel();
+            });
+            waiter.wait(&Deadline::never(), &cancel);
+        });
+
+        assert!(started.elapsed() < PROMPTLY, "{:?}", started.elapsed());
+        assert_eq!(waiter.seen, 0);
+    }
+
+    #[test]
+    fn stops_waiting_at_the_deadline() {
+        let backend = backend();
+        let mut waiter = ChangeWaiter::new(&backend);
+
+        let started = Instant::now();
+        waiter.wait(
+            &Deadline::after(Duration::from_millis(20)),
+            &CancelToken::new(),
+        );
+        assert!(started.elapsed() < PROMPTLY, "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn only_wakes_for_newer_changes() {
+        let signal = ChangeSignal::new();
+        signal.notify();
+        let seen = signal.generation();
+
+        assert_eq!(signal.wait_for_change(seen - 1, Duration::ZERO), seen);
+        let started = Instant::now();
+        assert_eq!(
+            signal.wait_for_change(seen, Duration::from_millis(20)),
+            seen
+        );
+        assert!(started.elapsed() >= Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,574 @@
+//! Experiments described in a single TOML file and expanded into a seeded list of matches.
+//!
+//! An experiment sets up its games the same way as `[automation]`, and lists values to try for any
+//! of the parameters under `[sweep]`. Every combination of the swept values is a variant, and each
+//! variant is played `repetitions` times. The same repetition of every variant is on the same map,
+//! so variants are compared on equal terms. The matches are shuffled from the seed, so the same
+//! file and seed always give the same matches in the same order.
+//!
+//! Each result is stored with a [`VariantTag`], so statistics can be grouped by variant.
+//!
+//! ```toml
+//! name = "Turn time"
+//! seed = 1234
+//! repetitions = 20
+//! teams = ["Team 17", "Bots"]
+//! scheme = "Intermediate"
+//! maps = ["random", { file = "Arena" }]
+//!
+//! [sweep]
+//! turn_time_secs = [30, 45, 60]
+//! worms = [3, 4]
+//! ```
+
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    config::{AutomationConfig, Config, ConfigError},
+    game::{GameOptions, GameSetup, MapChoice, MapRotation},
+    lobby::Handicap,
+    store::StoredResult,
+};
+
+/// Where the experiment to play is described.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExperimentConfig {
+    /// The experiment file, or no experiment is played when missing.
+    pub file: Option<String>,
+}
+
+/// An experiment file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Experiment {
+    /// Tags the results, so they can be told apart from other experiments.
+    pub name: String,
+    /// The seed the matches and maps are shuffled from. It must be set, so the matches are the same
+    /// each time the experiment is loaded and the matches already played can be skipped.
+    pub seed: Option<u64>,
+    /// How many games each variant plays.
+    pub repetitions: u32,
+    /// Play the matches in a shuffled order, and each pass through the maps in a shuffled order.
+    pub shuffle: bool,
+    pub teams: Vec<String>,
+    pub team_size: Option<usize>,
+    pub worms: Option<u8>,
+    pub handicap: Option<Handicap>,
+    pub scheme: Option<String>,
+    pub options: GameOptions,
+    pub maps: Vec<MapChoice>,
+    pub sweep: Sweep,
+}
+
+impl Default for Experiment {
+    fn default() -> Self {
+        Self {
+            name: "experiment".to_owned(),
+            seed: None,
+            repetitions: 1,
+            shuffle: true,
+            teams: Vec::new(),
+            team_size: None,
+            worms: None,
+            handicap: None,
+            scheme: None,
+            options: GameOptions::default(),
+            maps: Vec::new(),
+            sweep: Sweep::default(),
+        }
+    }
+}
+
+/// The values to try for each parameter. Parameters without values aren't swept.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Sweep {
+    /// Each a list of team names.
+    pub teams: Vec<Vec<String>>,
+    pub team_size: Vec<usize>,
+    pub worms: Vec<u8>,
+    pub handicap: Vec<Handicap>,
+    pub scheme: Vec<String>,
+    pub turn_time_secs: Vec<u32>,
+    pub round_time_mins: Vec<u32>,
+    pub worm_energy: Vec<u32>,
+    /// Each variant plays every game on its map instead of the experiment's maps.
+    pub map: Vec<MapChoice>,
+}
+
+/// A value of a swept parameter.
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    Teams(Vec<String>),
+    TeamSize(usize),
+    Worms(u8),
+    Handicap(Handicap),
+    Scheme(String),
+    TurnTime(u32),
+    RoundTime(u32),
+    WormEnergy(u32),
+    Map(MapChoice),
+}
+
+impl Change {
+    fn apply(&self, automation: &mut AutomationConfig) {
+        match self {
+            Change::Teams(teams) => automation.teams = teams.clone(),
+            Change::TeamSize(size) => automation.team_size = Some(*size),
+            Change::Worms(worms) => automation.worms = Some(*worms),
+            Change::Handicap(handicap) => automation.handicap = Some(*handicap),
+            Change::Scheme(scheme) => automation.scheme = Some(scheme.clone()),
+            Change::TurnTime(secs) => automation.options.turn_time_secs = Some(*secs),
+            Change::RoundTime(mins) => automation.options.round_time_mins = Some(*mins),
+            Change::WormEnergy(energy) => automation.options.worm_energy = Some(*energy),
+            Change::Map(map) => automation.maps = vec![map.clone()],
+        }
+    }
+
+    fn value(&self) -> Value {
+        let value = match self {
+            Change::Teams(teams) => serde_json::to_value(teams),
+            Change::TeamSize(size) => serde_json::to_value(size),
+            Change::Worms(worms) => serde_json::to_value(worms),
+            Change::Handicap(handicap) => serde_json::to_value(handicap),
+            Change::Scheme(scheme) => serde_json::to_value(scheme),
+            Change::TurnTime(value) | Change::RoundTime(value) | Change::WormEnergy(value) => {
+                serde_json::to_value(value)
+            }
+            Change::Map(map) => serde_json::to_value(map),
+        };
+        value.unwrap_or_default()
+    }
+}
+
+impl Sweep {
+    /// Each swept parameter by name, with a change for each of its values.
+    fn parameters(&self) -> Vec<(&'static str, Vec<Change>)> {
+        fn changes<T: Clone>(values: &[T], change: fn(T) -> Change) -> Vec<Change> {
+            values.iter().cloned().map(change).collect()
+        }
+
+        [
+            ("teams", changes(&self.teams, Change::Teams)),
+            ("team_size", changes(&self.team_size, Change::TeamSize)),
+            ("worms", changes(&self.worms, Change::Worms)),
+            ("handicap", changes(&self.handicap, Change::Handicap)),
+            ("scheme", changes(&self.scheme, Change::Scheme)),
+            (
+                "turn_time_secs",
+                changes(&self.turn_time_secs, Change::TurnTime),
+            ),
+            (
+                "round_time_mins",
+                changes(&self.round_time_mins, Change::RoundTime),
+            ),
+            (
+                "worm_energy",
+                changes(&self.worm_energy, Change::WormEnergy),
+            ),
+            ("map", changes(&self.map, Change::Map)),
+        ]
+        .into_iter()
+        .filter(|(_, changes)| !changes.is_empty())
+        .collect()
+    }
+}
+
+/// A combination of swept values, which is the automation config with the values applied.
+#[derive(Debug, Clone, PartialEq)]
+struct Variant {
+    name: Box<str>,
+    params: BTreeMap<Box<str>, Value>,
+    automation: AutomationConfig,
+}
+
+/// Which experiment, variant and repetition a result came from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantTag {
+    pub experiment: Box<str>,
+    pub seed: u64,
+    /// Names the combination of swept values, such as `turn_time_secs=30 worms=3`, or `base`
+    /// when nothing is swept.
+    pub variant: Box<str>,
+    /// Each swept parameter and its value.
+    pub params: BTreeMap<Box<str>, Value>,
+    /// Counting from `1`.
+    pub repetition: u32,
+}
+
+impl Display for VariantTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} repetition {}",
+            self.experiment, self.variant, self.repetition
+        )
+    }
+}
+
+/// A game of an experiment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperimentMatch {
+    pub tag: VariantTag,
+    pub setup: GameSetup,
+}
+
+impl Experiment {
+    /// Reads and validates an experiment file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ExperimentError> {
+        let experiment: Self = toml::from_str(&std::fs::read_to_string(path)?)?;
+        experiment.validate()?;
+        Ok(experiment)
+    }
+
+    pub fn validate(&self) -> Result<(), ExperimentError> {
+        let mut problems = Vec::new();
+        let mut check = |valid: bool, problem: &str| {
+            if !valid {
+                problems.push(problem.into());
+            }
+        };
+
+        check(!self.name.trim().is_empty(), "name must not be empty");
+        check(self.seed.is_some(), "seed must be set");
+        check(self.repetitions > 0, "repetitions must be at least 1");
+        check(
+            !self.teams.is_empty() || !self.sweep.teams.is_empty(),
+            "teams must name the teams unless they are swept",
+        );
+        check(
+            self.sweep.teams.iter().all(|teams| !teams.is_empty()),
+            "sweep.teams must not contain empty lineups",
+        );
+
+        // Every variant has to be a valid automation config
+        for variant in self.variants(&AutomationConfig::default()) {
+            let config = Config {
+                automation: variant.automation,
+                ..Config::default()
+            };
+            if let Err(ConfigError::Invalid(invalid)) = config.validate() {
+                problems.extend(
+                    invalid
+                        .iter()
+                        .map(|problem| format!("Variant {}: {problem}", variant.name).into()),
+                );
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ExperimentError::Invalid(problems)),
+        }
+    }
+
+    /// The automation config with the experiment's setup, before anything is swept.
+    fn base(&self, automation: &AutomationConfig) -> AutomationConfig {
+        AutomationConfig {
+            teams: self.teams.clone(),
+            team_size: self.team_size,
+            worms: self.worms,
+            handicap: self.handicap,
+            scheme: self.scheme.clone(),
+            options: self.options,
+            maps: self.maps.clone(),
+            shuffle_maps: self.shuffle,
+            ..automation.clone()
+        }
+    }
+
+    /// Every combination of the swept values, changing the earlier parameters slowest.
+    fn variants(&self, automation: &AutomationConfig) -> Vec<Variant> {
+        let mut variants = vec![Variant {
+            name: "base".into(),
+            params: BTreeMap::new(),
+            automation: self.base(automation),
+        }];
+        for (parameter, changes) in self.sweep.parameters() {
+            variants = variants
+                .iter()
+                .flat_map(|variant| {
+                    changes.iter().map(move |change| {
+                        let mut variant = variant.clone();
+                        change.apply(&mut variant.automation);
+                        variant.params.insert(parameter.into(), change.value());
+                        variant
+                    })
+                })
+                .collect();
+        }
+
+        for variant in variants
+            .iter_mut()
+            .filter(|variant| !variant.params.is_empty())
+        {
+            let values: Vec<String> = variant
+                .params
+                .iter()
+                .map(|(parameter, value)| match value {
+                    Value::String(text) => format!("{parameter}={text}"),
+                    value => format!("{parameter}={value}"),
+                })
+                .collect();
+            variant.name = values.join(" ").into();
+        }
+        variants
+    }
+
+    /// Every match of the experiment, in the order to play them.
+    ///
+    /// Settings the experiment doesn't have, such as the timeouts, come from the automation
+    /// config.
+    pub fn matches(&self, automation: &AutomationConfig) -> Vec<ExperimentMatch> {
+        let seed = self.seed.unwrap_or_default();
+        log::info!("Expanding experiment {} with seed {seed}", self.name);
+
+        let variants = self.variants(automation);
+        let maps = |variant: &Variant| match self.shuffle {
+            true => MapRotation::shuffled(variant.automation.maps.clone(), seed),
+            false => MapRotation::new(variant.automation.maps.clone()),
+        };
+        let mut matches = Vec::with_capacity(variants.len() * self.repetitions as usize);
+        for repetition in 1..=self.repetitions {
+            for variant in variants.iter() {
+                let mut setup = variant.automation.setup();
+                setup.map = maps(variant).map(repetition);
+                matches.push(ExperimentMatch {
+                    tag: VariantTag {
+                        experiment: self.name.as_str().into(),
+                        seed,
+                        variant: variant.name.clone(),
+                        params: variant.params.clone(),
+                        repetition,
+                    },
+                    setup,
+                });
+            }
+        }
+
+        if self.shuffle {
+            Rng::with_seed(seed).shuffle(&mut matches);
+        }
+        matches
+    }
+}
+
+/// The matches that have no stored result yet, so a restarted experiment carries on from where it
+/// was.
+pub fn unplayed(matches: Vec<ExperimentMatch>, records: &[StoredResult]) -> Vec<ExperimentMatch> {
+    let played: Vec<&VariantTag> = records
+        .iter()
+        .filter_map(|record| record.variant.as_ref())
+        .collect();
+    matches
+        .into_iter()
+        .filter(|game| !played.contains(&&game.tag))
+        .collect()
+}
+
+/// The stored results of an experiment grouped by variant.
+pub fn by_variant<'a>(
+    records: &'a [StoredResult],
+    experiment: &str,
+) -> BTreeMap<&'a str, Vec<&'a StoredResult>> {
+    let mut variants: BTreeMap<&str, Vec<&StoredResult>> = BTreeMap::new();
+    for record in records {
+        if let Some(tag) = &record.variant
+            && *tag.experiment == *experiment
+        {
+            variants.entry(&tag.variant).or_default().push(record);
+        }
+    }
+    variants
+}
+
+/// The reason an experiment file could not be used.
+#[derive(Debug)]
+pub enum ExperimentError {
+    Io(std::io::Error),
+    Format(toml::de::Error),
+    /// Each setting that has an unusable value.
+    Invalid(Vec<Box<str>>),
+}
+
+impl Display for ExperimentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExperimentError::Io(err) => write!(f, "Unable to read the experiment: {err}"),
+            ExperimentError::Format(err) => write!(f, "Invalid experiment: {err}"),
+            ExperimentError::Invalid(problems) => {
+                write!(f, "Invalid experiment:\n{}", problems.join("\n"))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExperimentError {}
+
+impl From<std::io::Error> for ExperimentError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for ExperimentError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::{
+        test_util::{drawn, result, stored},
+        tournament::Pairing,
+    };
+
+    /// The example from the module docs.
+    fn experiment() -> Experiment {
+        toml::from_str(
+            r#"
+            name = "Turn time"
+            seed = 1234
+            repetitions = 20
+            teams = ["Team 17", "Bots"]
+            scheme = "Intermediate"
+            maps = ["random", { file = "Arena" }]
+
+            [sweep]
+            turn_time_secs = [30, 45, 60]
+            worms = [3, 4]
+            "#,
+        )
+        .unwrap()
+    }
+
+    fn tags(matches: &[ExperimentMatch]) -> Vec<(&str, u32)> {
+        matches
+            .iter()
+            .map(|game| (&*game.tag.variant, game.tag.repetition))
+            .collect()
+    }
+
+    #[test]
+    fn plays_every_variant_each_repetition() {
+        let experiment = experiment();
+        experiment.validate().unwrap();
+        let matches = experiment.matches(&AutomationConfig::default());
+
+        // Three turn times by two worm counts, twenty times each
+        assert_eq!(matches.len(), 3 * 2 * 20);
+        let unique: BTreeSet<(&str, u32)> = tags(&matches).into_iter().collect();
+        assert_eq!(unique.len(), matches.len());
+        let variants: BTreeSet<&str> = unique.iter().map(|(variant, _)| *variant).collect();
+        assert_eq!(
+            variants,
+            BTreeSet::from([
+                "turn_time_secs=30 worms=3",
+                "turn_time_secs=30 worms=4",
+                "turn_time_secs=45 worms=3",
+                "turn_time_secs=45 worms=4",
+                "turn_time_secs=60 worms=3",
+                "turn_time_secs=60 worms=4",
+            ])
+        );
+
+        for game in &matches {
+            let turn_time = game.tag.params["turn_time_secs"].as_u64().unwrap() as u32;
+            assert_eq!(game.setup.options.turn_time_secs, Some(turn_time));
+            assert_eq!(game.setup.scheme.as_deref(), Some("Intermediate"));
+            assert_eq!(game.tag.seed, 1234);
+            // The same repetition of every variant is on the same map
+            let same_repetition = matches
+                .iter()
+                .filter(|other| other.tag.repetition == game.tag.repetition);
+            assert!(
+                same_repetition
+                    .into_iter()
+                    .all(|other| other.setup.map == game.setup.map)
+            );
+        }
+    }
+
+    #[test]
+    fn plays_the_base_setup_when_nothing_is_swept() {
+        let experiment = Experiment {
+            sweep: Sweep::default(),
+            repetitions: 3,
+            shuffle: false,
+            ..experiment()
+        };
+        let matches = experiment.matches(&AutomationConfig::default());
+
+        assert_eq!(tags(&matches), [("base", 1), ("base", 2), ("base", 3)]);
+        assert!(matches.iter().all(|game| game.tag.params.is_empty()));
+    }
+
+    #[test]
+    fn expands_the_same_from_the_same_seed() {
+        let experiment = experiment();
+        let automation = AutomationConfig::default();
+        let matches = experiment.matches(&automation);
+
+        assert_eq!(experiment.matches(&automation), matches);
+        let reseeded = Experiment {
+            seed: Some(4321),
+            ..experiment.clone()
+        }
+        .matches(&automation);
+        assert_ne!(tags(&reseeded), tags(&matches));
+        let mut sorted = tags(&reseeded);
+        sorted.sort();
+        let mut expected = tags(&matches);
+        expected.sort();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn requires_a_seed() {
+        let experiment = Experiment {
+            seed: None,
+            ..experiment()
+        };
+
+        let Err(ExperimentError::Invalid(problems)) = experiment.validate() else {
+            panic!("An experiment without a seed is valid");
+        };
+        assert_eq!(problems, ["seed must be set".into()]);
+    }
+
+    #[test]
+    fn skips_the_matches_already_played() {
+        let experiment = experiment();
+        let matches = experiment.matches(&AutomationConfig::default());
+        let pairing = Pairing {
+            stage: 1,
+            first: "Alpha".into(),
+            second: "Bravo".into(),
+        };
+        let stored = |round: u32, tag: &VariantTag| {
+            stored(&result(round, &pairing, drawn())).with_variant(tag.clone())
+        };
+        // Another experiment played the same variants from a different seed
+        let other = VariantTag {
+            seed: 4321,
+            ..matches[2].tag.clone()
+        };
+        let records = [
+            stored(1, &matches[0].tag),
+            stored(2, &matches[1].tag),
+            stored(3, &other),
+        ];
+
+        let unplayed = unplayed(matches.clone(), &records);
+        assert_eq!(unplayed, matches[2..]);
+    }
+}
@@ -0,0 +1,460 @@
+//! Sets up a game in the multiplayer lobby: the scheme, the game options, the map and the lineup.
+//!
+//! The game options are buttons that move on to their next value when clicked, like the per team
+//! controls, so each is clicked until its text shows the wanted value.
+//!
+//! The map is either generated from the seed in the seed box, or a saved map picked from the map
+//! list. The first item of the map list stands for the generated map.
+
+use std::{fmt::Display, sync::LazyLock};
+
+use fastrand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    automation::selector,
+    backend::{Backend, WindowId},
+    config,
+    lobby::{Lineup, LobbyError, SelectedTeam, set_lineup},
+    selector::Selector,
+    wait::{CancelToken, Deadline},
+    window::{select, wait_for_selector, wait_for_window},
+};
+
+/// The dropdown list of schemes.
+pub(crate) static SCHEME_LIST: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(36) > ComboBox"));
+
+/// The dropdown list of saved maps.
+pub(crate) static MAP_LIST: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(35) > ComboBox"));
+/// The seed the generated map is made from.
+pub(crate) static MAP_SEED: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(35) > Edit"));
+/// Generates a new map from a random seed.
+pub(crate) static NEW_MAP_BUTTON: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(35) > Button"));
+
+/// The item of the map list that is the generated map rather than a saved one.
+const GENERATED_MAP_ITEM: usize = 0;
+
+/// How many clicks an option may take to reach its value, in case its text never repeats.
+const MAX_OPTION_CLICKS: usize = 50;
+
+/// The game options that can be set from the lobby. Options that are `None` are left as they are,
+/// which is whatever the scheme has.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameOptions {
+    pub turn_time_secs: Option<u32>,
+    pub round_time_mins: Option<u32>,
+    /// The energy each worm starts with.
+    pub worm_energy: Option<u32>,
+}
+
+/// The option buttons in the lobby.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameOption {
+    TurnTime,
+    RoundTime,
+    WormEnergy,
+}
+
+impl GameOption {
+    const ALL: [GameOption; 3] = [
+        GameOption::TurnTime,
+        GameOption::RoundTime,
+        GameOption::WormEnergy,
+    ];
+
+    fn selector(&self) -> Selector {
+        let index = match self {
+            GameOption::TurnTime => 1,
+            GameOption::RoundTime => 2,
+            GameOption::WormEnergy => 3,
+        };
+        selector(&format!("> :nth(0) > :nth(36) > :nth({index})"))
+    }
+
+    fn wanted(&self, options: &GameOptions) -> Option<u32> {
+        match self {
+            GameOption::TurnTime => options.turn_time_secs,
+            GameOption::RoundTime => options.round_time_mins,
+            GameOption::WormEnergy => options.worm_energy,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            GameOption::TurnTime => "turn time",
+            GameOption::RoundTime => "round time",
+            GameOption::WormEnergy => "worm energy",
+        }
+    }
+}
+
+/// The terrain a game is played on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapChoice {
+    /// A newly generated map, recorded as [`MapChoice::Seeded`] once its seed is known.
+    Random,
+    /// The map generated from the seed.
+    Seeded(u32),
+    /// A saved map, by its name in the map list.
+    File(Box<str>),
+}
+
+impl Display for MapChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapChoice::Random => write!(f, "a random map"),
+            MapChoice::Seeded(seed) => write!(f, "map seed {seed}"),
+            MapChoice::File(name) => write!(f, "the {name} map"),
+        }
+    }
+}
+
+/// The maps to play on, one after another.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapRotation {
+    maps: Vec<MapChoice>,
+    /// Each pass through the maps is shuffled from this seed when set.
+    shuffle_seed: Option<u64>,
+}
+
+impl MapRotation {
+    /// Plays on the maps in order, starting again from the first after the last.
+    pub fn new(maps: Vec<MapChoice>) -> Self {
+        Self {
+            maps,
+            shuffle_seed: None,
+        }
+    }
+
+    /// Plays on every map once per pass, in an order shuffled from the seed, so the same seed
+    /// always gives the same maps for each round.
+    pub fn shuffled(maps: Vec<MapChoice>, seed: u64) -> Self {
+        Self {
+            maps,
+            shuffle_seed: Some(seed),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.is_empty()
+    }
+
+    pub fn shuffle_seed(&self) -> Option<u64> {
+        self.shuffle_seed
+    }
+
+    /// The map for the round, counting from `1`, or `None` if there are no maps.
+    pub fn map(&self, round: u32) -> Option<MapChoice> {
+        if self.maps.is_empty() {
+            return None;
+        }
+
+        let index = round.saturating_sub(1) as usize;
+        let (pass, index) = (index / self.maps.len(), index % self.maps.len());
+        let mut order: Vec<usize> = (0..self.maps.len()).collect();
+        if let Some(seed) = self.shuffle_seed {
+            Rng::with_seed(seed.wrapping_add(pass as u64)).shuffle(&mut order);
+        }
+        Some(self.maps[order[index]].clone())
+    }
+}
+
+/// Everything about a game that is chosen in the lobby.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSetup {
+    pub lineup: Lineup,
+    /// The name of the scheme, or `None` to keep the selected scheme.
+    pub scheme: Option<Box<str>>,
+    pub options: GameOptions,
+    /// The map, or `None` to keep the current map.
+    pub map: Option<MapChoice>,
+}
+
+impl GameSetup {
+    /// The lineup, with the scheme, options and map left as they are.
+    pub fn new(lineup: Lineup) -> Self {
+        Self {
+            lineup,
+            scheme: None,
+            options: GameOptions::default(),
+            map: None,
+        }
+    }
+}
+
+impl Display for GameSetup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lineup)?;
+        if let Some(scheme) = &self.scheme {
+            write!(f, " with the {scheme} scheme")?;
+        }
+        if let Some(map) = &self.map {
+            write!(f, " on {map}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Selects the scheme, sets the game options, the map and then the lineup.
+/// The scheme goes first as selecting one resets the options.
+pub fn set_up(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    setup: &GameSetup,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<SelectedTeam>, LobbyError> {
+    if let Some(scheme) = &setup.scheme {
+        select_scheme(backend, window_id, scheme, deadline, cancel)?;
+    }
+    set_options(backend, window_id, &setup.options, cancel)?;
+    if let Some(map) = &setup.map {
+        set_map(backend, window_id, map, deadline, cancel)?;
+    }
+    set_lineup(backend, window_id, &setup.lineup, deadline, cancel)
+}
+
+/// Selects the scheme with the name, ignoring case, and checks the scheme list shows it.
+pub fn select_scheme(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    name: &str,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<(), LobbyError> {
+    let list = wait_for_selector(backend, window_id, &SCHEME_LIST, deadline, cancel)?;
+    let scheme = select_item(backend, list, "scheme", name)?;
+    log::info!("Selected the {scheme} scheme");
+    Ok(())
+}
+
+/// Reads the name of the selected scheme, or `None` if it can't be read.
+pub fn current_scheme(backend: &dyn Backend, window_id: WindowId) -> Option<Box<str>> {
+    let list = select(backend, window_id, &SCHEME_LIST).ok()?;
+    let index = backend.selected_combo_item(list)?;
+    backend
+        .combo_items(list)
+        .into_iter()
+        .nth(index)
+        .map(Into::into)
+}
+
+/// Changes the map, checking the lobby shows it afterwards.
+/// Returns the map in use, which for a random map is the seed it was generated from.
+pub fn set_map(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    map: &MapChoice,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<MapChoice, LobbyError> {
+    let list = wait_for_selector(backend, window_id, &MAP_LIST, deadline, cancel)?;
+    let generated = || match backend.combo_items(list).get(GENERATED_MAP_ITEM) {
+        Some(item) => select_item(backend, list, "map", item),
+        None => Err(LobbyError::Map("the map list is empty".into())),
+    };
+
+    match map {
+        MapChoice::Random => {
+            generated()?;
+            let seed = select(backend, window_id, &MAP_SEED)?;
+            let before = backend.text(seed);
+            backend.click(select(backend, window_id, &NEW_MAP_BUTTON)?);
+            wait_for_window(
+                backend,
+                window_id,
+                deadline,
+                cancel,
+                "a new map seed",
+                |_| (backend.text(seed) != before).then_some(()),
+            )?;
+        }
+        MapChoice::Seeded(wanted) => {
+            generated()?;
+            let seed = select(backend, window_id, &MAP_SEED)?;
+            backend.set_text(seed, &wanted.to_string());
+            std::thread::sleep(config::get().automation.add_team_delay());
+        }
+        MapChoice::File(name) => {
+            select_item(backend, list, "map", name)?;
+        }
+    }
+
+    let current = current_map(backend, window_id)?;
+    let applied = match (map, &current) {
+        (MapChoice::Random, MapChoice::Seeded(_)) => true,
+        (MapChoice::File(wanted), MapChoice::File(name)) => {
+            wanted.trim().eq_ignore_ascii_case(name.trim())
+        }
+        (map, current) => map == current,
+    };
+    match applied {
+        true => {
+            log::info!("Set the map to {current}");
+            Ok(current)
+        }
+        false => Err(LobbyError::Map(
+            format!("wanted {map} but the lobby shows {current}").into(),
+        )),
+    }
+}
+
+/// Reads the map the lobby will play on. A generated map without a readable seed is
+/// [`MapChoice::Random`].
+pub fn current_map(backend: &dyn Backend, window_id: WindowId) -> Result<MapChoice, LobbyError> {
+    let list = select(backend, window_id, &MAP_LIST)?;
+    match backend.selected_combo_item(list) {
+        Some(index) if index != GENERATED_MAP_ITEM => {
+            // The list may have changed since the selection was read
+            match backend.combo_items(list).into_iter().nth(index) {
+                Some(name) => Ok(MapChoice::File(name.into())),
+                None => Err(LobbyError::Map(
+                    format!("the selected map {index} is no longer in the map list").into(),
+                )),
+            }
+        }
+        _ => {
+            let seed = backend.text(select(backend, window_id, &MAP_SEED)?);
+            Ok(seed
+                .trim()
+                .parse()
+                .map_or(MapChoice::Random, MapChoice::Seeded))
+        }
+    }
+}
+
+/// Clicks each option that is set until it shows the wanted value.
+/// Gives up on an option once its text comes round to where it started.
+pub fn set_options(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    options: &GameOptions,
+    cancel: &CancelToken,
+) -> Result<(), LobbyError> {
+    for option in GameOption::ALL {
+        let Some(wanted) = option.wanted(options) else {
+            continue;
+        };
+
+        let button = select(backend, window_id, &option.selector())?;
+        let started_at = backend.text(button);
+        let mut text = started_at.clone();
+        for clicks in 0.. {
+            cancel.check()?;
+            if option_value(&text) == Some(wanted) {
+                break;
+            }
+            if clicks >= MAX_OPTION_CLICKS || (clicks > 0 && text == started_at) {
+                return Err(LobbyError::GameOption {
+                    option: option.name(),
+                    wanted,
+                    actual: text,
+                });
+            }
+
+            backend.click(button);
+            std::thread::sleep(config::get().automation.add_team_delay());
+            text = backend.text(button);
+        }
+        log::info!("Set the {} to {wanted}", option.name());
+    }
+    Ok(())
+}
+
+/// Selects the item of a dropdown list with the name, ignoring case, and checks it is selected.
+/// Returns the item's text.
+fn select_item(
+    backend: &dyn Backend,
+    list: WindowId,
+    list_name: &'static str,
+    name: &str,
+) -> Result<String, LobbyError> {
+    let mut items = backend.combo_items(list);
+    let Some(index) = items
+        .iter()
+        .position(|item| item.trim().eq_ignore_ascii_case(name.trim()))
+    else {
+        return Err(LobbyError::UnknownItem {
+            list: list_name,
+            name: name.into(),
+            available: items,
+        });
+    };
+
+    if backend.selected_combo_item(list) != Some(index) {
+        backend.select_combo_item(list, index);
+        std::thread::sleep(config::get().automation.add_team_delay());
+    }
+
+    match backend.selected_combo_item(list) == Some(index) {
+        true => Ok(items.swap_remove(index)),
+        false => Err(LobbyError::NotSelected {
+            list: list_name,
+            name: name.into(),
+        }),
+    }
+}
+
+/// The first number in an option's text, as the units shown vary.
+fn option_value(text: &str) -> Option<u32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let digits: String = text[start..]
+        .chars()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fake::{FakeBackend, FakeDesktop, FakeWindow};
+
+    /// A lobby with only the map panel, with the map list showing the selected item.
+    fn lobby(selected: usize, seed: &str) -> (FakeBackend, WindowId) {
+        let mut desktop = FakeDesktop::new();
+        let root = desktop.add_root(FakeWindow::new("Worms2D"));
+        let frame = desktop.add_child(root, FakeWindow::new("Frame"));
+        for _ in 0..35 {
+            desktop.add_child(frame, FakeWindow::new("Static"));
+        }
+        let panel = desktop.add_child(frame, FakeWindow::new("Panel"));
+        let list = desktop.add_child(
+            panel,
+            FakeWindow::new("ComboBox").items(["(Generated map)", "Arena"]),
+        );
+        desktop.window_mut(list).unwrap().selected = Some(selected);
+        desktop.add_child(panel, FakeWindow::new("Edit").text(seed));
+        (FakeBackend::new(desktop), root)
+    }
+
+    #[test]
+    fn reads_the_current_map() {
+        let (backend, root) = lobby(1, "12");
+        assert_eq!(
+            current_map(&backend, root),
+            Ok(MapChoice::File("Arena".into()))
+        );
+
+        let (backend, root) = lobby(0, " 12 ");
+        assert_eq!(current_map(&backend, root), Ok(MapChoice::Seeded(12)));
+
+        let (backend, root) = lobby(0, "");
+        assert_eq!(current_map(&backend, root), Ok(MapChoice::Random));
+    }
+
+    #[test]
+    fn a_selection_past_the_map_list_is_an_error() {
+        let (backend, root) = lobby(5, "12");
+        assert!(matches!(
+            current_map(&backend, root),
+            Err(LobbyError::Map(_))
+        ));
+    }
+}
@@ -1,15 +1,27 @@
+use std::path::PathBuf;
+
 use windows::{
-    Win32::UI::WindowsAndMessaging::{MB_ICONERROR, MessageBoxA},
+    Win32::{
+        Foundation::{HINSTANCE, HMODULE},
+        Storage::FileSystem::{
+            GetFileVersionInfoA, GetFileVersionInfoSizeA, VS_FIXEDFILEINFO, VerQueryValueA,
+        },
+        System::LibraryLoader::GetModuleFileNameA,
+        UI::WindowsAndMessaging::{MB_ICONERROR, MessageBoxA},
+    },
     core::PCSTR,
 };
 
-const LOG_FILE: &str = "worms_ai_loop.log";
+use crate::config::{self, Config};
 
 /// Sets up:
 /// - A custom panic hook to display in a message box
+/// - The config, from the directory the DLL is in
 /// - Logging
-pub fn init() {
-    std::panic::set_hook(Box::new(|info| unsafe {
+///
+/// An invalid config is shown in a message box and the defaults are used instead.
+pub fn init(dll_module: HINSTANCE) {
+    std::panic::set_hook(Box::new(|info| {
         log::error!(
             "Line: {}\nError: {}",
             info.location()
@@ -18,27 +30,103 @@ pub fn init() {
             get_payload(info)
         );
 
-        // Has to be in a separate variable to work
-        let caption = concat!("Unrecoverable error : ", std::env!("CARGO_PKG_NAME"), "\0");
-        let caption = PCSTR(caption.as_ptr());
-
-        // Has to be in a separate variable to work
-        let text = format!(
-            "Line: {}\nError: {}\0",
-            info.location()
-                .map(|a| a.to_string())
-                .unwrap_or("Unknown Location".to_owned()),
-            get_payload(info)
+        show_error(
+            concat!("Unrecoverable error : ", std::env!("CARGO_PKG_NAME")),
+            &format!(
+                "Line: {}\nError: {}",
+                info.location()
+                    .map(|a| a.to_string())
+                    .unwrap_or("Unknown Location".to_owned()),
+                get_payload(info)
+            ),
         );
-        let text = PCSTR(text.as_ptr());
-
-        MessageBoxA(None, text, caption, MB_ICONERROR);
 
         std::process::exit(1);
     }));
 
-    simple_logging::log_to_file(LOG_FILE, log::LevelFilter::Info)
+    let dir = dll_dir(dll_module);
+    let loaded = Config::load_from_dir(&dir);
+    let config = match &loaded {
+        Ok(config) => config.clone(),
+        Err(_) => Config::default_in(&dir),
+    };
+
+    simple_logging::log_to_file(config.resolve(&config.logging.file), config.logging.level())
         .expect("Unable to create log file");
+
+    if let Err(err) = loaded {
+        log::error!("{err}");
+        show_error(
+            concat!("Invalid config : ", std::env!("CARGO_PKG_NAME")),
+            &format!("{err}\n\nThe default settings are used instead."),
+        );
+    }
+    log::info!("Config: {config:?}");
+
+    config::set(config);
+}
+
+/// Shows a message box with an error icon.
+fn show_error(caption: &str, text: &str) {
+    // Has to be in a separate variable to work
+    let caption = format!("{caption}\0");
+    let caption = PCSTR(caption.as_ptr());
+
+    // Has to be in a separate variable to work
+    let text = format!("{text}\0");
+    let text = PCSTR(text.as_ptr());
+
+    unsafe { MessageBoxA(None, text, caption, MB_ICONERROR) };
+}
+
+/// The directory the DLL was loaded from, or the working directory if it can't be found.
+fn dll_dir(dll_module: HINSTANCE) -> PathBuf {
+    let mut buffer = [0u8; 1024];
+    let len = unsafe { GetModuleFileNameA(Some(HMODULE(dll_module.0)), &mut buffer) } as usize;
+
+    let path = PathBuf::from(String::from_utf8_lossy(&buffer[..len]).into_owned());
+    match path.parent() {
+        Some(dir) if len > 0 => dir.to_owned(),
+        _ => PathBuf::new(),
+    }
+}
+
+/// The file version of the WA executable this DLL was loaded into, such as `3.8.1.0`.
+pub fn wa_version() -> Option<Box<str>> {
+    let mut buffer = [0u8; 1024];
+    let len = unsafe { GetModuleFileNameA(None, &mut buffer) } as usize;
+    if len == 0 || len >= buffer.len() {
+        return None;
+    }
+    // Includes the nul terminator
+    let path = PCSTR(buffer[..=len].as_ptr());
+
+    let size = unsafe { GetFileVersionInfoSizeA(path, None) };
+    if size == 0 {
+        return None;
+    }
+    let mut info = vec![0u8; size as usize];
+    unsafe { GetFileVersionInfoA(path, None, size, info.as_mut_ptr().cast()) }.ok()?;
+
+    let mut fixed: *mut core::ffi::c_void = std::ptr::null_mut();
+    let mut fixed_len = 0;
+    let found = unsafe {
+        VerQueryValueA(
+            info.as_ptr().cast(),
+            PCSTR(c"\\".as_ptr().cast()),
+            &mut fixed,
+            &mut fixed_len,
+        )
+    };
+    if !found.as_bool() || fixed.is_null() || (fixed_len as usize) < size_of::<VS_FIXEDFILEINFO>() {
+        return None;
+    }
+
+    // SAFETY: The root block of the version info is a VS_FIXEDFILEINFO that lives in `info`
+    let fixed = unsafe { &*(fixed as *const VS_FIXEDFILEINFO) };
+    let (major, minor) = (fixed.dwFileVersionMS >> 16, fixed.dwFileVersionMS & 0xFFFF);
+    let (patch, build) = (fixed.dwFileVersionLS >> 16, fixed.dwFileVersionLS & 0xFFFF);
+    Some(format!("{major}.{minor}.{patch}.{build}").into())
 }
 
 /// See 'payload' in [std::panic::PanicHookInfo]
@@ -1,175 +1,252 @@
+#[cfg(windows)]
 mod analyser;
+pub mod automation;
+pub mod backend;
+pub mod checkpoint;
+pub mod config;
+pub mod diff;
+pub mod events;
+pub mod experiment;
+pub mod game;
+#[cfg(windows)]
 mod init;
-mod window;
-
-use mouce::{Mouse, MouseActions as _};
-use std::time::Duration;
-use windows::Win32::UI::WindowsAndMessaging::{
-    FindWindowA, GetWindowRect, GetWindowTextA, SendMessageA, WHEEL_DELTA, WM_MOUSEWHEEL,
+pub mod lobby;
+pub mod navigation;
+pub mod rating;
+pub mod report;
+pub mod results;
+pub mod screen;
+pub mod selector;
+pub mod snapshot;
+pub mod sprt;
+pub mod store;
+#[cfg(test)]
+mod test_util;
+pub mod tournament;
+pub mod wait;
+pub mod window;
+
+#[cfg(windows)]
+use std::{
+    cell::RefCell,
+    sync::{Mutex, PoisonError},
+    time::Duration,
 };
-use windows::core::s;
+#[cfg(windows)]
 use windows::{Win32::Foundation::*, Win32::System::SystemServices::*};
 
+#[cfg(windows)]
 use crate::analyser::analyse;
-use crate::window::{Click as _, GetFound, Text as _, get_windows, wait_for_window};
+#[cfg(windows)]
+use crate::{
+    backend::{Backend as _, win32::Win32Backend},
+    checkpoint::CheckpointFile,
+    experiment::Experiment,
+    game::GameSetup,
+    rating::Ratings,
+    store::{ResultStore, RunInfo, Session, StoredResult},
+    wait::CancelToken,
+};
 
-const CREATE_GAME_MENU: &str = "(1)Create single or multiplayer game";
-const ADD_TEAM_MESSAGE: &str = "Left click a team to add it to the game. Right click to edit.";
-const ROUND_RESULTS_TEXT: &str = "(1) ROUND RESULTS";
+/// Stops the rounds running, whether started on attach or from the analyser, once the DLL is
+/// detached.
+#[cfg(windows)]
+static RUNNING: Mutex<Option<CancelToken>> = Mutex::new(None);
 
+#[cfg(windows)]
 #[unsafe(no_mangle)]
 #[allow(non_snake_case, unused_variables)]
 extern "system" fn DllMain(dll_module: HINSTANCE, call_reason: u32, _: *mut ()) -> bool {
     match call_reason {
         DLL_PROCESS_ATTACH => {
-            init::init();
-            analyse();
+            init::init(dll_module);
+            if config::get().analyser.enabled {
+                analyse();
+            } else {
+                attach();
+            }
+        }
+        DLL_PROCESS_DETACH => {
+            log::info!("Detach time");
+            let running = RUNNING
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            if let Some(running) = running {
+                running.cancel();
+            }
         }
-        DLL_PROCESS_DETACH => log::info!("Detach time"),
         _ => (),
     }
 
     true
 }
 
-fn attach() {
+/// Starts playing rounds in a new thread, stopping any rounds already running so only one loop
+/// drives the lobby. The returned token stops it.
+#[cfg(windows)]
+fn attach() -> CancelToken {
     log::info!("Started");
-    std::thread::spawn(|| {
+    let cancel = CancelToken::new();
+    let previous = RUNNING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .replace(cancel.clone());
+    if let Some(previous) = previous {
+        log::info!("Stopping the rounds already running");
+        previous.cancel();
+    }
+
+    let token = cancel.clone();
+    std::thread::spawn(move || {
         std::thread::sleep(Duration::from_secs(1));
-        let window_id = unsafe { FindWindowA(None, s!("Worms Armageddon")) }
-            .expect("Unable to find `Worms Armageddon` window");
+        let backend = Win32Backend;
+        let Some(window_id) = backend.find_window(automation::WINDOW_TITLE) else {
+            log::error!("Unable to find `Worms Armageddon` window");
+            return;
+        };
         log::info!("Window ID: {window_id:?}");
 
-        let id = wait_for_window(window_id, |windows| {
-            windows.filter(|id| {
-                // Read window text into buffer
-                let mut window_text = [' ' as u8; 255];
-                unsafe { GetWindowTextA(*id, &mut window_text) };
-                let text =
-                    String::from_utf8(window_text.to_vec()).expect("Unable to get window title");
-
-                text.starts_with(CREATE_GAME_MENU)
-            })
+        let session = Session::start(RunInfo::new(config::get(), init::wa_version()));
+        let store = ResultStore::new(config::get().resolve(&config::get().results.file));
+        log::info!(
+            "Session {} recording to {}",
+            session.id,
+            store.path().display()
+        );
+
+        let history = store.load_migrated().unwrap_or_else(|err| {
+            log::error!("Unable to read the previous results, rating from scratch: {err}");
+            Vec::new()
         });
-
-        log::info!("Pressing: {id:?}");
-
-        // Select multiplayer game
-        id.click();
-
-        let mut button = get_windows(window_id).get(0).get(38).get(1).value();
-
-        while button.is_none() {
-            button = get_windows(window_id).get(0).get(38).get(1).value();
-            std::thread::sleep(Duration::from_millis(200));
-        }
-        let button = button.unwrap();
-        log::info!("found button");
-
-        // Scroll to bottom of the teams list
-        unsafe {
-            SendMessageA(
-                button,
-                WM_MOUSEWHEEL,
-                // The multiplier controls the scroll strength
-                WPARAM(((WHEEL_DELTA as i64 * -5) << 16) as usize),
-                LPARAM(0isize),
-            )
+        let config = &config::get().automation;
+        let checkpoints =
+            CheckpointFile::new(config::get().resolve(&config::get().tournament.checkpoint_file));
+        let checkpoint = checkpoints.resume_or_start(config::get(), &history, config.shuffle_seed);
+        let plan = config.plan(
+            checkpoint
+                .as_ref()
+                .and_then(|checkpoint| checkpoint.shuffle_seed),
+        );
+        // The results in the store, which a checkpoint catches up from when resumed
+        let mut stored = history.len();
+        let mut ratings = match &checkpoint {
+            Some(checkpoint) => checkpoint.ratings.clone(),
+            None => Ratings::from_history(&config::get().ratings, &history),
         };
+        // The rounds of the tournament played before this session
+        let resumed = checkpoint
+            .as_ref()
+            .map_or(0, |checkpoint| checkpoint.rounds);
+        // Shared between choosing each round's pairing and recording its result
+        let checkpoint = checkpoint.map(|mut checkpoint| {
+            checkpoint.shuffle_seed = plan.maps.shuffle_seed();
+            log::info!("{}", checkpoint.tournament);
+            RefCell::new(checkpoint)
+        });
 
-        add_teams(window_id, 2);
+        let sprt = config::get().sprt.sprt().map(RefCell::new);
+        let experiment = match &config::get().experiment.file {
+            Some(file) => match Experiment::load(config::get().resolve(file)) {
+                Ok(experiment) => {
+                    let matches = experiment.matches(config);
+                    let total = matches.len();
+                    let matches = experiment::unplayed(matches, &history);
+                    log::info!(
+                        "Playing {} of the {total} matches of experiment {}",
+                        matches.len(),
+                        experiment.name
+                    );
+                    Some(matches)
+                }
+                Err(err) => {
+                    log::error!("{err}");
+                    return;
+                }
+            },
+            None => None,
+        };
+        // The match of each round, counting from `1`
+        let experiment_match = |round: u32| {
+            experiment
+                .as_ref()
+                .and_then(|matches| matches.get(round.checked_sub(1)? as usize))
+        };
 
-        // Click on play button
-        log::debug!("Clicking play button");
-        get_windows(window_id)
-            .get(0)
-            .get(2)
-            .value()
-            .expect("Unable to find play button")
-            .click();
+        let result = automation::play_rounds(
+            &backend,
+            window_id,
+            &config.timeouts(),
+            &token,
+            |round| {
+                if let Some(matches) = &experiment {
+                    let game = experiment_match(round);
+                    if game.is_none() {
+                        log::info!(
+                            "Played the {} matches left in the experiment",
+                            matches.len()
+                        );
+                    }
+                    log::info!("Round {round}: {}", game?.tag);
+                    return Some(game?.setup.clone());
+                }
 
-        // Wait until button after match is shown
-        log::debug!("Waiting until round finishes");
-        let mut round_results = None;
-        while round_results.is_none() {
-            round_results = get_windows(window_id)
-                .get(0)
-                .get(5)
-                .value()
-                .filter(|found| found.text().contains(ROUND_RESULTS_TEXT));
-            std::thread::sleep(Duration::from_millis(250));
+                let setup = plan.setup(resumed + round)?;
+                let (pairing, map) = match (&checkpoint, &sprt) {
+                    (Some(checkpoint), _) => {
+                        (checkpoint.borrow().tournament.current()?.clone(), setup.map)
+                    }
+                    // Both games of a pair are on the same map
+                    (None, Some(sprt)) => {
+                        let pairing = sprt.borrow().next()?;
+                        let map = plan.maps.map(pairing.stage).or(setup.map);
+                        (pairing, map)
+                    }
+                    (None, None) => return Some(setup),
+                };
+                log::info!("Round {round}: stage {} {pairing}", pairing.stage);
+                Some(GameSetup {
+                    lineup: config.with_team_settings(pairing.lineup()),
+                    map,
+                    ..setup
+                })
+            },
+            |result| {
+                let mut record = StoredResult::new(&session, result.clone());
+                if let Some(game) = experiment_match(result.round) {
+                    record = record.with_variant(game.tag.clone());
+                }
+                match store.append(&record) {
+                    Ok(()) => stored += 1,
+                    Err(err) => log::error!("Unable to store the result: {err}"),
+                }
+                if ratings.record(result) {
+                    log::info!("{ratings}");
+                }
+                if let Some(sprt) = &sprt {
+                    let mut sprt = sprt.borrow_mut();
+                    if sprt.record(result) {
+                        log::info!("{sprt}");
+                    }
+                }
+                if let Some(checkpoint) = &checkpoint {
+                    let mut checkpoint = checkpoint.borrow_mut();
+                    if !checkpoint.record(result, &ratings, stored) {
+                        return;
+                    }
+                    if let Err(err) = checkpoints.save(&mut checkpoint) {
+                        log::error!("Unable to save the tournament checkpoint: {err}");
+                    }
+                    if checkpoint.tournament.is_finished() {
+                        log::info!("{}", checkpoint.tournament);
+                    }
+                }
+            },
+        );
+        if let Err(err) = result {
+            log::error!("Unable to play rounds: {err}");
         }
-        log::debug!("Round finished");
-
-        // Exit the round over screen
-        get_windows(window_id)
-            .get(0)
-            .get(1)
-            .value()
-            .expect("Unable to get exit button")
-            .click();
     });
-}
-
-/// When on the round configuration, adds the given number of teams via using the mouse cursor.
-fn add_teams(armageddon_id: HWND, num_teams: u8) {
-    // Gets the position of the bar under the window for mouse position
-    let add_team = get_windows(armageddon_id)
-        .get(0)
-        .get(35)
-        .value()
-        .expect("Unable to get add teams button");
-
-    let mut place = RECT::default();
-    unsafe {
-        GetWindowRect(add_team, &mut place as *mut RECT).expect("Able to get window position");
-    }
-
-    let x_middle = place.left + ((place.right - place.left) / 2);
-    let mut y_pos = place.top;
-    let mouse = Mouse::new();
-    let mut previous_position: Option<(i32, i32)> = None;
-
-    mouse.move_to(x_middle, y_pos).expect("Able to move mouse");
 
-    for _ in 0..num_teams {
-        loop {
-            // Check if mouse moved manually
-            {
-                if let Some(prev_pos) = previous_position
-                    && prev_pos.1 - 2
-                        != mouse
-                            .get_position()
-                            .expect("Unable to get mouse position")
-                            .1
-                {
-                    log::info!("Manual mouse movement detected; sleeping");
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-                }
-
-                previous_position =
-                    Some(mouse.get_position().expect("Unable to get mouse position"));
-            }
-
-            // Move mouse until the mouse is over a team
-            y_pos -= 2;
-            mouse.move_to(x_middle, y_pos).expect("Able to move mouse");
-
-            let text = get_windows(armageddon_id)
-                .get(0)
-                .get(9)
-                .value()
-                .expect("Unable to get message box")
-                .text();
-
-            if text.contains(ADD_TEAM_MESSAGE) {
-                mouse
-                    .click_button(mouce::common::MouseButton::Left)
-                    .expect("Able to click mouse button");
-
-                std::thread::sleep(Duration::from_millis(100)); // Otherwise it is too fast
-                break;
-            }
-        }
-    }
+    cancel
 }
@@ -0,0 +1,785 @@
+//! Chooses the teams for a game in the multiplayer lobby and their settings.
+//!
+//! The list boxes are read and clicked through window messages, so the real cursor is never
+//! moved and it works while the desktop is in use or locked. Team colours are read from the
+//! pixels of the teams in game list, so it has to be visible on screen.
+
+use std::{fmt::Display, sync::LazyLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    automation::selector,
+    backend::{Backend, WindowId},
+    config,
+    game::{GameSetup, select_scheme, set_options},
+    selector::{SelectError, Selector},
+    wait::{CancelToken, Deadline, WaitError},
+    window::{get_windows, select, wait_for_selector, wait_for_window},
+};
+
+/// The list of teams that can be added to the game.
+pub(crate) static TEAM_LIST: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(38) > :nth(1)"));
+
+/// The list of teams that have been added to the game, beside the list of available teams.
+pub(crate) static SELECTED_LIST: LazyLock<Selector> =
+    LazyLock::new(|| selector("> :nth(0) > :nth(37) > :nth(1)"));
+
+/// The per team controls beside the teams in game list start at this child index, with
+/// [`SlotControl::COUNT`] controls for each team in the order of [`SlotControl`].
+const SLOT_CONTROLS_START: usize = 2;
+/// WA allows at most this many worms in a team.
+pub const MAX_WORMS: u8 = 8;
+
+/// How many times the teams are added again when the lobby doesn't match, before giving up.
+const SET_TEAMS_ATTEMPTS: u32 = 2;
+
+/// The colours teams can have. Teams with the same colour are allied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TeamColour {
+    Red,
+    Blue,
+    Green,
+    Yellow,
+    Magenta,
+    Cyan,
+}
+
+impl TeamColour {
+    pub const ALL: [TeamColour; 6] = [
+        TeamColour::Red,
+        TeamColour::Blue,
+        TeamColour::Green,
+        TeamColour::Yellow,
+        TeamColour::Magenta,
+        TeamColour::Cyan,
+    ];
+
+    /// The colour the frontend draws the team in, as `0xRRGGBB`.
+    pub fn rgb(&self) -> u32 {
+        match self {
+            TeamColour::Red => 0xFF4040,
+            TeamColour::Blue => 0x4040FF,
+            TeamColour::Green => 0x40FF40,
+            TeamColour::Yellow => 0xFFFF40,
+            TeamColour::Magenta => 0xFF40FF,
+            TeamColour::Cyan => 0x40FFFF,
+        }
+    }
+
+    /// The team colour closest to the `0xRRGGBB` colour, if any is close enough.
+    pub fn nearest(rgb: u32) -> Option<Self> {
+        // Far enough to allow for shading, but not to match greys or the background
+        const MAX_DISTANCE: u32 = 3 * 80 * 80;
+
+        let distance = |colour: TeamColour| {
+            let [_, r1, g1, b1] = rgb.to_be_bytes();
+            let [_, r2, g2, b2] = colour.rgb().to_be_bytes();
+            [(r1, r2), (g1, g2), (b1, b2)]
+                .into_iter()
+                .map(|(a, b)| (a.abs_diff(b) as u32).pow(2))
+                .sum::<u32>()
+        };
+
+        Self::ALL
+            .into_iter()
+            .min_by_key(|colour| distance(*colour))
+            .filter(|colour| distance(*colour) <= MAX_DISTANCE)
+    }
+}
+
+impl Display for TeamColour {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Changes the energy of a team's worms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Handicap {
+    #[default]
+    None,
+    /// More energy than the other teams.
+    Plus,
+    /// Less energy than the other teams.
+    Minus,
+}
+
+impl Handicap {
+    /// Reads the handicap from the text of its control.
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() || text.eq_ignore_ascii_case("none") {
+            Some(Handicap::None)
+        } else if text.starts_with('+') {
+            Some(Handicap::Plus)
+        } else if text.starts_with('-') {
+            Some(Handicap::Minus)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Handicap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// The controls each team in the game has. Clicking one moves it on to its next value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlotControl {
+    Alliance,
+    Worms,
+    Handicap,
+}
+
+impl SlotControl {
+    const COUNT: usize = 3;
+
+    fn selector(&self, slot: usize) -> Selector {
+        let index = SLOT_CONTROLS_START + slot * Self::COUNT + *self as usize;
+        selector(&format!("> :nth(0) > :nth(37) > :nth({index})"))
+    }
+
+    /// How many clicks it takes to go through every value.
+    fn values(&self) -> usize {
+        match self {
+            SlotControl::Alliance => TeamColour::ALL.len(),
+            SlotControl::Worms => MAX_WORMS as usize,
+            SlotControl::Handicap => 3,
+        }
+    }
+}
+
+/// A team that has been added to the game.
+/// The settings are `None` when they couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SelectedTeam {
+    pub name: Box<str>,
+    pub colour: Option<TeamColour>,
+    pub worms: Option<u8>,
+    pub handicap: Option<Handicap>,
+}
+
+impl Display for SelectedTeam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = || "?".to_owned();
+        write!(
+            f,
+            "{} ({}, {} worms, {} handicap)",
+            self.name,
+            self.colour
+                .map_or_else(unknown, |colour| colour.to_string()),
+            self.worms.map_or_else(unknown, |worms| worms.to_string()),
+            self.handicap
+                .map_or_else(unknown, |handicap| handicap.to_string()),
+        )
+    }
+}
+
+/// The settings for a team in the game. Settings that are `None` are left as they are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TeamSettings {
+    /// Teams with the same colour are allied.
+    pub colour: Option<TeamColour>,
+    pub worms: Option<u8>,
+    pub handicap: Option<Handicap>,
+}
+
+impl TeamSettings {
+    /// Whether the team has every setting that is set.
+    pub fn matches(&self, team: &SelectedTeam) -> bool {
+        self.colour.is_none_or(|colour| team.colour == Some(colour))
+            && self.worms.is_none_or(|worms| team.worms == Some(worms))
+            && self
+                .handicap
+                .is_none_or(|handicap| team.handicap == Some(handicap))
+    }
+}
+
+impl Display for TeamSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let settings: Vec<String> = [
+            self.colour.map(|colour| colour.to_string()),
+            self.worms.map(|worms| format!("{worms} worms")),
+            self.handicap.map(|handicap| format!("{handicap} handicap")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        match settings.is_empty() {
+            true => write!(f, "unchanged settings"),
+            false => write!(f, "{}", settings.join(", ")),
+        }
+    }
+}
+
+/// The teams for a game and their settings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lineup {
+    pub teams: Teams,
+    /// The settings for each team, in the same order. Teams without settings are left as they are.
+    pub settings: Vec<TeamSettings>,
+}
+
+impl Lineup {
+    /// The teams, with their settings left as they are.
+    pub fn new(teams: Teams) -> Self {
+        Self {
+            teams,
+            settings: Vec::new(),
+        }
+    }
+
+    /// Every team for itself, each with its own colour.
+    pub fn free_for_all(teams: Teams) -> Self {
+        Self::versus(teams, 1)
+    }
+
+    /// Teams of `team_size` allies, in the order of the teams, so a size of 2 is 2 vs 2 vs ...
+    pub fn versus(teams: Teams, team_size: usize) -> Self {
+        let settings = (0..teams.len())
+            .map(|index| TeamSettings {
+                colour: Some(TeamColour::ALL[(index / team_size.max(1)) % TeamColour::ALL.len()]),
+                ..Default::default()
+            })
+            .collect();
+        Self { teams, settings }
+    }
+
+    /// Gives every team the same number of worms.
+    pub fn with_worms(mut self, worms: u8) -> Self {
+        self.settings
+            .resize(self.teams.len(), TeamSettings::default());
+        for settings in self.settings.iter_mut() {
+            settings.worms = Some(worms);
+        }
+        self
+    }
+
+    /// The lineup with every team that has no colour set on a colour of its own, as in a free for
+    /// all, so no alliance is kept from an earlier game.
+    pub fn without_alliances(&self) -> Self {
+        let settings = (0..self.teams.len())
+            .map(|index| {
+                let settings = self.settings.get(index).copied().unwrap_or_default();
+                TeamSettings {
+                    colour: settings
+                        .colour
+                        .or(Some(TeamColour::ALL[index % TeamColour::ALL.len()])),
+                    ..settings
+                }
+            })
+            .collect();
+        Self {
+            teams: self.teams.clone(),
+            settings,
+        }
+    }
+
+    /// Gives every team the same handicap.
+    pub fn with_handicap(mut self, handicap: Handicap) -> Self {
+        self.settings
+            .resize(self.teams.len(), TeamSettings::default());
+        for settings in self.settings.iter_mut() {
+            settings.handicap = Some(handicap);
+        }
+        self
+    }
+}
+
+impl Display for Lineup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.teams)
+    }
+}
+
+/// Which teams to add to a game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Teams {
+    /// The teams with these names, in order.
+    Named(Vec<Box<str>>),
+    /// This many teams from the bottom of the list.
+    Last(u8),
+}
+
+impl Teams {
+    pub fn len(&self) -> usize {
+        match self {
+            Teams::Named(names) => names.len(),
+            Teams::Last(count) => *count as usize,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the selected teams are exactly these teams, in order.
+    /// The last teams in the list can't be known until the list is read, so they never match.
+    fn matches(&self, selected: &[SelectedTeam]) -> bool {
+        match self {
+            Teams::Named(names) => names_match(names, selected),
+            Teams::Last(_) => false,
+        }
+    }
+
+    /// Picks the names of the teams out of the available teams.
+    fn resolve(&self, available: &[String]) -> Result<Vec<Box<str>>, LobbyError> {
+        match self {
+            Teams::Named(names) => {
+                let missing: Vec<Box<str>> = names
+                    .iter()
+                    .filter(|name| !available.iter().any(|team| team.as_str() == name.as_ref()))
+                    .cloned()
+                    .collect();
+                match missing.is_empty() {
+                    true => Ok(names.clone()),
+                    false => Err(LobbyError::MissingTeams {
+                        missing,
+                        available: available.iter().map(|team| team.as_str().into()).collect(),
+                    }),
+                }
+            }
+            Teams::Last(count) => {
+                let start = available.len().checked_sub(*count as usize).ok_or(
+                    LobbyError::NotEnoughTeams {
+                        wanted: *count,
+                        available: available.len(),
+                    },
+                )?;
+                Ok(available[start..]
+                    .iter()
+                    .map(|team| team.as_str().into())
+                    .collect())
+            }
+        }
+    }
+}
+
+impl Display for Teams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Teams::Named(names) => write!(f, "the teams {}", names.join(", ")),
+            Teams::Last(count) => write!(f, "the last {count} teams"),
+        }
+    }
+}
+
+/// The reason the lobby could not be set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LobbyError {
+    /// Teams that were asked for by name aren't in the list of teams.
+    MissingTeams {
+        missing: Vec<Box<str>>,
+        available: Vec<Box<str>>,
+    },
+    /// The list has fewer teams than were asked for.
+    NotEnoughTeams {
+        wanted: u8,
+        available: usize,
+    },
+    /// The team's list item was not where the list box said it was, so it couldn't be clicked.
+    Unclickable(Box<str>),
+    /// A team's setting still wasn't the wanted value after going through every value.
+    Setting {
+        team: SelectedTeam,
+        wanted: TeamSettings,
+    },
+    /// There is no such item in a dropdown list, such as the scheme list.
+    UnknownItem {
+        list: &'static str,
+        name: Box<str>,
+        available: Vec<String>,
+    },
+    /// The item was picked but the dropdown list still shows another.
+    NotSelected {
+        list: &'static str,
+        name: Box<str>,
+    },
+    /// The map couldn't be changed to the one wanted.
+    Map(Box<str>),
+    /// A game option still wasn't the wanted value after going through every value.
+    GameOption {
+        option: &'static str,
+        wanted: u32,
+        actual: String,
+    },
+    /// The teams in the game still weren't the intended teams after adding them again.
+    Mismatch {
+        expected: Vec<Box<str>>,
+        actual: Vec<SelectedTeam>,
+    },
+    Wait(WaitError),
+}
+
+impl Display for LobbyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LobbyError::MissingTeams { missing, available } => write!(
+                f,
+                "No teams named {}; the available teams are {}",
+                missing.join(", "),
+                available.join(", ")
+            ),
+            LobbyError::NotEnoughTeams { wanted, available } => {
+                write!(
+                    f,
+                    "Wanted {wanted} teams but only {available} are available"
+                )
+            }
+            LobbyError::Unclickable(team) => write!(f, "Unable to click the team {team}"),
+            LobbyError::Setting { team, wanted } => {
+                write!(f, "Unable to give {team} {wanted}")
+            }
+            LobbyError::UnknownItem {
+                list,
+                name,
+                available,
+            } => write!(
+                f,
+                "No {name} in the {list} list; it has {}",
+                available.join(", ")
+            ),
+            LobbyError::NotSelected { list, name } => {
+                write!(f, "Unable to select {name} in the {list} list")
+            }
+            LobbyError::Map(problem) => write!(f, "Unable to set the map: {problem}"),
+            LobbyError::GameOption {
+                option,
+                wanted,
+                actual,
+            } => write!(
+                f,
+                "Unable to set the {option} to {wanted}, it is '{actual}'"
+            ),
+            LobbyError::Mismatch { expected, actual } => write!(
+                f,
+                "Expected the teams {} but the game has {}",
+                expected.join(", "),
+                describe(actual)
+            ),
+            LobbyError::Wait(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for LobbyError {}
+
+impl From<WaitError> for LobbyError {
+    fn from(value: WaitError) -> Self {
+        Self::Wait(value)
+    }
+}
+
+impl From<SelectError> for LobbyError {
+    fn from(value: SelectError) -> Self {
+        Self::Wait(value.into())
+    }
+}
+
+/// Gets the names of the teams that can be added to the game.
+pub fn available_teams(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<String>, WaitError> {
+    let team_list = wait_for_selector(backend, window_id, &TEAM_LIST, deadline, cancel)?;
+    Ok(backend.list_items(team_list))
+}
+
+/// Reads the teams that have been added to the game and their settings, in order.
+pub fn selected_teams(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<SelectedTeam>, WaitError> {
+    let list = wait_for_selector(backend, window_id, &SELECTED_LIST, deadline, cancel)?;
+    let offset = config::get().automation.team_colour_offset;
+    let found = get_windows(backend, window_id);
+
+    let teams = backend
+        .list_items(list)
+        .into_iter()
+        .enumerate()
+        .map(|(index, name)| {
+            backend.scroll_list_to(list, index);
+            let colour = backend
+                .list_item_rect(list, index)
+                .and_then(|rect| backend.pixel(list, (rect.left + offset, rect.center().1)))
+                .and_then(TeamColour::nearest);
+
+            let control_text = |control: SlotControl| {
+                control
+                    .selector(index)
+                    .select_one(backend, &found)
+                    .ok()
+                    .map(|control| backend.text(control.value()))
+            };
+
+            SelectedTeam {
+                name: name.into(),
+                colour,
+                worms: control_text(SlotControl::Worms).and_then(|text| text.trim().parse().ok()),
+                handicap: control_text(SlotControl::Handicap)
+                    .and_then(|text| Handicap::parse(&text)),
+            }
+        })
+        .collect();
+    Ok(teams)
+}
+
+/// Sets up the teams in the game with [`set_teams`], then gives each its settings.
+pub fn set_lineup(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    lineup: &Lineup,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<SelectedTeam>, LobbyError> {
+    let selected = set_teams(backend, window_id, &lineup.teams, deadline, cancel)?;
+    if lineup.settings.is_empty() {
+        return Ok(selected);
+    }
+
+    configure_teams(backend, window_id, &lineup.settings, deadline, cancel)
+}
+
+/// Gives the teams in the game their settings, in order, verifying each by reading it back.
+pub fn configure_teams(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    settings: &[TeamSettings],
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<SelectedTeam>, LobbyError> {
+    for (slot, wanted) in settings.iter().enumerate() {
+        let controls = [
+            (SlotControl::Alliance, wanted.colour.is_some()),
+            (SlotControl::Worms, wanted.worms.is_some()),
+            (SlotControl::Handicap, wanted.handicap.is_some()),
+        ];
+
+        for (control, is_set) in controls {
+            if !is_set {
+                continue;
+            }
+            let only_control = TeamSettings {
+                colour: wanted.colour.filter(|_| control == SlotControl::Alliance),
+                worms: wanted.worms.filter(|_| control == SlotControl::Worms),
+                handicap: wanted.handicap.filter(|_| control == SlotControl::Handicap),
+            };
+
+            let mut clicks = 0;
+            loop {
+                cancel.check()?;
+                let team = selected_teams(backend, window_id, deadline, cancel)?
+                    .into_iter()
+                    .nth(slot)
+                    .ok_or(WaitError::NotFound(SelectError::NoMatch {
+                        selector: format!("team {slot} of {}", *SELECTED_LIST).into(),
+                    }))?;
+
+                if only_control.matches(&team) {
+                    break;
+                }
+                if clicks >= control.values() {
+                    return Err(LobbyError::Setting {
+                        team,
+                        wanted: only_control,
+                    });
+                }
+
+                backend.click(select(backend, window_id, &control.selector(slot))?);
+                clicks += 1;
+                std::thread::sleep(config::get().automation.add_team_delay());
+            }
+        }
+    }
+
+    let selected = selected_teams(backend, window_id, deadline, cancel)?;
+    log::info!("Configured {}", describe(&selected));
+    Ok(selected)
+}
+
+/// Makes the teams in the game exactly the given teams, in order, and verifies it by reading
+/// them back. Teams that are already in the game are kept if they match, otherwise they are
+/// removed and the teams added again.
+pub fn set_teams(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    teams: &Teams,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<SelectedTeam>, LobbyError> {
+    let mut selected = selected_teams(backend, window_id, deadline, cancel)?;
+    if teams.matches(&selected) {
+        log::info!("The game already has {}", describe(&selected));
+        return Ok(selected);
+    }
+
+    let mut expected = Vec::new();
+    for attempt in 1..=SET_TEAMS_ATTEMPTS {
+        if !selected.is_empty() {
+            remove_teams(backend, window_id, deadline, cancel)?;
+        }
+
+        expected = add_teams(backend, window_id, teams, deadline, cancel)?;
+        selected = selected_teams(backend, window_id, deadline, cancel)?;
+        if names_match(&expected, &selected) {
+            log::info!("The game has {}", describe(&selected));
+            return Ok(selected);
+        }
+
+        log::warn!(
+            "Expected the teams {} but the game has {} (attempt {attempt} of {SET_TEAMS_ATTEMPTS})",
+            expected.join(", "),
+            describe(&selected)
+        );
+    }
+
+    Err(LobbyError::Mismatch {
+        expected,
+        actual: selected,
+    })
+}
+
+/// Puts the lobby back into a known state for the next match.
+///
+/// Every team is removed from the game, the scheme and options are restored to the defaults, and
+/// the defaults' lineup is added again with no alliance or colour left from the previous match.
+/// Returns the teams in the game, read back to verify the lineup.
+pub fn reset(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    defaults: &GameSetup,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<SelectedTeam>, LobbyError> {
+    remove_teams(backend, window_id, deadline, cancel)?;
+
+    wait_for_window(
+        backend,
+        window_id,
+        deadline,
+        cancel,
+        "the teams in game list to be empty",
+        |found| {
+            SELECTED_LIST
+                .select_one(backend, found)
+                .ok()
+                .filter(|list| backend.list_items(list.value()).is_empty())
+                .map(|_| ())
+        },
+    )?;
+
+    if let Some(scheme) = &defaults.scheme {
+        select_scheme(backend, window_id, scheme, deadline, cancel)?;
+    }
+    set_options(backend, window_id, &defaults.options, cancel)?;
+
+    let selected = set_lineup(
+        backend,
+        window_id,
+        &defaults.lineup.without_alliances(),
+        deadline,
+        cancel,
+    )?;
+    log::info!("Reset the lobby to {}", describe(&selected));
+    Ok(selected)
+}
+
+/// Removes every team from the game by clicking them in the teams in game list.
+pub fn remove_teams(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<(), LobbyError> {
+    let list = wait_for_selector(backend, window_id, &SELECTED_LIST, deadline, cancel)?;
+
+    while let Some(name) = backend.list_items(list).into_iter().next() {
+        cancel.check()?;
+        deadline.check("the teams to be removed from the game")?;
+
+        log::info!("Removing team {name}");
+        click_item(backend, list, 0).ok_or(LobbyError::Unclickable(name.into()))?;
+
+        std::thread::sleep(config::get().automation.add_team_delay());
+    }
+
+    Ok(())
+}
+
+/// When in the lobby, adds the teams to the game in order by clicking their list items.
+/// Returns the names of the teams that were added.
+pub fn add_teams(
+    backend: &dyn Backend,
+    window_id: WindowId,
+    teams: &Teams,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<Vec<Box<str>>, LobbyError> {
+    let team_list = wait_for_selector(backend, window_id, &TEAM_LIST, deadline, cancel)?;
+    let names = teams.resolve(&backend.list_items(team_list))?;
+
+    for name in names.iter() {
+        cancel.check()?;
+
+        // Adding a team may change the list, so it is read again each time
+        let available = backend.list_items(team_list);
+        let Some(index) = available.iter().position(|team| **team == **name) else {
+            return Err(LobbyError::MissingTeams {
+                missing: vec![name.clone()],
+                available: available.iter().map(|team| team.as_str().into()).collect(),
+            });
+        };
+
+        log::info!("Adding team {name}");
+        click_item(backend, team_list, index).ok_or(LobbyError::Unclickable(name.clone()))?;
+
+        // Otherwise it is too fast
+        std::thread::sleep(config::get().automation.add_team_delay());
+    }
+
+    Ok(names)
+}
+
+/// Scrolls the item into view and clicks it, if it is actually there.
+fn click_item(backend: &dyn Backend, list: WindowId, index: usize) -> Option<()> {
+    backend.scroll_list_to(list, index);
+
+    let position = backend.list_item_rect(list, index)?.center();
+    if backend.list_item_at(list, position) != Some(index) {
+        return None;
+    }
+
+    backend.click_at(list, position);
+    Some(())
+}
+
+fn names_match(names: &[Box<str>], selected: &[SelectedTeam]) -> bool {
+    names.len() == selected.len()
+        && names
+            .iter()
+            .zip(selected)
+            .all(|(name, team)| *name == team.name)
+}
+
+fn describe(teams: &[SelectedTeam]) -> String {
+    match teams.is_empty() {
+        true => "no teams".to_owned(),
+        false => teams
+            .iter()
+            .map(SelectedTeam::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+    }
+}
@@ -0,0 +1,434 @@
+//! Drives the WA frontend from whichever screen it is on to a goal.
+//!
+//! The frontend is modelled as a graph, with screens as nodes and the actions that move between
+//! them as edges. A route is planned from the recognised screen, but only its first action is
+//! taken before the screen is recognised again, so landing on an unexpected screen just means
+//! planning a new route from there.
+//!
+//! Error dialogs can open over any screen, so rather than being part of the graph they are
+//! dismissed wherever they appear, and the screen under them recognised once they close.
+
+use std::{
+    collections::{HashMap, VecDeque, hash_map::Entry},
+    fmt::Display,
+    sync::LazyLock,
+};
+
+use crate::{
+    automation::{CREATE_GAME_BUTTON, PLAY_BUTTON, ROUND_RESULTS_EXIT, Timeouts, selector},
+    backend::{Backend, WindowId},
+    game::{GameSetup, set_up},
+    lobby::LobbyError,
+    screen::{Recognition, Recognizer, Screen},
+    selector::Selector,
+    wait::{CancelToken, Deadline, WaitError},
+    window::{get_process_windows, select, wait_for_window},
+};
+
+/// The button that leaves a lobby or the team editor.
+static BACK_BUTTON: LazyLock<Selector> =
+    LazyLock::new(|| selector(r#"Button[text~="(?i)^([(][0-9]+[)])? *(back|exit|cancel)$"]"#));
+/// The button that dismisses an error dialog, among the top level windows of the process.
+static DIALOG_OK_BUTTON: LazyLock<Selector> =
+    LazyLock::new(|| selector(r##"[class="#32770"] > Button[text~="(?i)^&?ok$"]"##));
+
+/// How many actions a navigation may take before giving up.
+pub const MAX_STEPS: usize = 20;
+
+/// What to do to move from one screen to another.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Click the single window matching the selector.
+    Click(Selector),
+    /// Nothing to do but wait, such as for a round to be played out.
+    Wait,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Click(selector) => write!(f, "click `{selector}`"),
+            Action::Wait => write!(f, "wait"),
+        }
+    }
+}
+
+/// An action that is expected to move from one screen to another.
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: Screen,
+    pub to: Screen,
+    pub action: Action,
+    pub description: Box<str>,
+}
+
+impl Edge {
+    pub fn new(from: Screen, to: Screen, action: Action, description: &str) -> Self {
+        Self {
+            from,
+            to,
+            action,
+            description: description.into(),
+        }
+    }
+}
+
+/// The screens of the frontend and how to move between them.
+#[derive(Debug, Clone)]
+pub struct NavGraph {
+    edges: Vec<Edge>,
+}
+
+impl Default for NavGraph {
+    /// The screens of WA that are known how to move between.
+    fn default() -> Self {
+        use Screen::*;
+
+        let back = || Action::Click(BACK_BUTTON.clone());
+        Self::new(vec![
+            Edge::new(
+                MainMenu,
+                MultiplayerLobby,
+                Action::Click(CREATE_GAME_BUTTON.clone()),
+                "Open the multiplayer lobby",
+            ),
+            Edge::new(
+                MultiplayerLobby,
+                InGame,
+                Action::Click(PLAY_BUTTON.clone()),
+                "Start the game",
+            ),
+            Edge::new(
+                InGame,
+                RoundResults,
+                Action::Wait,
+                "Wait for the round to end",
+            ),
+            Edge::new(
+                RoundResults,
+                MultiplayerLobby,
+                Action::Click(ROUND_RESULTS_EXIT.clone()),
+                "Leave the round results",
+            ),
+            Edge::new(MultiplayerLobby, MainMenu, back(), "Leave the lobby"),
+            Edge::new(SinglePlayerLobby, MainMenu, back(), "Leave the lobby"),
+            Edge::new(TeamEditor, MainMenu, back(), "Leave the team editor"),
+        ])
+    }
+}
+
+impl NavGraph {
+    pub fn new(edges: Vec<Edge>) -> Self {
+        Self { edges }
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Finds the shortest sequence of edges from one screen to another.
+    /// The route is empty when already on the screen, and `None` when the screen can't be reached.
+    pub fn route(&self, from: Screen, to: Screen) -> Option<Vec<&Edge>> {
+        // The edge used to first reach each screen
+        let mut reached_by: HashMap<Screen, Option<&Edge>> = HashMap::from([(from, None)]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(screen) = queue.pop_front() {
+            if screen == to {
+                let mut route = Vec::new();
+                let mut current = screen;
+                while let Some(edge) = reached_by[&current] {
+                    route.push(edge);
+                    current = edge.from;
+                }
+                route.reverse();
+                return Some(route);
+            }
+
+            for edge in self.edges.iter().filter(|edge| edge.from == screen) {
+                if let Entry::Vacant(entry) = reached_by.entry(edge.to) {
+                    entry.insert(Some(edge));
+                    queue.push_back(edge.to);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Where to navigate to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Goal {
+    Screen(Screen),
+    /// The multiplayer lobby, set up for the game.
+    Lobby(GameSetup),
+}
+
+impl Goal {
+    /// The screen the goal is on.
+    pub fn screen(&self) -> Screen {
+        match self {
+            Goal::Screen(screen) => *screen,
+            Goal::Lobby(_) => Screen::MultiplayerLobby,
+        }
+    }
+}
+
+impl Display for Goal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Goal::Screen(screen) => write!(f, "{screen}"),
+            Goal::Lobby(setup) => write!(f, "{} with {setup}", self.screen()),
+        }
+    }
+}
+
+/// The reason a goal could not be reached.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NavigationError {
+    /// There is no known way from the screen to the goal.
+    NoRoute {
+        from: Recognition,
+        goal: Box<Goal>,
+    },
+    /// The goal still wasn't reached after [`MAX_STEPS`] actions.
+    TooManySteps {
+        last: Screen,
+        goal: Box<Goal>,
+    },
+    /// The goal screen was reached but the lobby could not be set up.
+    Lobby(LobbyError),
+    Wait(WaitError),
+}
+
+impl NavigationError {
+    /// Whether navigating stopped because it was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(
+            self,
+            NavigationError::Wait(WaitError::Cancelled)
+                | NavigationError::Lobby(LobbyError::Wait(WaitError::Cancelled))
+        )
+    }
+}
+
+impl Display for NavigationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavigationError::NoRoute { from, goal } => {
+                write!(f, "No known way to reach {goal} from {from}")
+            }
+            NavigationError::TooManySteps { last, goal } => write!(
+                f,
+                "Gave up reaching {goal} after {MAX_STEPS} actions, last on {last}"
+            ),
+            NavigationError::Lobby(err) => write!(f, "{err}"),
+            NavigationError::Wait(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for NavigationError {}
+
+impl From<LobbyError> for NavigationError {
+    fn from(value: LobbyError) -> Self {
+        Self::Lobby(value)
+    }
+}
+
+impl From<WaitError> for NavigationError {
+    fn from(value: WaitError) -> Self {
+        Self::Wait(value)
+    }
+}
+
+/// Plans and takes the actions to reach a goal.
+#[derive(Debug, Clone, Default)]
+pub struct Navigator {
+    graph: NavGraph,
+    recognizer: Recognizer,
+}
+
+impl Navigator {
+    pub fn new(graph: NavGraph, recognizer: Recognizer) -> Self {
+        Self { graph, recognizer }
+    }
+
+    /// Takes actions one at a time until the goal is reached, recognising the screen after each.
+    /// Returns the recognised goal screen.
+    pub fn navigate(
+        &self,
+        backend: &dyn Backend,
+        window_id: WindowId,
+        goal: &Goal,
+        timeouts: &Timeouts,
+        cancel: &CancelToken,
+    ) -> Result<Recognition, NavigationError> {
+        let mut current = self.wait_for_screen(
+            backend,
+            window_id,
+            &Deadline::after(timeouts.screen),
+            cancel,
+            "a known screen",
+            |screen| screen != Screen::Unknown,
+        )?;
+
+        for _ in 0..MAX_STEPS {
+            if current.screen == goal.screen() {
+                if let Goal::Lobby(setup) = goal {
+                    set_up(
+                        backend,
+                        window_id,
+                        setup,
+                        &Deadline::after(timeouts.screen),
+                        cancel,
+                    )?;
+                }
+                return Ok(current);
+            }
+
+            if current.screen == Screen::ErrorDialog {
+                log::warn!("On {current}; dismissing it to reach {goal}");
+                let ok = DIALOG_OK_BUTTON
+                    .select_one(backend, &get_process_windows(backend, window_id))
+                    .map(|found| found.value())
+                    .map_err(WaitError::from)?;
+                backend.click(ok);
+
+                current = self.wait_for_screen(
+                    backend,
+                    window_id,
+                    &Deadline::after(timeouts.screen),
+                    cancel,
+                    "the error dialog to close",
+                    |screen| screen != Screen::ErrorDialog && screen != Screen::Unknown,
+                )?;
+                continue;
+            }
+
+            let Some(edge) = self
+                .graph
+                .route(current.screen, goal.screen())
+                .and_then(|route| route.first().copied())
+            else {
+                return Err(NavigationError::NoRoute {
+                    from: current,
+                    goal: Box::new(goal.clone()),
+                });
+            };
+
+            log::info!("On {current}; {} to reach {goal}", edge.description);
+            let timeout = match &edge.action {
+                Action::Click(selector) => {
+                    backend.click(select(backend, window_id, selector).map_err(WaitError::from)?);
+                    timeouts.screen
+                }
+                Action::Wait => timeouts.round,
+            };
+
+            let left = current.screen;
+            current = self.wait_for_screen(
+                backend,
+                window_id,
+                &Deadline::after(timeout),
+                cancel,
+                format!("{} to leave {left}", edge.description),
+                |screen| screen != left && screen != Screen::Unknown,
+            )?;
+        }
+
+        Err(NavigationError::TooManySteps {
+            last: current.screen,
+            goal: Box::new(goal.clone()),
+        })
+    }
+
+    /// Recognises the screen whenever the windows change, until it passes the check.
+    fn wait_for_screen(
+        &self,
+        backend: &dyn Backend,
+        window_id: WindowId,
+        deadline: &Deadline,
+        cancel: &CancelToken,
+        waiting_for: impl Display,
+        check: impl Fn(Screen) -> bool,
+    ) -> Result<Recognition, WaitError> {
+        wait_for_window(backend, window_id, deadline, cancel, waiting_for, |found| {
+            let recognition = self.recognizer.recognise(backend, found);
+            check(recognition.screen).then_some(recognition)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREENS: [Screen; 8] = [
+        Screen::MainMenu,
+        Screen::SinglePlayerLobby,
+        Screen::MultiplayerLobby,
+        Screen::TeamEditor,
+        Screen::InGame,
+        Screen::RoundResults,
+        Screen::ErrorDialog,
+        Screen::Unknown,
+    ];
+
+    /// The screens the default graph's route passes through, after the first.
+    fn visits(from: Screen, to: Screen) -> Option<Vec<Screen>> {
+        NavGraph::default()
+            .route(from, to)
+            .map(|route| route.iter().map(|edge| edge.to).collect())
+    }
+
+    #[test]
+    fn routes_between_every_pair_of_screens() {
+        use Screen::*;
+
+        for from in SCREENS {
+            for to in SCREENS {
+                let expected: Option<&[Screen]> = match (from, to) {
+                    _ if from == to => Some(&[]),
+                    (MainMenu, MultiplayerLobby) => Some(&[MultiplayerLobby]),
+                    (MainMenu, InGame) => Some(&[MultiplayerLobby, InGame]),
+                    (MainMenu, RoundResults) => Some(&[MultiplayerLobby, InGame, RoundResults]),
+                    (MultiplayerLobby, MainMenu) => Some(&[MainMenu]),
+                    (MultiplayerLobby, InGame) => Some(&[InGame]),
+                    (MultiplayerLobby, RoundResults) => Some(&[InGame, RoundResults]),
+                    (InGame, MainMenu) => Some(&[RoundResults, MultiplayerLobby, MainMenu]),
+                    (InGame, MultiplayerLobby) => Some(&[RoundResults, MultiplayerLobby]),
+                    (InGame, RoundResults) => Some(&[RoundResults]),
+                    (RoundResults, MainMenu) => Some(&[MultiplayerLobby, MainMenu]),
+                    (RoundResults, MultiplayerLobby) => Some(&[MultiplayerLobby]),
+                    (RoundResults, InGame) => Some(&[MultiplayerLobby, InGame]),
+                    (SinglePlayerLobby | TeamEditor, MainMenu) => Some(&[MainMenu]),
+                    (SinglePlayerLobby | TeamEditor, MultiplayerLobby) => {
+                        Some(&[MainMenu, MultiplayerLobby])
+                    }
+                    (SinglePlayerLobby | TeamEditor, InGame) => {
+                        Some(&[MainMenu, MultiplayerLobby, InGame])
+                    }
+                    (SinglePlayerLobby | TeamEditor, RoundResults) => {
+                        Some(&[MainMenu, MultiplayerLobby, InGame, RoundResults])
+                    }
+                    // Only the user opens the other screens, nothing leads out of an unknown
+                    // screen, and dialogs are dismissed outside the graph
+                    _ => None,
+                };
+
+                assert_eq!(visits(from, to).as_deref(), expected, "From {from} to {to}");
+            }
+        }
+    }
+
+    #[test]
+    fn error_dialogs_are_not_part_of_the_graph() {
+        assert!(NavGraph::default().edges().iter().all(|edge| {
+            edge.from != Screen::ErrorDialog
+                && edge.to != Screen::ErrorDialog
+                && edge.to != Screen::Unknown
+        }));
+    }
+}
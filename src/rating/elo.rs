@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::Standings;
+
+/// A team's Elo rating.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EloRating {
+    pub rating: f64,
+    pub rounds: u32,
+}
+
+/// Elo ratings, updated after each round from every pair of teams in it.
+///
+/// Every pair is scored against the ratings from before the round. In rounds of more than two
+/// teams, each team's change is divided by the number of opponents so a round moves a rating as
+/// far as a 1 vs 1 round would.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Elo {
+    initial: f64,
+    k: f64,
+    ratings: BTreeMap<Box<str>, EloRating>,
+}
+
+impl Elo {
+    pub fn new(initial: f64, k: f64) -> Self {
+        Self {
+            initial,
+            k,
+            ratings: BTreeMap::new(),
+        }
+    }
+
+    /// The team's rating, or the initial rating if it hasn't played.
+    pub fn rating(&self, team: &str) -> EloRating {
+        self.ratings.get(team).copied().unwrap_or(EloRating {
+            rating: self.initial,
+            rounds: 0,
+        })
+    }
+
+    /// The expected score of a team rated `rating` against one rated `other`.
+    pub fn expected(rating: f64, other: f64) -> f64 {
+        1.0 / (1.0 + 10f64.powf((other - rating) / 400.0))
+    }
+
+    pub fn update(&mut self, standings: &Standings) {
+        let opponents = (standings.len().max(2) - 1) as f64;
+        let mut changes: BTreeMap<&str, f64> = BTreeMap::new();
+        for (team, other, score) in standings.pairs() {
+            let (rating, other_rating) = (self.rating(team).rating, self.rating(other).rating);
+            let change = self.k / opponents * (score - Self::expected(rating, other_rating));
+            *changes.entry(team).or_default() += change;
+            *changes.entry(other).or_default() -= change;
+        }
+
+        for (team, change) in changes {
+            let mut rating = self.rating(team);
+            rating.rating += change;
+            rating.rounds += 1;
+            self.ratings.insert(team.into(), rating);
+        }
+    }
+
+    /// Every team that has played, best first.
+    pub fn leaderboard(&self) -> Vec<(&str, EloRating)> {
+        let mut teams: Vec<(&str, EloRating)> = self
+            .ratings
+            .iter()
+            .map(|(team, rating)| (&**team, *rating))
+            .collect();
+        teams.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        teams
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_close;
+
+    fn standings(ranks: &[&[&str]]) -> Standings {
+        Standings {
+            ranks: ranks
+                .iter()
+                .map(|group| group.iter().map(|team| (*team).into()).collect())
+                .collect(),
+            alliances: ranks
+                .iter()
+                .flat_map(|group| group.iter().map(|team| vec![(*team).into()]))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn expects_the_better_team_to_win() {
+        assert_close(Elo::expected(1500.0, 1500.0), 0.5, 0.001);
+        assert_close(Elo::expected(1600.0, 1400.0), 0.759747, 0.001);
+        assert_close(Elo::expected(1400.0, 1600.0), 0.240253, 0.001);
+    }
+
+    #[test]
+    fn rates_a_win() {
+        let mut elo = Elo::new(1500.0, 32.0);
+        elo.update(&standings(&[&["Winner"], &["Loser"]]));
+        assert_close(elo.rating("Winner").rating, 1516.0, 0.001);
+        assert_close(elo.rating("Loser").rating, 1484.0, 0.001);
+
+        // The favourite gains less for winning again
+        elo.update(&standings(&[&["Winner"], &["Loser"]]));
+        assert_close(elo.rating("Winner").rating, 1530.530, 0.001);
+        assert_close(elo.rating("Loser").rating, 1469.470, 0.001);
+        assert_eq!(elo.rating("Winner").rounds, 2);
+    }
+
+    #[test]
+    fn rates_a_draw_between_equals_as_no_change() {
+        let mut elo = Elo::new(1500.0, 32.0);
+        elo.update(&standings(&[&["Alpha", "Bravo"]]));
+        assert_close(elo.rating("Alpha").rating, 1500.0, 0.001);
+        assert_close(elo.rating("Bravo").rating, 1500.0, 0.001);
+    }
+
+    #[test]
+    fn shares_the_change_between_opponents() {
+        let mut elo = Elo::new(1500.0, 32.0);
+        elo.update(&standings(&[&["First"], &["Second"], &["Third"]]));
+        assert_close(elo.rating("First").rating, 1516.0, 0.001);
+        assert_close(elo.rating("Second").rating, 1500.0, 0.001);
+        assert_close(elo.rating("Third").rating, 1484.0, 0.001);
+    }
+}
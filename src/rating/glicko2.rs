@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, f64::consts::PI};
+
+use serde::{Deserialize, Serialize};
+
+use super::Standings;
+
+/// Converts between the Glicko and Glicko-2 scales.
+const SCALE: f64 = 173.7178;
+/// How closely the new volatility is found.
+const CONVERGENCE: f64 = 0.000001;
+
+/// A team's Glicko-2 rating.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Glicko2Rating {
+    pub rating: f64,
+    /// How uncertain the rating is. The true rating is within twice this of the rating 95% of the
+    /// time.
+    pub deviation: f64,
+    pub volatility: f64,
+    pub rounds: u32,
+}
+
+/// Glicko-2 ratings, following Glickman's "Example of the Glicko-2 system".
+///
+/// Each round is a rating period in which every team played every other team in the round, scored
+/// by where they finished. Teams that sit out a round are left unchanged rather than having their
+/// deviation grow, so a rating only depends on the rounds the team played.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Glicko2 {
+    initial: Glicko2Rating,
+    tau: f64,
+    ratings: BTreeMap<Box<str>, Glicko2Rating>,
+}
+
+impl Glicko2 {
+    pub fn new(rating: f64, deviation: f64, volatility: f64, tau: f64) -> Self {
+        Self {
+            initial: Glicko2Rating {
+                rating,
+                deviation,
+                volatility,
+                rounds: 0,
+            },
+            tau,
+            ratings: BTreeMap::new(),
+        }
+    }
+
+    /// The team's rating, or the initial rating if it hasn't played.
+    pub fn rating(&self, team: &str) -> Glicko2Rating {
+        self.ratings.get(team).copied().unwrap_or(self.initial)
+    }
+
+    pub fn update(&mut self, standings: &Standings) {
+        // Each team's opponents with its score against them
+        let mut games: BTreeMap<&str, Vec<(&str, f64)>> = BTreeMap::new();
+        for (team, other, score) in standings.pairs() {
+            games.entry(team).or_default().push((other, score));
+            games.entry(other).or_default().push((team, 1.0 - score));
+        }
+
+        let updated: Vec<(&str, Glicko2Rating)> = games
+            .iter()
+            .map(|(team, games)| (*team, self.rate(self.rating(team), games)))
+            .collect();
+        for (team, rating) in updated {
+            self.ratings.insert(team.into(), rating);
+        }
+    }
+
+    /// Every team that has played, best first.
+    pub fn leaderboard(&self) -> Vec<(&str, Glicko2Rating)> {
+        let mut teams: Vec<(&str, Glicko2Rating)> = self
+            .ratings
+            .iter()
+            .map(|(team, rating)| (&**team, *rating))
+            .collect();
+        teams.sort_by(|a, b| b.1.rating.total_cmp(&a.1.rating));
+        teams
+    }
+
+    /// The rating after one rating period of games against the opponents' current ratings.
+    fn rate(&self, player: Glicko2Rating, games: &[(&str, f64)]) -> Glicko2Rating {
+        let mu = (player.rating - self.initial.rating) / SCALE;
+        let phi = player.deviation / SCALE;
+
+        let games: Vec<(f64, f64, f64)> = games
+            .iter()
+            .map(|(opponent, score)| {
+                let opponent = self.rating(opponent);
+                let g = g((opponent.deviation) / SCALE);
+                let expected = expected(mu, (opponent.rating - self.initial.rating) / SCALE, g);
+                (g, expected, *score)
+            })
+            .collect();
+
+        let variance = 1.0
+            / games
+                .iter()
+                .map(|(g, expected, _)| g * g * expected * (1.0 - expected))
+                .sum::<f64>();
+        let improvement: f64 = games
+            .iter()
+            .map(|(g, expected, score)| g * (score - expected))
+            .sum();
+        let delta = variance * improvement;
+
+        let volatility = self.volatility(phi, player.volatility, variance, delta);
+        let phi_star = (phi * phi + volatility * volatility).sqrt();
+        let phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / variance).sqrt();
+        let mu = mu + phi * phi * improvement;
+
+        Glicko2Rating {
+            rating: SCALE * mu + self.initial.rating,
+            deviation: SCALE * phi,
+            volatility,
+            rounds: player.rounds + 1,
+        }
+    }
+
+    /// Finds the new volatility with the Illinois algorithm, step 5 of the paper.
+    fn volatility(&self, phi: f64, sigma: f64, variance: f64, delta: f64) -> f64 {
+        let tau = self.tau;
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            let denominator = phi * phi + variance + ex;
+            ex * (delta * delta - phi * phi - variance - ex) / (2.0 * denominator * denominator)
+                - (x - a) / (tau * tau)
+        };
+
+        let mut low = a;
+        let mut high = if delta * delta > phi * phi + variance {
+            (delta * delta - phi * phi - variance).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * tau) < 0.0 {
+                k += 1.0;
+            }
+            a - k * tau
+        };
+
+        let (mut f_low, mut f_high) = (f(low), f(high));
+        while (high - low).abs() > CONVERGENCE {
+            let next = low + (low - high) * f_low / (f_high - f_low);
+            let f_next = f(next);
+            if f_next * f_high <= 0.0 {
+                low = high;
+                f_low = f_high;
+            } else {
+                f_low /= 2.0;
+            }
+            high = next;
+            f_high = f_next;
+        }
+
+        (low / 2.0).exp()
+    }
+}
+
+/// Reduces the impact of a game by how uncertain the opponent's rating is.
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt()
+}
+
+fn expected(mu: f64, opponent_mu: f64, g: f64) -> f64 {
+    1.0 / (1.0 + (-g * (mu - opponent_mu)).exp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::assert_close;
+
+    fn rating(rating: f64, deviation: f64) -> Glicko2Rating {
+        Glicko2Rating {
+            rating,
+            deviation,
+            volatility: 0.06,
+            rounds: 0,
+        }
+    }
+
+    #[test]
+    fn matches_the_example_from_the_paper() {
+        let mut glicko2 = Glicko2::new(1500.0, 350.0, 0.06, 0.5);
+        for (team, opponent) in [
+            ("A", rating(1400.0, 30.0)),
+            ("B", rating(1550.0, 100.0)),
+            ("C", rating(1700.0, 300.0)),
+        ] {
+            glicko2.ratings.insert(team.into(), opponent);
+        }
+
+        let rated = glicko2.rate(rating(1500.0, 200.0), &[("A", 1.0), ("B", 0.0), ("C", 0.0)]);
+
+        assert_close(rated.rating, 1464.06, 0.01);
+        assert_close(rated.deviation, 151.52, 0.01);
+        assert_close(rated.volatility, 0.05999, 0.00001);
+        assert_eq!(rated.rounds, 1);
+    }
+
+    #[test]
+    fn moves_new_teams_apart_evenly() {
+        let mut glicko2 = Glicko2::new(1500.0, 350.0, 0.06, 0.5);
+        glicko2.update(&Standings {
+            ranks: vec![vec!["Winner".into()], vec!["Loser".into()]],
+            alliances: vec![vec!["Winner".into()], vec!["Loser".into()]],
+        });
+
+        let (winner, loser) = (glicko2.rating("Winner"), glicko2.rating("Loser"));
+        assert!(winner.rating > 1500.0);
+        assert_close(winner.rating - 1500.0, 1500.0 - loser.rating, 1e-9);
+        assert_eq!(winner.deviation, loser.deviation);
+        assert!(winner.deviation < 350.0);
+    }
+}
@@ -0,0 +1,293 @@
+//! Rates teams from the results of the rounds they played.
+//!
+//! A round is turned into [`Standings`], the teams grouped by where they finished, and each rating
+//! model updates from those. Ratings only depend on the order the rounds are given in, so
+//! [`Ratings::from_history`] recomputes the same ratings from the results store every time.
+
+mod elo;
+mod glicko2;
+mod trueskill;
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+pub use elo::{Elo, EloRating};
+pub use glicko2::{Glicko2, Glicko2Rating};
+pub use trueskill::{TrueSkill, TrueSkillRating};
+
+use crate::{results::RoundResult, store::StoredResult};
+
+/// The teams of a round grouped by where they finished, best first.
+/// Teams in the same group drew with each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standings {
+    pub ranks: Vec<Vec<Box<str>>>,
+    /// Teams that played on the same side, which are the teams with the same colour.
+    pub alliances: Vec<Vec<Box<str>>>,
+}
+
+/// Allied teams, which finish together at the best place of any of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Side {
+    /// Sides with the same rank drew.
+    pub rank: usize,
+    pub teams: Vec<Box<str>>,
+}
+
+impl Standings {
+    /// The standings of a round, or `None` if the round had fewer than two teams or its outcome
+    /// wasn't read, as it then says nothing about the teams.
+    ///
+    /// A drawn round puts every team in one group. Otherwise the placements are used, with the
+    /// winner first, and any team without a placement comes last.
+    pub fn from_result(result: &RoundResult) -> Option<Self> {
+        let teams: Vec<Box<str>> = result.lineup.iter().map(|team| team.name.clone()).collect();
+        if teams.len() < 2 {
+            return None;
+        }
+
+        let mut alliances: Vec<Vec<Box<str>>> = Vec::new();
+        for (index, team) in result.lineup.iter().enumerate() {
+            let ally = result.lineup[..index]
+                .iter()
+                .position(|other| team.colour.is_some() && other.colour == team.colour);
+            match ally.and_then(|ally| {
+                let name = &result.lineup[ally].name;
+                alliances
+                    .iter_mut()
+                    .find(|alliance| alliance.contains(name))
+            }) {
+                Some(alliance) => alliance.push(team.name.clone()),
+                None => alliances.push(vec![team.name.clone()]),
+            }
+        }
+
+        let outcome = &result.outcome;
+        if outcome.draw {
+            return Some(Self {
+                ranks: vec![teams],
+                alliances,
+            });
+        }
+        if outcome.winner.is_none() && outcome.placements.is_empty() {
+            return None;
+        }
+
+        let mut order: Vec<Box<str>> = outcome.winner.iter().cloned().collect();
+        for placement in outcome.placements.iter() {
+            if !order.contains(&placement.team) {
+                order.push(placement.team.clone());
+            }
+        }
+        let mut ranks: Vec<Vec<Box<str>>> = order
+            .into_iter()
+            .filter(|team| teams.contains(team))
+            .map(|team| vec![team])
+            .collect();
+        if ranks.is_empty() {
+            return None;
+        }
+
+        let unplaced: Vec<Box<str>> = teams
+            .into_iter()
+            .filter(|team| !ranks.iter().flatten().any(|placed| placed == team))
+            .collect();
+        if !unplaced.is_empty() {
+            ranks.push(unplaced);
+        }
+
+        Some(Self { ranks, alliances })
+    }
+
+    /// The sides in finishing order. A side finishes at the best place of its teams.
+    pub fn sides(&self) -> Vec<Side> {
+        let rank_of = |team: &str| -> usize {
+            self.ranks
+                .iter()
+                .position(|group| group.iter().any(|placed| **placed == *team))
+                .unwrap_or(self.ranks.len())
+        };
+
+        let mut sides: Vec<Side> = self
+            .alliances
+            .iter()
+            .map(|alliance| Side {
+                rank: alliance
+                    .iter()
+                    .map(|team| rank_of(team))
+                    .min()
+                    .unwrap_or(self.ranks.len()),
+                teams: alliance.clone(),
+            })
+            .collect();
+        sides.sort_by_key(|side| side.rank);
+        sides
+    }
+
+    /// Every team, best first.
+    pub fn teams(&self) -> impl Iterator<Item = &Box<str>> {
+        self.ranks.iter().flatten()
+    }
+
+    /// Every pair of teams with the score of the first against the second:
+    /// `1.0` for finishing higher, `0.5` for a draw.
+    pub fn pairs(&self) -> Vec<(&str, &str, f64)> {
+        let mut pairs = Vec::new();
+        for (rank, group) in self.ranks.iter().enumerate() {
+            for (index, team) in group.iter().enumerate() {
+                for other in group[index + 1..].iter() {
+                    pairs.push((&**team, &**other, 0.5));
+                }
+                for other in self.ranks[rank + 1..].iter().flatten() {
+                    pairs.push((&**team, &**other, 1.0));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// The number of teams.
+    pub fn len(&self) -> usize {
+        self.teams().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Display for Standings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranks: Vec<String> = self.ranks.iter().map(|group| group.join(" = ")).collect();
+        write!(f, "{}", ranks.join(" > "))
+    }
+}
+
+/// The settings of each rating model.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RatingConfig {
+    /// The rating new teams start at, for both models.
+    pub initial_rating: f64,
+    /// How far a single round can move an Elo rating.
+    pub elo_k: f64,
+    /// The rating deviation new teams start at.
+    pub glicko_deviation: f64,
+    /// The volatility new teams start at.
+    pub glicko_volatility: f64,
+    /// How much the volatility can change between rounds.
+    pub glicko_tau: f64,
+    /// The TrueSkill mean new teams start at, on its own scale.
+    pub trueskill_mean: f64,
+    /// The TrueSkill deviation new teams start at.
+    pub trueskill_deviation: f64,
+    /// How much a team's performance varies from round to round.
+    pub trueskill_beta: f64,
+    /// How much a team's skill may change between rounds.
+    pub trueskill_tau: f64,
+    /// The chance of a round between evenly matched sides being drawn.
+    pub draw_probability: f64,
+}
+
+impl Default for RatingConfig {
+    fn default() -> Self {
+        Self {
+            initial_rating: 1500.0,
+            elo_k: 32.0,
+            glicko_deviation: 350.0,
+            glicko_volatility: 0.06,
+            glicko_tau: 0.5,
+            trueskill_mean: 25.0,
+            trueskill_deviation: 25.0 / 3.0,
+            trueskill_beta: 25.0 / 6.0,
+            trueskill_tau: 25.0 / 300.0,
+            draw_probability: 0.1,
+        }
+    }
+}
+
+/// Every rating model, kept up to date together.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ratings {
+    pub elo: Elo,
+    pub glicko2: Glicko2,
+    pub trueskill: TrueSkill,
+    /// The number of rounds that were rated.
+    pub rounds: usize,
+}
+
+impl Ratings {
+    pub fn new(config: &RatingConfig) -> Self {
+        Self {
+            elo: Elo::new(config.initial_rating, config.elo_k),
+            glicko2: Glicko2::new(
+                config.initial_rating,
+                config.glicko_deviation,
+                config.glicko_volatility,
+                config.glicko_tau,
+            ),
+            trueskill: TrueSkill::new(
+                config.trueskill_mean,
+                config.trueskill_deviation,
+                config.trueskill_beta,
+                config.trueskill_tau,
+                config.draw_probability,
+            ),
+            rounds: 0,
+        }
+    }
+
+    /// Rates every stored round in the order they were played, so the same history always gives
+    /// the same ratings.
+    pub fn from_history(config: &RatingConfig, records: &[StoredResult]) -> Self {
+        let mut records: Vec<&StoredResult> = records.iter().collect();
+        records.sort_by(|a, b| {
+            (a.result.finished_at, &a.session, a.result.round).cmp(&(
+                b.result.finished_at,
+                &b.session,
+                b.result.round,
+            ))
+        });
+
+        let mut ratings = Self::new(config);
+        for record in records {
+            ratings.record(&record.result);
+        }
+        ratings
+    }
+
+    /// Updates every model with the round. Returns `false` if the round couldn't be rated.
+    pub fn record(&mut self, result: &RoundResult) -> bool {
+        let Some(standings) = Standings::from_result(result) else {
+            return false;
+        };
+        self.elo.update(&standings);
+        self.glicko2.update(&standings);
+        self.trueskill.update(&standings.sides());
+        self.rounds += 1;
+        true
+    }
+}
+
+impl Display for Ratings {
+    /// A leaderboard, best first by the conservative TrueSkill rating, as it is the only model
+    /// that rates every round.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Ratings after {} rounds:", self.rounds)?;
+        for (team, rating) in self.trueskill.leaderboard() {
+            let glicko2 = self.glicko2.rating(team);
+            writeln!(
+                f,
+                "{team}: TrueSkill {:.1} ± {:.1}, Glicko-2 {:.0} ± {:.0}, Elo {:.0} ({} rounds)",
+                rating.mean,
+                2.0 * rating.deviation,
+                glicko2.rating,
+                2.0 * glicko2.deviation,
+                self.elo.rating(team).rating,
+                rating.rounds
+            )?;
+        }
+        Ok(())
+    }
+}
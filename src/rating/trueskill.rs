@@ -0,0 +1,478 @@
+use std::{collections::BTreeMap, f64::consts::SQRT_2};
+
+use serde::{Deserialize, Serialize};
+
+use super::Side;
+
+/// How many passes over the sides are made at most while the messages settle.
+const MAX_ITERATIONS: usize = 100;
+/// The messages have settled once no mean moves by more than this in a pass.
+const CONVERGENCE: f64 = 0.0001;
+
+/// A team's TrueSkill rating.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrueSkillRating {
+    pub mean: f64,
+    pub deviation: f64,
+    pub rounds: u32,
+}
+
+impl TrueSkillRating {
+    /// A rating the team is very likely to be at least, which is what teams are ranked by so that
+    /// teams with few rounds don't top the leaderboard.
+    pub fn conservative(&self) -> f64 {
+        self.mean - 3.0 * self.deviation
+    }
+}
+
+/// TrueSkill ratings, following Herbrich, Minka and Graepel's "TrueSkill: A Bayesian Skill
+/// Rating System".
+///
+/// Each side of a round is a set of allied teams whose performances add up, and sides are
+/// compared with the next in the finishing order, so any number of teams and alliances is rated
+/// from a single round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrueSkill {
+    initial: TrueSkillRating,
+    /// How much a team's performance varies between rounds.
+    beta: f64,
+    /// How much a team's skill may change between rounds.
+    tau: f64,
+    draw_probability: f64,
+    ratings: BTreeMap<Box<str>, TrueSkillRating>,
+}
+
+impl TrueSkill {
+    pub fn new(mean: f64, deviation: f64, beta: f64, tau: f64, draw_probability: f64) -> Self {
+        Self {
+            initial: TrueSkillRating {
+                mean,
+                deviation,
+                rounds: 0,
+            },
+            beta,
+            tau,
+            draw_probability,
+            ratings: BTreeMap::new(),
+        }
+    }
+
+    /// The team's rating, or the initial rating if it hasn't played.
+    pub fn rating(&self, team: &str) -> TrueSkillRating {
+        self.ratings.get(team).copied().unwrap_or(self.initial)
+    }
+
+    /// Every team that has played, best first by [`TrueSkillRating::conservative`].
+    pub fn leaderboard(&self) -> Vec<(&str, TrueSkillRating)> {
+        let mut teams: Vec<(&str, TrueSkillRating)> = self
+            .ratings
+            .iter()
+            .map(|(team, rating)| (&**team, *rating))
+            .collect();
+        teams.sort_by(|a, b| b.1.conservative().total_cmp(&a.1.conservative()));
+        teams
+    }
+
+    /// Updates the teams from the sides of a round, which are in finishing order.
+    pub fn update(&mut self, sides: &[Side]) {
+        if sides.len() < 2 {
+            return;
+        }
+
+        // The skill of each team before the round, widened by how much it may have changed
+        let skills: Vec<Vec<(&str, Gaussian)>> = sides
+            .iter()
+            .map(|side| {
+                side.teams
+                    .iter()
+                    .map(|team| {
+                        let rating = self.rating(team);
+                        let variance = rating.deviation.powi(2) + self.tau.powi(2);
+                        (&**team, Gaussian::from_moments(rating.mean, variance))
+                    })
+                    .collect()
+            })
+            .collect();
+
+        // The performance of each side, as the sum of its teams' noisy performances
+        let performances: Vec<Gaussian> = skills
+            .iter()
+            .map(|skills| {
+                let (mean, variance) =
+                    skills
+                        .iter()
+                        .fold((0.0, 0.0), |(mean, variance), (_, skill)| {
+                            (
+                                mean + skill.mean(),
+                                variance + skill.variance() + self.beta.powi(2),
+                            )
+                        });
+                Gaussian::from_moments(mean, variance)
+            })
+            .collect();
+
+        let evidence = self.compare_sides(sides, &skills, &performances);
+
+        for ((skills, evidence), performance) in skills.iter().zip(evidence).zip(&performances) {
+            if evidence.precision <= 0.0 {
+                continue;
+            }
+            for (team, skill) in skills.iter() {
+                // The evidence about the side's performance, less the other teams' part in it,
+                // is evidence about this team's performance and so its skill
+                let others_mean = performance.mean() - skill.mean();
+                let others_variance = performance.variance() - skill.variance() - self.beta.powi(2);
+                let message = Gaussian::from_moments(
+                    evidence.mean() - others_mean,
+                    evidence.variance() + others_variance + self.beta.powi(2),
+                );
+                let posterior = skill.multiply(message);
+
+                let rating = self.rating(team);
+                self.ratings.insert(
+                    (*team).into(),
+                    TrueSkillRating {
+                        mean: posterior.mean(),
+                        deviation: posterior.variance().sqrt(),
+                        rounds: rating.rounds + 1,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Passes messages along the chain of comparisons between neighbouring sides until they
+    /// settle, returning what the comparisons say about each side's performance.
+    fn compare_sides(
+        &self,
+        sides: &[Side],
+        skills: &[Vec<(&str, Gaussian)>],
+        performances: &[Gaussian],
+    ) -> Vec<Gaussian> {
+        let comparisons = sides.len() - 1;
+        // The messages from each comparison to the side above and below it
+        let mut to_above = vec![Gaussian::UNIFORM; comparisons];
+        let mut to_below = vec![Gaussian::UNIFORM; comparisons];
+
+        let draw_margin = |index: usize| {
+            let teams = skills[index].len() + skills[index + 1].len();
+            inverse_cdf((self.draw_probability + 1.0) / 2.0) * (teams as f64).sqrt() * self.beta
+        };
+
+        let order: Vec<usize> = (0..comparisons).chain((0..comparisons).rev()).collect();
+        for _ in 0..MAX_ITERATIONS {
+            let mut largest_change: f64 = 0.0;
+            for &index in order.iter() {
+                // Each side as known from everything but this comparison
+                let above = match index.checked_sub(1) {
+                    Some(previous) => performances[index].multiply(to_below[previous]),
+                    None => performances[index],
+                };
+                let below = match index + 1 < comparisons {
+                    true => performances[index + 1].multiply(to_above[index + 1]),
+                    false => performances[index + 1],
+                };
+
+                let difference = Gaussian::from_moments(
+                    above.mean() - below.mean(),
+                    above.variance() + below.variance(),
+                );
+                let truncated = truncate(
+                    difference,
+                    draw_margin(index),
+                    sides[index].rank == sides[index + 1].rank,
+                );
+                let message = truncated.divide(difference);
+                if message.precision <= 0.0 {
+                    continue;
+                }
+
+                let new_above = Gaussian::from_moments(
+                    message.mean() + below.mean(),
+                    message.variance() + below.variance(),
+                );
+                let new_below = Gaussian::from_moments(
+                    above.mean() - message.mean(),
+                    above.variance() + message.variance(),
+                );
+                largest_change = largest_change
+                    .max(change(to_above[index], new_above))
+                    .max(change(to_below[index], new_below));
+                to_above[index] = new_above;
+                to_below[index] = new_below;
+            }
+
+            if largest_change < CONVERGENCE {
+                break;
+            }
+        }
+
+        (0..sides.len())
+            .map(|index| {
+                let from_above = index.checked_sub(1).map(|above| to_below[above]);
+                let from_below = (index < comparisons).then(|| to_above[index]);
+                [from_above, from_below]
+                    .into_iter()
+                    .flatten()
+                    .fold(Gaussian::UNIFORM, Gaussian::multiply)
+            })
+            .collect()
+    }
+}
+
+/// A normal distribution in terms of its precision and precision adjusted mean, so that
+/// multiplying and dividing distributions is adding and subtracting.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Gaussian {
+    precision: f64,
+    precision_mean: f64,
+}
+
+impl Gaussian {
+    /// Says nothing, so multiplying by it changes nothing.
+    const UNIFORM: Gaussian = Gaussian {
+        precision: 0.0,
+        precision_mean: 0.0,
+    };
+
+    fn from_moments(mean: f64, variance: f64) -> Self {
+        Self {
+            precision: 1.0 / variance,
+            precision_mean: mean / variance,
+        }
+    }
+
+    fn mean(&self) -> f64 {
+        match self.precision {
+            0.0 => 0.0,
+            precision => self.precision_mean / precision,
+        }
+    }
+
+    fn variance(&self) -> f64 {
+        1.0 / self.precision
+    }
+
+    fn multiply(self, other: Self) -> Self {
+        Self {
+            precision: self.precision + other.precision,
+            precision_mean: self.precision_mean + other.precision_mean,
+        }
+    }
+
+    fn divide(self, other: Self) -> Self {
+        Self {
+            precision: self.precision - other.precision,
+            precision_mean: self.precision_mean - other.precision_mean,
+        }
+    }
+}
+
+/// How far a message moved, for checking the messages have settled.
+fn change(old: Gaussian, new: Gaussian) -> f64 {
+    match old.precision {
+        0.0 => f64::INFINITY,
+        _ => (old.mean() - new.mean()).abs(),
+    }
+}
+
+/// The distribution of the difference in performance once it is known that the side above won by
+/// more than the draw margin, or drew by being within it.
+fn truncate(difference: Gaussian, draw_margin: f64, draw: bool) -> Gaussian {
+    let deviation = difference.variance().sqrt();
+    let t = difference.mean() / deviation;
+    let e = draw_margin / deviation;
+    let (v, w) = match draw {
+        false => (v_win(t, e), w_win(t, e)),
+        true => (v_draw(t, e), w_draw(t, e)),
+    };
+    Gaussian::from_moments(
+        difference.mean() + deviation * v,
+        difference.variance() * (1.0 - w),
+    )
+}
+
+fn v_win(t: f64, e: f64) -> f64 {
+    let denominator = cdf(t - e);
+    match denominator < f64::MIN_POSITIVE {
+        true => e - t,
+        false => pdf(t - e) / denominator,
+    }
+}
+
+fn w_win(t: f64, e: f64) -> f64 {
+    if cdf(t - e) < f64::MIN_POSITIVE {
+        return if t < 0.0 { 1.0 } else { 0.0 };
+    }
+    let v = v_win(t, e);
+    v * (v + t - e)
+}
+
+fn v_draw(t: f64, e: f64) -> f64 {
+    let (a, b) = (e - t.abs(), -e - t.abs());
+    let denominator = cdf(a) - cdf(b);
+    let v = match denominator < f64::MIN_POSITIVE {
+        true => -t.abs() + e,
+        false => (pdf(b) - pdf(a)) / denominator,
+    };
+    if t < 0.0 { -v } else { v }
+}
+
+fn w_draw(t: f64, e: f64) -> f64 {
+    let (a, b) = (e - t.abs(), -e - t.abs());
+    let denominator = cdf(a) - cdf(b);
+    if denominator < f64::MIN_POSITIVE {
+        return 1.0;
+    }
+    let v = v_draw(t, e);
+    v * v + (a * pdf(a) - b * pdf(b)) / denominator
+}
+
+/// The standard normal density.
+fn pdf(x: f64) -> f64 {
+    (-x * x / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt()
+}
+
+/// The standard normal cumulative distribution.
+pub(crate) fn cdf(x: f64) -> f64 {
+    erfc(-x / SQRT_2) / 2.0
+}
+
+/// The complementary error function, from Numerical Recipes' Chebyshev fit, which is accurate to
+/// about 1.2e-7.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + z / 2.0);
+    let r = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+/// The inverse of [`cdf`], from Acklam's rational approximation.
+pub(crate) fn inverse_cdf(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.383_577_518_672_69e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    const LOW: f64 = 0.02425;
+
+    let p = p.clamp(f64::MIN_POSITIVE, 1.0 - f64::EPSILON);
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p < LOW {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - LOW {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{rating::RatingConfig, test_util::assert_close};
+
+    fn trueskill() -> TrueSkill {
+        let config = RatingConfig::default();
+        TrueSkill::new(
+            config.trueskill_mean,
+            config.trueskill_deviation,
+            config.trueskill_beta,
+            config.trueskill_tau,
+            config.draw_probability,
+        )
+    }
+
+    fn side(rank: usize, teams: &[&str]) -> Side {
+        Side {
+            rank,
+            teams: teams.iter().map(|team| (*team).into()).collect(),
+        }
+    }
+
+    fn assert_rating(rating: TrueSkillRating, mean: f64, deviation: f64) {
+        assert_close(rating.mean, mean, 0.001);
+        assert_close(rating.deviation, deviation, 0.001);
+    }
+
+    // The expected values are those of the reference `trueskill` package with its defaults
+
+    #[test]
+    fn rates_a_win_between_new_teams() {
+        let mut trueskill = trueskill();
+        trueskill.update(&[side(0, &["Winner"]), side(1, &["Loser"])]);
+
+        assert_rating(trueskill.rating("Winner"), 29.396, 7.171);
+        assert_rating(trueskill.rating("Loser"), 20.604, 7.171);
+        assert_eq!(trueskill.rating("Winner").rounds, 1);
+    }
+
+    #[test]
+    fn rates_a_draw_between_new_teams() {
+        let mut trueskill = trueskill();
+        trueskill.update(&[side(0, &["Alpha"]), side(0, &["Bravo"])]);
+
+        assert_rating(trueskill.rating("Alpha"), 25.0, 6.458);
+        assert_rating(trueskill.rating("Bravo"), 25.0, 6.458);
+    }
+
+    #[test]
+    fn rates_allied_teams_together() {
+        let mut trueskill = trueskill();
+        trueskill.update(&[side(0, &["Alpha", "Bravo"]), side(1, &["Charlie", "Delta"])]);
+
+        for team in ["Alpha", "Bravo"] {
+            assert_rating(trueskill.rating(team), 28.108, 7.774);
+        }
+        for team in ["Charlie", "Delta"] {
+            assert_rating(trueskill.rating(team), 21.892, 7.774);
+        }
+    }
+
+    #[test]
+    fn leaves_a_round_of_one_side_unrated() {
+        let mut trueskill = trueskill();
+        trueskill.update(&[side(0, &["Alpha", "Bravo"])]);
+
+        assert!(trueskill.leaderboard().is_empty());
+    }
+}
@@ -0,0 +1,597 @@
+//! Summarises the results store: how often each team wins overall, on each scheme and on each map,
+//! how the teams have done against each other, how often rounds are drawn and how long they take.
+//!
+//! A [`Report`] is written both as Markdown and as a single HTML file with its styles inline, so
+//! it can be opened or passed around without anything else. Win rates come with a 95% Wilson
+//! score interval, which unlike the usual normal interval stays between 0% and 100% and is still
+//! meaningful after only a few games.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    config::Config,
+    game::MapChoice,
+    rating::Standings,
+    store::{ResultStore, StoreError, StoredResult},
+};
+
+/// Standard deviations either side of the estimate for a 95% confidence interval.
+const Z_95: f64 = 1.959964;
+/// Stands in for a scheme or map that couldn't be read.
+const UNKNOWN: &str = "unknown";
+
+/// How a team finished a round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Won,
+    Drawn,
+    Lost,
+}
+
+impl Outcome {
+    /// The outcome for the other side.
+    fn reversed(self) -> Self {
+        match self {
+            Outcome::Won => Outcome::Lost,
+            Outcome::Drawn => Outcome::Drawn,
+            Outcome::Lost => Outcome::Won,
+        }
+    }
+}
+
+/// How a team did over some rounds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Tally {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The share of games won, or `0.0` without any games.
+    pub fn win_rate(&self) -> f64 {
+        share(self.wins, self.games())
+    }
+
+    /// The share of games drawn, or `0.0` without any games.
+    pub fn draw_rate(&self) -> f64 {
+        share(self.draws, self.games())
+    }
+
+    /// The 95% Wilson score interval of the win rate, or the whole range without any games.
+    pub fn win_interval(&self) -> (f64, f64) {
+        let games = self.games() as f64;
+        if games == 0.0 {
+            return (0.0, 1.0);
+        }
+
+        let rate = self.win_rate();
+        let spread = Z_95 * Z_95 / games;
+        let centre = (rate + spread / 2.0) / (1.0 + spread);
+        let margin =
+            Z_95 * (rate * (1.0 - rate) / games + spread / (4.0 * games)).sqrt() / (1.0 + spread);
+        ((centre - margin).max(0.0), (centre + margin).min(1.0))
+    }
+
+    fn add(&mut self, outcome: Outcome) {
+        match outcome {
+            Outcome::Won => self.wins += 1,
+            Outcome::Drawn => self.draws += 1,
+            Outcome::Lost => self.losses += 1,
+        }
+    }
+}
+
+impl Display for Tally {
+    /// Wins, draws and losses.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}-{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// Rounds, whoever played in them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RoundSummary {
+    pub rounds: u32,
+    pub draws: u32,
+    /// Of every round together.
+    pub duration: Duration,
+}
+
+impl RoundSummary {
+    /// The share of rounds drawn, or `0.0` without any rounds.
+    pub fn draw_rate(&self) -> f64 {
+        share(self.draws, self.rounds)
+    }
+
+    /// The mean duration of a round, or zero without any rounds.
+    pub fn average_duration(&self) -> Duration {
+        self.duration / self.rounds.max(1)
+    }
+
+    fn add(&mut self, draw: bool, duration: Duration) {
+        self.rounds += 1;
+        self.draws += draw as u32;
+        self.duration += duration;
+    }
+}
+
+/// The rounds split into groups, such as by scheme, with how each team did in each group.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Breakdown {
+    pub rounds: BTreeMap<Box<str>, RoundSummary>,
+    pub teams: BTreeMap<Box<str>, BTreeMap<Box<str>, Tally>>,
+}
+
+impl Breakdown {
+    pub fn is_empty(&self) -> bool {
+        self.rounds.is_empty()
+    }
+}
+
+/// The statistics of the stored results.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Every round, including those without a readable outcome.
+    pub rounds: RoundSummary,
+    /// Rounds whose outcome couldn't be read, which only count towards the round summaries.
+    pub unread: u32,
+    pub teams: BTreeMap<Box<str>, Tally>,
+    pub schemes: Breakdown,
+    pub maps: Breakdown,
+    /// By experiment variant, for the rounds played in experiments.
+    pub variants: Breakdown,
+    /// How the first team did against the second in the rounds they both played in on different
+    /// sides.
+    pub head_to_head: BTreeMap<(Box<str>, Box<str>), Tally>,
+}
+
+impl Report {
+    pub fn new(records: &[StoredResult]) -> Self {
+        let mut report = Self::default();
+        for record in records {
+            report.add(record);
+        }
+        report
+    }
+
+    fn add(&mut self, record: &StoredResult) {
+        let result = &record.result;
+        let scheme = result.scheme.as_deref().unwrap_or(UNKNOWN);
+        let map = result.map.as_ref().map_or(UNKNOWN.into(), map_name);
+        let variant = record
+            .variant
+            .as_ref()
+            .map(|tag| format!("{}: {}", tag.experiment, tag.variant));
+
+        let mut groups = vec![(&mut self.schemes, scheme), (&mut self.maps, &*map)];
+        if let Some(variant) = &variant {
+            groups.push((&mut self.variants, variant.as_str()));
+        }
+        let (draw, duration) = (result.outcome.draw, result.duration);
+        self.rounds.add(draw, duration);
+        for (breakdown, group) in groups.iter_mut() {
+            breakdown
+                .rounds
+                .entry((*group).into())
+                .or_default()
+                .add(draw, duration);
+        }
+
+        let Some(standings) = Standings::from_result(result) else {
+            self.unread += 1;
+            return;
+        };
+        let sides = standings.sides();
+        let best = sides.first().map_or(0, |side| side.rank);
+        let shared = sides.iter().filter(|side| side.rank == best).count() > 1;
+        for side in sides.iter() {
+            let outcome = match (side.rank == best, shared) {
+                (true, false) => Outcome::Won,
+                (true, true) => Outcome::Drawn,
+                (false, _) => Outcome::Lost,
+            };
+            for team in side.teams.iter() {
+                self.teams.entry(team.clone()).or_default().add(outcome);
+                for (breakdown, group) in groups.iter_mut() {
+                    breakdown
+                        .teams
+                        .entry((*group).into())
+                        .or_default()
+                        .entry(team.clone())
+                        .or_default()
+                        .add(outcome);
+                }
+            }
+        }
+
+        // Sides are in finishing order, so each finished no worse than every side after it
+        for (index, side) in sides.iter().enumerate() {
+            for other in sides[index + 1..].iter() {
+                let outcome = match side.rank == other.rank {
+                    true => Outcome::Drawn,
+                    false => Outcome::Won,
+                };
+                for team in side.teams.iter() {
+                    for opponent in other.teams.iter() {
+                        self.head_to_head
+                            .entry((team.clone(), opponent.clone()))
+                            .or_default()
+                            .add(outcome);
+                        self.head_to_head
+                            .entry((opponent.clone(), team.clone()))
+                            .or_default()
+                            .add(outcome.reversed());
+                    }
+                }
+            }
+        }
+    }
+
+    /// The report as Markdown.
+    pub fn markdown(&self) -> String {
+        let mut text = "# Worms results report\n".to_owned();
+        for table in self.tables() {
+            text.push_str(&format!("\n## {}\n\n", table.title));
+            if let Some(note) = table.note {
+                text.push_str(&format!("{note}\n\n"));
+            }
+            let row = |cells: &[String]| {
+                let cells: Vec<String> =
+                    cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+                format!("| {} |\n", cells.join(" | "))
+            };
+            text.push_str(&row(&table.headers));
+            text.push_str(&row(&vec!["---".to_owned(); table.headers.len()]));
+            for cells in table.rows.iter() {
+                text.push_str(&row(cells));
+            }
+        }
+        text
+    }
+
+    /// The report as an HTML page that needs no other files.
+    pub fn html(&self) -> String {
+        let mut text = concat!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n",
+            "<title>Worms results report</title>\n<style>\n",
+            "body { font-family: sans-serif; margin: 2em; color: #222; }\n",
+            "table { border-collapse: collapse; margin-bottom: 1em; }\n",
+            "th, td { border: 1px solid #ccc; padding: 0.3em 0.7em; text-align: right; }\n",
+            "th:first-child, td:first-child { text-align: left; }\n",
+            "th { background: #eee; }\n",
+            "tr:nth-child(even) td { background: #f8f8f8; }\n",
+            "</style>\n</head>\n<body>\n<h1>Worms results report</h1>\n",
+        )
+        .to_owned();
+        for table in self.tables() {
+            text.push_str(&format!("<h2>{}</h2>\n", escape(&table.title)));
+            if let Some(note) = table.note {
+                text.push_str(&format!("<p>{}</p>\n", escape(note)));
+            }
+            text.push_str("<table>\n<tr>");
+            for header in table.headers.iter() {
+                text.push_str(&format!("<th>{}</th>", escape(header)));
+            }
+            text.push_str("</tr>\n");
+            for cells in table.rows.iter() {
+                text.push_str("<tr>");
+                for cell in cells {
+                    text.push_str(&format!("<td>{}</td>", escape(cell)));
+                }
+                text.push_str("</tr>\n");
+            }
+            text.push_str("</table>\n");
+        }
+        text.push_str("</body>\n</html>\n");
+        text
+    }
+
+    /// Writes the report next to the path, as Markdown with a `.md` extension and as HTML with
+    /// a `.html` extension. Returns the paths written.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<[PathBuf; 2], ReportError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let (markdown, html) = (path.with_extension("md"), path.with_extension("html"));
+        std::fs::write(&markdown, self.markdown())?;
+        std::fs::write(&html, self.html())?;
+        Ok([markdown, html])
+    }
+
+    fn tables(&self) -> Vec<Table> {
+        let mut tables = vec![
+            Table {
+                title: "Summary".to_owned(),
+                note: None,
+                headers: headers(&["Statistic", "Value"]),
+                rows: vec![
+                    vec!["Rounds".to_owned(), self.rounds.rounds.to_string()],
+                    vec![
+                        "Rounds without a readable outcome".to_owned(),
+                        self.unread.to_string(),
+                    ],
+                    vec![
+                        "Draws".to_owned(),
+                        format!(
+                            "{} ({})",
+                            self.rounds.draws,
+                            percent(self.rounds.draw_rate())
+                        ),
+                    ],
+                    vec![
+                        "Average duration".to_owned(),
+                        minutes(self.rounds.average_duration()),
+                    ],
+                    vec!["Teams".to_owned(), self.teams.len().to_string()],
+                ],
+            },
+            Table {
+                title: "Teams".to_owned(),
+                note: Some("Best win rate first, with its 95% confidence interval."),
+                headers: headers(&[
+                    "Team",
+                    "Games",
+                    "Won",
+                    "Drawn",
+                    "Lost",
+                    "Win rate",
+                    "95% CI",
+                    "Draw rate",
+                ]),
+                rows: by_win_rate(&self.teams)
+                    .into_iter()
+                    .map(|(team, tally)| {
+                        let mut row = vec![team.to_owned()];
+                        row.extend(tally_cells(tally));
+                        row.push(percent(tally.draw_rate()));
+                        row
+                    })
+                    .collect(),
+            },
+        ];
+
+        for (breakdown, name) in [
+            (&self.schemes, "Scheme"),
+            (&self.maps, "Map"),
+            (&self.variants, "Variant"),
+        ] {
+            if breakdown.is_empty() {
+                continue;
+            }
+            tables.push(Table {
+                title: format!("{name}s"),
+                note: None,
+                headers: headers(&[name, "Rounds", "Draw rate", "Average duration"]),
+                rows: breakdown
+                    .rounds
+                    .iter()
+                    .map(|(group, summary)| {
+                        vec![
+                            group.to_string(),
+                            summary.rounds.to_string(),
+                            percent(summary.draw_rate()),
+                            minutes(summary.average_duration()),
+                        ]
+                    })
+                    .collect(),
+            });
+            tables.push(Table {
+                title: format!("Teams by {}", name.to_lowercase()),
+                note: None,
+                headers: headers(&[
+                    name, "Team", "Games", "Won", "Drawn", "Lost", "Win rate", "95% CI",
+                ]),
+                rows: breakdown
+                    .teams
+                    .iter()
+                    .flat_map(|(group, teams)| {
+                        by_win_rate(teams).into_iter().map(|(team, tally)| {
+                            let mut row = vec![group.to_string(), team.to_owned()];
+                            row.extend(tally_cells(tally));
+                            row
+                        })
+                    })
+                    .collect(),
+            });
+        }
+
+        let teams: Vec<&str> = self.teams.keys().map(|team| &**team).collect();
+        let mut columns = vec![String::new()];
+        columns.extend(teams.iter().map(|team| team.to_string()));
+        tables.push(Table {
+            title: "Head to head".to_owned(),
+            note: Some(
+                "Wins, draws and losses of each row's team against each column's team, \
+                 in rounds they played on different sides.",
+            ),
+            headers: columns,
+            rows: teams
+                .iter()
+                .map(|&team| {
+                    let mut row = vec![team.to_owned()];
+                    row.extend(teams.iter().map(|&opponent| {
+                        match self.head_to_head.get(&(team.into(), opponent.into())) {
+                            _ if team == opponent => String::new(),
+                            Some(tally) => format!("{tally} ({})", percent(tally.win_rate())),
+                            None => "-".to_owned(),
+                        }
+                    }));
+                    row
+                })
+                .collect(),
+        });
+        tables
+    }
+}
+
+/// Loads the results in the config's store and saves their report to the config's report path.
+/// Returns the paths written.
+pub fn generate(config: &Config) -> Result<[PathBuf; 2], ReportError> {
+    let records = ResultStore::new(config.resolve(&config.results.file)).load()?;
+    Report::new(&records).save(config.resolve(&config.results.report))
+}
+
+/// A table of the report, before it is written in either format.
+struct Table {
+    title: String,
+    note: Option<&'static str>,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+fn headers(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+/// The teams with the best win rate first, then by name.
+fn by_win_rate(teams: &BTreeMap<Box<str>, Tally>) -> Vec<(&str, &Tally)> {
+    let mut teams: Vec<(&str, &Tally)> =
+        teams.iter().map(|(team, tally)| (&**team, tally)).collect();
+    teams.sort_by(|(_, a), (_, b)| b.win_rate().total_cmp(&a.win_rate()));
+    teams
+}
+
+/// The games, wins, draws, losses, win rate and its interval.
+fn tally_cells(tally: &Tally) -> [String; 6] {
+    let (low, high) = tally.win_interval();
+    [
+        tally.games().to_string(),
+        tally.wins.to_string(),
+        tally.draws.to_string(),
+        tally.losses.to_string(),
+        percent(tally.win_rate()),
+        format!("{} to {}", percent(low), percent(high)),
+    ]
+}
+
+/// A short name for the map to group rounds by.
+fn map_name(map: &MapChoice) -> Box<str> {
+    match map {
+        MapChoice::Random => "random".into(),
+        MapChoice::Seeded(seed) => format!("seed {seed}").into(),
+        MapChoice::File(name) => name.clone(),
+    }
+}
+
+fn share(count: u32, total: u32) -> f64 {
+    match total {
+        0 => 0.0,
+        total => count as f64 / total as f64,
+    }
+}
+
+fn percent(share: f64) -> String {
+    format!("{:.1}%", share * 100.0)
+}
+
+fn minutes(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{}m {:02}s", secs / 60, secs % 60)
+}
+
+/// Escapes the text for HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The reason a report could not be made.
+#[derive(Debug)]
+pub enum ReportError {
+    Store(StoreError),
+    Io(std::io::Error),
+}
+
+impl Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportError::Store(err) => write!(f, "{err}"),
+            ReportError::Io(err) => write!(f, "Unable to write the report: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ReportError {}
+
+impl From<StoreError> for ReportError {
+    fn from(value: StoreError) -> Self {
+        Self::Store(value)
+    }
+}
+
+impl From<std::io::Error> for ReportError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_interval(tally: Tally, expected: (f64, f64)) {
+        let (low, high) = tally.win_interval();
+        assert!(
+            (low - expected.0).abs() < 1e-4 && (high - expected.1).abs() < 1e-4,
+            "({low}, {high}) isn't {expected:?}"
+        );
+    }
+
+    #[test]
+    fn wilson_interval() {
+        assert_eq!(Tally::default().win_interval(), (0.0, 1.0));
+        let tally = |wins, losses| Tally {
+            wins,
+            draws: 0,
+            losses,
+        };
+        assert_interval(tally(5, 5), (0.2366, 0.7634));
+        assert_interval(tally(10, 0), (0.7225, 1.0));
+        assert_interval(tally(0, 10), (0.0, 0.2775));
+        // Draws count as games that weren't won
+        let drawn = Tally {
+            wins: 3,
+            draws: 1,
+            losses: 0,
+        };
+        assert_interval(drawn, (0.3006, 0.9544));
+    }
+
+    #[test]
+    fn rates_without_games() {
+        let tally = Tally::default();
+        assert_eq!(tally.win_rate(), 0.0);
+        assert_eq!(tally.draw_rate(), 0.0);
+        assert_eq!(RoundSummary::default().average_duration(), Duration::ZERO);
+    }
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape(r#"<b>"Tom & Jerry"</b>"#),
+            "&lt;b&gt;&quot;Tom &amp; Jerry&quot;&lt;/b&gt;"
+        );
+        assert_eq!(escape("&lt;"), "&amp;lt;");
+    }
+
+    #[test]
+    fn an_empty_report_has_no_teams() {
+        let report = Report::new(&[]);
+        assert_eq!(report, Report::default());
+        assert!(report.html().contains("<h2>Summary</h2>"));
+    }
+}
@@ -0,0 +1,237 @@
+//! Reads the outcome of a round from the round results screen.
+//!
+//! The results are laid out as a table, so the windows are grouped into rows by their position.
+//! Each row that holds the name of a team in the game is that team's placement, in order from the
+//! top, and the other cells of the row are its stats, named by the header row above when there is
+//! one. The outcome is read from the line on its own that names the winner or says the round was
+//! drawn, compared whole so team names and headings that contain the marker text don't count.
+//! Parsing only needs a [`Found`] tree, so it works the same on a snapshot loaded with
+//! [`crate::snapshot::Snapshot::to_backend`].
+
+use std::{collections::BTreeMap, fmt::Display, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::{Backend, Rect, WindowId},
+    config,
+    game::{GameOptions, MapChoice},
+    lobby::SelectedTeam,
+    window::Found,
+};
+
+/// How a round ended, as read from the round results screen.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundOutcome {
+    /// `None` for a draw, or when no winner was shown.
+    pub winner: Option<Box<str>>,
+    pub draw: bool,
+    /// Every team found in the results, best first.
+    pub placements: Vec<Placement>,
+}
+
+/// Where a team finished and the stats shown for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Placement {
+    pub team: Box<str>,
+    /// Counting from `1`.
+    pub place: usize,
+    /// Each cell of the team's row by its column header, or `column N` when there is no header.
+    pub stats: BTreeMap<Box<str>, Box<str>>,
+}
+
+/// Everything recorded about a round that was played out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundResult {
+    /// Counting from `1`.
+    pub round: u32,
+    /// Seconds since the unix epoch when the round results were shown.
+    pub finished_at: u64,
+    /// From clicking play until the round results were shown.
+    pub duration: Duration,
+    /// The teams in the game as read from the lobby before playing.
+    pub lineup: Vec<SelectedTeam>,
+    /// `None` if the scheme couldn't be read.
+    pub scheme: Option<Box<str>>,
+    /// The options that were asked for, as the scheme decides the rest.
+    pub options: GameOptions,
+    /// `None` if the map couldn't be read.
+    pub map: Option<MapChoice>,
+    pub outcome: RoundOutcome,
+}
+
+impl Display for RoundResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Round {}: {} teams", self.round, self.lineup.len())?;
+        if let Some(map) = &self.map {
+            write!(f, " on {map}")?;
+        }
+        match (&self.outcome.winner, self.outcome.draw) {
+            (_, true) => write!(f, ", drawn")?,
+            (Some(winner), false) => write!(f, ", won by {winner}")?,
+            (None, false) => write!(f, ", no winner shown")?,
+        }
+        write!(f, ", finished after {:.0?}", self.duration)
+    }
+}
+
+/// A window with text and its position.
+struct Cell {
+    text: Box<str>,
+    rect: Rect,
+}
+
+/// Reads the outcome from the round results windows under root, looking for the teams by name.
+pub fn parse(backend: &dyn Backend, root: &Found, teams: &[SelectedTeam]) -> RoundOutcome {
+    let markers = &config::get().automation.markers;
+    let mut cells = Vec::new();
+    collect_cells(backend, root, &mut cells);
+    let rows = into_rows(cells);
+
+    let team_named = |text: &str| {
+        teams
+            .iter()
+            .find(|team| team.name.trim().eq_ignore_ascii_case(text.trim()))
+    };
+    let team_rows: Vec<(usize, &SelectedTeam)> = rows
+        .iter()
+        .enumerate()
+        .filter_map(|(index, row)| {
+            row.iter()
+                .find_map(|cell| team_named(&cell.text))
+                .map(|team| (index, team))
+        })
+        .collect();
+
+    // The header is the nearest row above the first team that has several cells
+    let header = team_rows.first().and_then(|(first, _)| {
+        rows[..*first]
+            .iter()
+            .rev()
+            .find(|row| row.len() > 1 && row.iter().all(|cell| team_named(&cell.text).is_none()))
+    });
+
+    let placements: Vec<Placement> = team_rows
+        .iter()
+        .enumerate()
+        .map(|(index, (row, team))| Placement {
+            team: team.name.clone(),
+            place: index + 1,
+            stats: row_stats(&rows[*row], &team.name, header),
+        })
+        .collect();
+
+    // The outcome is shown on a line of its own, apart from the teams and the header
+    let lines: Vec<&str> = rows
+        .iter()
+        .enumerate()
+        .filter(|(index, row)| {
+            row.len() == 1
+                && team_rows.iter().all(|(team_row, _)| team_row != index)
+                && header.is_none_or(|header| !std::ptr::eq(header, *row))
+        })
+        .map(|(_, row)| line_text(&row[0].text))
+        .collect();
+    let draw = lines
+        .iter()
+        .any(|line| line.eq_ignore_ascii_case(markers.draw_text.trim()));
+    let winner = (!draw)
+        .then(|| {
+            lines.iter().find_map(|line| {
+                let name = strip_marker(line, markers.winner_text.trim())?;
+                team_named(name.trim_matches(|c: char| c.is_whitespace() || c == ':'))
+            })
+        })
+        .flatten()
+        .map(|team| team.name.clone());
+
+    RoundOutcome {
+        winner,
+        draw,
+        placements,
+    }
+}
+
+/// Reads the outcome from the round results shown in the window.
+pub fn read(backend: &dyn Backend, window_id: WindowId, teams: &[SelectedTeam]) -> RoundOutcome {
+    parse(
+        backend,
+        &crate::window::get_windows(backend, window_id),
+        teams,
+    )
+}
+
+/// The text of a result line without the space and closing punctuation around it, such as `!`.
+fn line_text(text: &str) -> &str {
+    text.trim()
+        .trim_end_matches(|c: char| c.is_ascii_punctuation())
+        .trim_end()
+}
+
+/// The rest of the line when it starts or ends with the marker, ignoring case.
+fn strip_marker<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let split = line.len().checked_sub(marker.len())?;
+    if line.is_char_boundary(split) && line[split..].eq_ignore_ascii_case(marker) {
+        return Some(&line[..split]);
+    }
+    if line.is_char_boundary(marker.len()) && line[..marker.len()].eq_ignore_ascii_case(marker) {
+        return Some(&line[marker.len()..]);
+    }
+    None
+}
+
+fn collect_cells(backend: &dyn Backend, found: &Found, cells: &mut Vec<Cell>) {
+    for child in found.children() {
+        let text = backend.text(child.value());
+        if !text.trim().is_empty()
+            && let Some(rect) = backend.rect(child.value())
+        {
+            cells.push(Cell {
+                text: text.trim().into(),
+                rect,
+            });
+        }
+        collect_cells(backend, child, cells);
+    }
+}
+
+/// Groups cells whose vertical centres lie within each other, ordered top to bottom and then
+/// left to right.
+fn into_rows(mut cells: Vec<Cell>) -> Vec<Vec<Cell>> {
+    cells.sort_by_key(|cell| (cell.rect.center().1, cell.rect.left));
+
+    let mut rows: Vec<Vec<Cell>> = Vec::new();
+    for cell in cells {
+        let (_, y) = cell.rect.center();
+        match rows.last_mut() {
+            Some(row) if row[0].rect.top <= y && y <= row[0].rect.bottom => row.push(cell),
+            _ => rows.push(vec![cell]),
+        }
+    }
+    for row in rows.iter_mut() {
+        row.sort_by_key(|cell| cell.rect.left);
+    }
+    rows
+}
+
+/// Names every cell of a team's row but its name by the header cell nearest above it.
+fn row_stats(row: &[Cell], team: &str, header: Option<&Vec<Cell>>) -> BTreeMap<Box<str>, Box<str>> {
+    row.iter()
+        .filter(|cell| !cell.text.eq_ignore_ascii_case(team.trim()))
+        .enumerate()
+        .map(|(index, cell)| {
+            let (x, _) = cell.rect.center();
+            let name = header
+                .and_then(|header| {
+                    header
+                        .iter()
+                        .min_by_key(|heading| (heading.rect.center().0 - x).abs())
+                })
+                .map_or_else(
+                    || format!("column {}", index + 1).into(),
+                    |heading| heading.text.clone(),
+                );
+            (name, cell.text.clone())
+        })
+        .collect()
+}
@@ -0,0 +1,228 @@
+//! Works out which WA frontend screen is currently shown.
+//!
+//! Each screen has markers, which are selectors for windows that only exist on that screen.
+//! The screen whose markers match with the most weight is the recognised screen.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    automation::selector,
+    backend::Backend,
+    config,
+    lobby::TEAM_LIST,
+    selector::{Selector, quote},
+    window::{Found, get_process_windows},
+};
+
+/// A screen of the WA frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Screen {
+    MainMenu,
+    /// The lobby for creating a single player game.
+    SinglePlayerLobby,
+    /// The lobby for creating an offline multiplayer game.
+    MultiplayerLobby,
+    TeamEditor,
+    InGame,
+    RoundResults,
+    ErrorDialog,
+    Unknown,
+}
+
+impl Screen {
+    pub fn is_lobby(&self) -> bool {
+        matches!(self, Screen::SinglePlayerLobby | Screen::MultiplayerLobby)
+    }
+}
+
+impl Display for Screen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Screen::MainMenu => "Main Menu",
+            Screen::SinglePlayerLobby => "Single Player Lobby",
+            Screen::MultiplayerLobby => "Multiplayer Lobby",
+            Screen::TeamEditor => "Team Editor",
+            Screen::InGame => "In Game",
+            Screen::RoundResults => "Round Results",
+            Screen::ErrorDialog => "Error Dialog",
+            Screen::Unknown => "Unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Evidence that a screen is shown.
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub screen: Screen,
+    pub selector: Selector,
+    /// How strongly a match indicates the screen, relative to the screen's other markers.
+    pub weight: f32,
+    pub description: Box<str>,
+    /// Matched against the other top level windows of the process, rather than the frontend.
+    pub dialog: bool,
+}
+
+impl Marker {
+    pub fn new(screen: Screen, selector: Selector, weight: f32, description: &str) -> Self {
+        Self {
+            screen,
+            selector,
+            weight,
+            description: description.into(),
+            dialog: false,
+        }
+    }
+
+    /// Matches the marker against the dialogs the frontend has open, which aren't its children.
+    pub fn in_dialogs(mut self) -> Self {
+        self.dialog = true;
+        self
+    }
+}
+
+/// The screen that was recognised and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recognition {
+    pub screen: Screen,
+    /// Between `0` and `1`; the share of the screen's marker weight that matched.
+    pub confidence: f32,
+    /// The descriptions of the markers that matched for the screen.
+    pub evidence: Vec<Box<str>>,
+}
+
+impl Display for Recognition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({:.0}%): {}",
+            self.screen,
+            self.confidence * 100.0,
+            self.evidence.join("; ")
+        )
+    }
+}
+
+/// Recognises screens from a set of markers.
+#[derive(Debug, Clone)]
+pub struct Recognizer {
+    markers: Vec<Marker>,
+}
+
+impl Default for Recognizer {
+    /// Recognises screens using the markers known for WA, with the configured text.
+    fn default() -> Self {
+        use Screen::*;
+
+        let text = &config::get().automation.markers;
+
+        Self::new(vec![
+            Marker::new(
+                MainMenu,
+                selector(&format!("[text^={}]", quote(&text.create_game_menu))),
+                1.0,
+                "Create game button",
+            ),
+            Marker::new(MultiplayerLobby, TEAM_LIST.clone(), 0.5, "Team list"),
+            Marker::new(
+                MultiplayerLobby,
+                selector(&format!(
+                    "> :nth(0) > :nth(9)[text*={}]",
+                    quote(&text.add_team_message)
+                )),
+                1.0,
+                "Add team message",
+            ),
+            Marker::new(
+                SinglePlayerLobby,
+                selector(r#"[text~="(?i)single player"]"#),
+                1.0,
+                "Single player title",
+            ),
+            Marker::new(
+                TeamEditor,
+                selector(r#"[text~="(?i)team editor"]"#),
+                1.0,
+                "Team editor title",
+            ),
+            Marker::new(
+                RoundResults,
+                selector(&format!("[text*={}]", quote(&text.round_results_text))),
+                1.0,
+                "Round results title",
+            ),
+            // Outweighs every other screen, as the dialog blocks the frontend under it
+            Marker::new(
+                ErrorDialog,
+                selector(r##"[class="#32770"] Static[text~="(?i)error"]"##),
+                2.0,
+                "Dialog describing an error",
+            )
+            .in_dialogs(),
+        ])
+    }
+}
+
+impl Recognizer {
+    pub fn new(markers: Vec<Marker>) -> Self {
+        Self { markers }
+    }
+
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    /// Recognises the screen shown by the windows under root.
+    pub fn recognise(&self, backend: &dyn Backend, root: &Found) -> Recognition {
+        // The frontend windows are destroyed while the game is running
+        if root.children().is_empty() {
+            return Recognition {
+                screen: Screen::InGame,
+                confidence: 0.75,
+                evidence: vec!["No frontend windows".into()],
+            };
+        }
+
+        let dialogs = get_process_windows(backend, root.value());
+
+        // (screen, matched weight, total weight, evidence)
+        let mut scores: Vec<(Screen, f32, f32, Vec<Box<str>>)> = Vec::new();
+        for marker in self.markers.iter() {
+            let index = match scores.iter().position(|score| score.0 == marker.screen) {
+                Some(index) => index,
+                None => {
+                    scores.push((marker.screen, 0.0, 0.0, Vec::new()));
+                    scores.len() - 1
+                }
+            };
+            let score = &mut scores[index];
+
+            score.2 += marker.weight;
+            let windows = if marker.dialog { &dialogs } else { root };
+            if !marker.selector.select_all(backend, windows).is_empty() {
+                score.1 += marker.weight;
+                score.3.push(marker.description.clone());
+            }
+        }
+
+        scores
+            .into_iter()
+            .filter(|(_, matched, _, _)| *matched > 0.0)
+            .max_by(|a, b| {
+                a.1.total_cmp(&b.1)
+                    .then((a.1 / a.2).total_cmp(&(b.1 / b.2)))
+            })
+            .map(|(screen, matched, total, evidence)| Recognition {
+                screen,
+                confidence: matched / total,
+                evidence,
+            })
+            .unwrap_or_else(|| Recognition {
+                screen: Screen::Unknown,
+                confidence: 0.0,
+                evidence: vec!["No markers matched".into()],
+            })
+    }
+}
@@ -0,0 +1,625 @@
+//! A small selector language for locating windows within a [`Found`] tree.
+//!
+//! A selector is a sequence of compound selectors joined by combinators, similar to CSS:
+//! - `Button` matches windows with the class name `Button` (case insensitive). `*` matches any class.
+//! - `#38` matches windows with the control ID `38`.
+//! - `:nth(2)` matches windows that are the child at index `2` of their parent.
+//! - `[text="..."]` matches the exact window text, `[text^="..."]` a prefix,
+//!   `[text*="..."]` a substring and `[text~="..."]` a regex. `[class="..."]` matches a class name
+//!   that can't be written as a bare identifier, such as `#32770`.
+//! - `a b` matches `b` when it is any descendant of `a`; `a > b` when it is a direct child of `a`.
+//!
+//! Selectors are matched against the descendants of the root window, never the root itself.
+//! A leading `>` anchors the first compound selector to the direct children of the root.
+//!
+//! `> :nth(0) > Button[text*="Play"]` selects the button containing "Play" that is a child of the
+//! first child of the root.
+
+use std::fmt::Display;
+
+use regex::Regex;
+
+use crate::{
+    backend::{Backend, WindowId},
+    window::Found,
+};
+
+/// A parsed selector.
+#[derive(Debug, Clone)]
+pub struct Selector {
+    source: Box<str>,
+    steps: Vec<Step>,
+}
+
+/// A compound selector and how it relates to the previous step.
+#[derive(Debug, Clone)]
+struct Step {
+    combinator: Combinator,
+    compound: Compound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    /// Any level below the previous step.
+    Descendant,
+    /// Directly below the previous step.
+    Child,
+}
+
+/// Every condition must hold for a window to match.
+#[derive(Debug, Clone, Default)]
+struct Compound {
+    conditions: Vec<Condition>,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Class(Box<str>),
+    ControlId(i32),
+    Index(usize),
+    TextEquals(Box<str>),
+    TextStartsWith(Box<str>),
+    TextContains(Box<str>),
+    TextMatches(Regex),
+}
+
+impl Selector {
+    /// Parses a selector from the given source text.
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        Parser::new(source).parse()
+    }
+
+    /// Gets every window under root matching this selector, in tree order.
+    pub fn select_all<'a>(&self, backend: &dyn Backend, root: &'a Found) -> Vec<&'a Found> {
+        let mut matched = Vec::new();
+        let mut chain = vec![root];
+        self.collect(backend, root, &mut chain, &mut Vec::new(), &mut matched);
+        matched
+    }
+
+    /// Gets the single window under root matching this selector.
+    pub fn select_one<'a>(
+        &self,
+        backend: &dyn Backend,
+        root: &'a Found,
+    ) -> Result<&'a Found, SelectError> {
+        match self.select_all(backend, root).as_slice() {
+            [] => Err(SelectError::NoMatch {
+                selector: self.source.clone(),
+            }),
+            [found] => Ok(found),
+            matched => Err(SelectError::Ambiguous {
+                selector: self.source.clone(),
+                count: matched.len(),
+            }),
+        }
+    }
+
+    /// Walks the tree, keeping the chain of ancestors and their child indexes to match against.
+    fn collect<'a>(
+        &self,
+        backend: &dyn Backend,
+        node: &'a Found,
+        chain: &mut Vec<&'a Found>,
+        indexes: &mut Vec<usize>,
+        matched: &mut Vec<&'a Found>,
+    ) {
+        for (index, child) in node.children().iter().enumerate() {
+            chain.push(child);
+            indexes.push(index);
+
+            if self.matches_at(
+                backend,
+                self.steps.len() - 1,
+                chain,
+                indexes,
+                chain.len() - 1,
+            ) {
+                matched.push(child);
+            }
+            self.collect(backend, child, chain, indexes, matched);
+
+            chain.pop();
+            indexes.pop();
+        }
+    }
+
+    /// Whether the step at `step` matches the window at `node` in the chain, with all previous
+    /// steps matching its ancestors.
+    ///
+    /// The root is at the start of the chain and is never matched against.
+    fn matches_at(
+        &self,
+        backend: &dyn Backend,
+        step: usize,
+        chain: &[&Found],
+        indexes: &[usize],
+        node: usize,
+    ) -> bool {
+        let Step {
+            combinator,
+            compound,
+        } = &self.steps[step];
+
+        // Indexes are offset by one as the root has no index
+        if !compound.matches(backend, chain[node].value(), indexes[node - 1]) {
+            return false;
+        }
+
+        match (step, combinator) {
+            (0, Combinator::Child) => node == 1,
+            (0, Combinator::Descendant) => true,
+            (_, Combinator::Child) => {
+                node > 1 && self.matches_at(backend, step - 1, chain, indexes, node - 1)
+            }
+            (_, Combinator::Descendant) => (1..node)
+                .rev()
+                .any(|ancestor| self.matches_at(backend, step - 1, chain, indexes, ancestor)),
+        }
+    }
+}
+
+impl Display for Selector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Compound {
+    fn matches(&self, backend: &dyn Backend, id: WindowId, index: usize) -> bool {
+        // Only query the window text once, and only if needed
+        let mut cached_text = None;
+        let mut text = || cached_text.get_or_insert_with(|| backend.text(id)).clone();
+
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Class(class) => backend.class_name(id).eq_ignore_ascii_case(class),
+            Condition::ControlId(control_id) => backend.control_id(id) == *control_id,
+            Condition::Index(expected) => index == *expected,
+            Condition::TextEquals(expected) => text() == **expected,
+            Condition::TextStartsWith(prefix) => text().starts_with(&**prefix),
+            Condition::TextContains(part) => text().contains(&**part),
+            Condition::TextMatches(regex) => regex.is_match(&text()),
+        })
+    }
+}
+
+/// The reason a selector could not resolve to a single window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectError {
+    /// No window matched the selector.
+    NoMatch { selector: Box<str> },
+    /// More than one window matched the selector.
+    Ambiguous { selector: Box<str>, count: usize },
+}
+
+impl Display for SelectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectError::NoMatch { selector } => write!(f, "No window matches '{selector}'"),
+            SelectError::Ambiguous { selector, count } => {
+                write!(f, "{count} windows match '{selector}', expected one")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SelectError {}
+
+/// A selector could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The byte offset into the source where the error occurred.
+    pub position: usize,
+    pub message: Box<str>,
+    pub source: Box<str>,
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid selector '{}' at position {}: {}",
+            self.source, self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+struct Parser<'a> {
+    source: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            position: 0,
+        }
+    }
+
+    fn parse(mut self) -> Result<Selector, ParseError> {
+        let mut steps = Vec::new();
+
+        self.skip_whitespace();
+        let mut combinator = Combinator::Descendant;
+        if self.eat('>') {
+            combinator = Combinator::Child;
+        }
+
+        loop {
+            self.skip_whitespace();
+            let compound = self.compound()?;
+            steps.push(Step {
+                combinator,
+                compound,
+            });
+
+            let had_whitespace = self.skip_whitespace();
+            combinator = match self.peek() {
+                None => break,
+                Some('>') => {
+                    self.position += 1;
+                    Combinator::Child
+                }
+                Some(_) if had_whitespace => Combinator::Descendant,
+                Some(c) => return Err(self.error(format!("Unexpected '{c}'"))),
+            };
+        }
+
+        Ok(Selector {
+            source: self.source.into(),
+            steps,
+        })
+    }
+
+    fn compound(&mut self) -> Result<Compound, ParseError> {
+        let mut compound = Compound::default();
+
+        let universal = self.eat('*');
+        if !universal && self.peek().is_some_and(is_ident_char) {
+            compound
+                .conditions
+                .push(Condition::Class(self.ident().into()));
+        }
+
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.position += 1;
+                    let id = self.number()?;
+                    let id =
+                        i32::try_from(id).map_err(|_| self.error("Control ID is too large"))?;
+                    compound.conditions.push(Condition::ControlId(id));
+                }
+                Some(':') => {
+                    self.position += 1;
+                    let pseudo = self.ident();
+                    if pseudo != "nth" {
+                        return Err(self.error(format!("Unknown pseudo class ':{pseudo}'")));
+                    }
+                    self.expect('(')?;
+                    self.skip_whitespace();
+                    let index = self.number()?;
+                    self.skip_whitespace();
+                    self.expect(')')?;
+                    compound.conditions.push(Condition::Index(index));
+                }
+                Some('[') => {
+                    self.position += 1;
+                    compound.conditions.push(self.attribute()?);
+                }
+                _ => break,
+            }
+        }
+
+        // An empty compound is only allowed when written as `*`
+        if compound.conditions.is_empty() && !universal {
+            return Err(self.error("Expected a selector"));
+        }
+
+        Ok(compound)
+    }
+
+    fn attribute(&mut self) -> Result<Condition, ParseError> {
+        self.skip_whitespace();
+        let name_start = self.position;
+        let name = self.ident();
+        if !matches!(name, "text" | "class") {
+            return Err(ParseError {
+                position: name_start,
+                message: format!("Unknown attribute '{name}'").into(),
+                source: self.source.into(),
+            });
+        }
+        let operator_start = self.position;
+        let operator: String = self.source[self.position..]
+            .chars()
+            .take_while(|c| matches!(c, '=' | '^' | '*' | '~'))
+            .collect();
+        self.position += operator.len();
+        let value = self.string()?;
+        self.skip_whitespace();
+        self.expect(']')?;
+
+        let condition = match (name, operator.as_str()) {
+            ("text", "=") => Condition::TextEquals(value.into()),
+            ("text", "^=") => Condition::TextStartsWith(value.into()),
+            ("text", "*=") => Condition::TextContains(value.into()),
+            ("text", "~=") => Condition::TextMatches(
+                Regex::new(&value).map_err(|err| self.error(format!("Invalid regex: {err}")))?,
+            ),
+            ("class", "=") => Condition::Class(value.into()),
+            _ => {
+                return Err(ParseError {
+                    position: operator_start,
+                    message: format!("Unsupported operator '{operator}' for '{name}'").into(),
+                    source: self.source.into(),
+                });
+            }
+        };
+
+        Ok(condition)
+    }
+
+    /// Parses a single or double quoted string, with `\` escaping the next character.
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.skip_whitespace();
+        let quote = match self.peek() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err(self.error("Expected a quoted string")),
+        };
+        self.position += 1;
+
+        let mut value = String::new();
+        let mut chars = self.source[self.position..].chars();
+        loop {
+            let Some(c) = chars.next() else {
+                return Err(self.error("Unterminated string"));
+            };
+            self.position += c.len_utf8();
+
+            match c {
+                '\\' => {
+                    let Some(escaped) = chars.next() else {
+                        return Err(self.error("Unterminated string"));
+                    };
+                    self.position += escaped.len_utf8();
+                    value.push(escaped);
+                }
+                c if c == quote => return Ok(value),
+                c => value.push(c),
+            }
+        }
+    }
+
+    fn ident(&mut self) -> &'a str {
+        let start = self.position;
+        let len: usize = self.source[start..]
+            .chars()
+            .take_while(|c| is_ident_char(*c))
+            .map(char::len_utf8)
+            .sum();
+        self.position += len;
+        &self.source[start..self.position]
+    }
+
+    fn number(&mut self) -> Result<usize, ParseError> {
+        let start = self.position;
+        let len = self.source[start..]
+            .chars()
+            .take_while(char::is_ascii_digit)
+            .count();
+        self.position += len;
+        self.source[start..self.position]
+            .parse()
+            .map_err(|_| ParseError {
+                position: start,
+                message: "Expected a number".into(),
+                source: self.source.into(),
+            })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.position..].chars().next()
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += expected.len_utf8();
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        Err(self.error(format!("Expected '{expected}'")))
+    }
+
+    /// Returns whether any whitespace was skipped.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        let len: usize = self.source[start..]
+            .chars()
+            .take_while(|c| c.is_whitespace())
+            .map(char::len_utf8)
+            .sum();
+        self.position += len;
+        len != 0
+    }
+
+    fn error(&self, message: impl Into<Box<str>>) -> ParseError {
+        ParseError {
+            position: self.position,
+            message: message.into(),
+            source: self.source.into(),
+        }
+    }
+}
+
+/// Quotes text as a string in a selector, so it matches the text exactly as written.
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::fake::{FakeBackend, FakeDesktop, FakeWindow},
+        window::get_windows,
+    };
+
+    /// A dialog with a panel of two buttons and a label, next to a second panel with one button.
+    fn backend() -> (FakeBackend, WindowId) {
+        let mut desktop = FakeDesktop::new();
+        let root = desktop.add_root(FakeWindow::new("Root").text("Worms Armageddon"));
+        let panel = desktop.add_child(root, FakeWindow::new("Panel").text("Menu"));
+        desktop.add_child(
+            panel,
+            FakeWindow::new("Button").text("Play game").control_id(1),
+        );
+        desktop.add_child(panel, FakeWindow::new("Button").text("Exit").control_id(2));
+        desktop.add_child(panel, FakeWindow::new("Static").text("Say \"hi\"\n\\ back"));
+        let other = desktop.add_child(root, FakeWindow::new("#32770"));
+        let inner = desktop.add_child(other, FakeWindow::new("Panel"));
+        desktop.add_child(inner, FakeWindow::new("Button").text("OK").control_id(1));
+        (FakeBackend::new(desktop), root)
+    }
+
+    fn texts(source: &str) -> Vec<String> {
+        let (backend, root) = backend();
+        let found = get_windows(&backend, root);
+        Selector::parse(source)
+            .unwrap()
+            .select_all(&backend, &found)
+            .iter()
+            .map(|found| backend.text(found.value()))
+            .collect()
+    }
+
+    fn error(source: &str) -> (usize, String) {
+        let err = Selector::parse(source).unwrap_err();
+        (err.position, err.message.into())
+    }
+
+    #[test]
+    fn matches_class_id_and_index() {
+        assert_eq!(texts("Button"), ["Play game", "Exit", "OK"]);
+        assert_eq!(texts("button"), ["Play game", "Exit", "OK"]);
+        assert_eq!(texts("Button#1"), ["Play game", "OK"]);
+        assert_eq!(texts(":nth(1)"), ["Exit", ""]);
+        assert_eq!(texts("*").len(), 7);
+    }
+
+    #[test]
+    fn matches_text_operators() {
+        assert_eq!(texts(r#"[text="Exit"]"#), ["Exit"]);
+        assert_eq!(texts(r#"[text="Exi"]"#), Vec::<String>::new());
+        assert_eq!(texts(r#"[text^="Pla"]"#), ["Play game"]);
+        assert_eq!(texts(r#"[text*="ay g"]"#), ["Play game"]);
+        assert_eq!(texts(r#"Button[text~="^(?i)e.i"]"#), ["Exit"]);
+        assert_eq!(texts(r##"[class="#32770"] Button"##), ["OK"]);
+    }
+
+    #[test]
+    fn matches_combinators() {
+        assert_eq!(texts("Panel Button"), ["Play game", "Exit", "OK"]);
+        assert_eq!(texts("> Panel > Button"), ["Play game", "Exit"]);
+        assert_eq!(texts("> Button"), Vec::<String>::new());
+        assert_eq!(
+            texts(r##"[class="#32770"] > Button"##),
+            Vec::<String>::new()
+        );
+        assert_eq!(texts("> :nth(1) Button"), ["OK"]);
+        assert_eq!(texts("> :nth(0)>:nth(1)"), ["Exit"]);
+    }
+
+    #[test]
+    fn selects_one() {
+        let (backend, root) = backend();
+        let found = get_windows(&backend, root);
+        let select = |source: &str| {
+            Selector::parse(source)
+                .unwrap()
+                .select_one(&backend, &found)
+        };
+
+        assert!(select("Static").is_ok());
+        assert_eq!(
+            select("Button").unwrap_err(),
+            SelectError::Ambiguous {
+                selector: "Button".into(),
+                count: 3
+            }
+        );
+        assert_eq!(
+            select("Edit").unwrap_err(),
+            SelectError::NoMatch {
+                selector: "Edit".into()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_quotes_and_escapes() {
+        assert_eq!(texts(r#"[text='Exit']"#), ["Exit"]);
+        assert_eq!(texts(r#"[ text= "Exit" ]"#), ["Exit"]);
+        assert_eq!(texts(r#"[text*='"hi\"']"#), ["Say \"hi\"\n\\ back"]);
+        assert_eq!(texts(r#"[text*="\\ b"]"#), ["Say \"hi\"\n\\ back"]);
+        assert_eq!(texts(r#"[text*="\q"]"#), Vec::<String>::new());
+    }
+
+    #[test]
+    fn quote_round_trips() {
+        let text = "Say \"hi\"\n\\ back";
+        assert_eq!(texts(&format!("[text={}]", quote(text))), [text]);
+        assert_eq!(quote(r#"a"b\c"#), r#""a\"b\\c""#);
+        assert_eq!(quote("\u{e9}\t"), "\"\u{e9}\t\"");
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        assert_eq!(error(""), (0, "Expected a selector".to_owned()));
+        assert_eq!(error("Button >"), (8, "Expected a selector".to_owned()));
+        assert_eq!(error("Button!"), (6, "Unexpected '!'".to_owned()));
+        assert_eq!(error("#x"), (1, "Expected a number".to_owned()));
+        assert_eq!(
+            error(":first"),
+            (6, "Unknown pseudo class ':first'".to_owned())
+        );
+        assert_eq!(error(":nth(1"), (6, "Expected ')'".to_owned()));
+        assert_eq!(
+            error("[name='a']"),
+            (1, "Unknown attribute 'name'".to_owned())
+        );
+        assert_eq!(
+            error("[class^='a']"),
+            (6, "Unsupported operator '^=' for 'class'".to_owned())
+        );
+        assert_eq!(
+            error("[text=a]"),
+            (6, "Expected a quoted string".to_owned())
+        );
+        assert_eq!(error("[text='a"), (8, "Unterminated string".to_owned()));
+        assert_eq!(error("[text='a']x"), (10, "Unexpected 'x'".to_owned()));
+        assert!(error("[text~='(']").1.starts_with("Invalid regex"));
+    }
+}
@@ -0,0 +1,340 @@
+//! Captured window trees that can be saved to disk and loaded back as test fixtures.
+
+use std::{fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{
+    Rect, WindowId,
+    fake::{FakeBackend, FakeDesktop, FakeWindow},
+};
+
+/// The version written to new snapshots.
+/// Increment this whenever the format changes in a way older snapshots can't be read as.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// A captured window tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Seconds since the unix epoch when the snapshot was taken.
+    pub captured_at: u64,
+    pub root: WinData,
+}
+
+/// Contains information about a window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WinData {
+    pub text: Box<str>,
+    /// The class name.
+    pub name: Box<str>,
+    /// The real class name, which is the class a superclassed window is based on.
+    pub w_type: Box<str>,
+    pub control_id: i32,
+    pub info: WinInfo,
+    pub atom: u32,
+
+    pub depth: usize,
+    pub path: Vec<usize>,
+
+    pub children: Vec<WinData>,
+}
+
+/// The parts of a `WINDOWINFO` that describe a window.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WinInfo {
+    pub window: Rect,
+    pub client: Rect,
+    pub style: u32,
+    pub ex_style: u32,
+    pub status: u32,
+    pub border_width: u32,
+    pub border_height: u32,
+    pub atom_type: u16,
+    pub creator_version: u16,
+}
+
+impl WinData {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        text: impl Into<Box<str>>,
+        name: impl Into<Box<str>>,
+        w_type: impl Into<Box<str>>,
+        control_id: i32,
+        info: WinInfo,
+        atom: u32,
+        depth: usize,
+        path: Vec<usize>,
+    ) -> Self {
+        Self {
+            text: text.into(),
+            name: name.into(),
+            w_type: w_type.into(),
+            control_id,
+            info,
+            atom,
+            children: Vec::new(),
+            depth,
+            path,
+        }
+    }
+
+    pub fn add_child(&mut self, child: WinData) {
+        self.children.push(child);
+    }
+
+    pub fn last_child_containing(&self, cursor_pos: (i32, i32)) -> Option<&WinData> {
+        // If window does not contain cursor
+        if !self.info.window.contains(cursor_pos) {
+            return None;
+        }
+
+        for data in self.children.iter() {
+            if let Some(contains) = data.last_child_containing(cursor_pos) {
+                return Some(contains);
+            };
+        }
+
+        Some(self)
+    }
+}
+
+impl Snapshot {
+    pub fn new(root: WinData) -> Self {
+        let captured_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            version: SNAPSHOT_VERSION,
+            captured_at,
+            root,
+        }
+    }
+
+    /// Reads a snapshot from JSON, rejecting snapshots from newer versions.
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        #[derive(Deserialize)]
+        struct Version {
+            version: u32,
+        }
+
+        let Version { version } = serde_json::from_str(json)?;
+        if version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SnapshotError> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Saves the snapshot, creating any missing parent directories.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// Creates a [`FakeBackend`] containing the windows of this snapshot.
+    /// Returns the ID of the root window, for use with [`crate::window::get_windows`].
+    pub fn to_backend(&self) -> (FakeBackend, WindowId) {
+        let mut desktop = FakeDesktop::new();
+        let root = desktop.add_root(fake_window(&self.root));
+        add_fake_children(&mut desktop, root, &self.root);
+        (FakeBackend::new(desktop), root)
+    }
+}
+
+fn fake_window(data: &WinData) -> FakeWindow {
+    FakeWindow::new(&*data.name)
+        .text(&*data.text)
+        .control_id(data.control_id)
+        .rect(data.info.window)
+}
+
+fn add_fake_children(desktop: &mut FakeDesktop, parent: WindowId, data: &WinData) {
+    for child in data.children.iter() {
+        let id = desktop.add_child(parent, fake_window(child));
+        add_fake_children(desktop, id, child);
+    }
+}
+
+/// The reason a snapshot could not be saved or loaded.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    /// The snapshot was written by a newer version of this crate.
+    UnsupportedVersion(u32),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "Unable to access snapshot: {err}"),
+            SnapshotError::Format(err) => write!(f, "Invalid snapshot: {err}"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "Snapshot version {version} is newer than the supported version {SNAPSHOT_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::Backend, selector::Selector, window::get_windows};
+
+    fn info(left: i32, top: i32, right: i32, bottom: i32) -> WinInfo {
+        WinInfo {
+            window: Rect::new(left, top, right, bottom),
+            client: Rect::new(left, top, right, bottom),
+            ..WinInfo::default()
+        }
+    }
+
+    /// A lobby with a list of teams and a play button.
+    fn snapshot() -> Snapshot {
+        let mut root = WinData::new(
+            "Worms Armageddon",
+            "Worms2D",
+            "Worms2D",
+            0,
+            info(0, 0, 640, 480),
+            1,
+            0,
+            Vec::new(),
+        );
+        let mut list = WinData::new(
+            "",
+            "ListBox",
+            "ListBox",
+            1008,
+            info(10, 10, 210, 310),
+            2,
+            1,
+            vec![0],
+        );
+        list.add_child(WinData::new(
+            "Scrollbar",
+            "ScrollBar",
+            "ScrollBar",
+            0,
+            info(190, 10, 210, 310),
+            3,
+            2,
+            vec![0, 0],
+        ));
+        root.add_child(list);
+        root.add_child(WinData::new(
+            "Play",
+            "Button",
+            "Button",
+            1009,
+            info(500, 400, 620, 460),
+            4,
+            1,
+            vec![1],
+        ));
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            captured_at: 1_700_000_000,
+            root,
+        }
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let snapshot = snapshot();
+        let loaded = Snapshot::from_json(&snapshot.to_json().unwrap()).unwrap();
+
+        assert_eq!(loaded.version, SNAPSHOT_VERSION);
+        assert_eq!(loaded.captured_at, snapshot.captured_at);
+        assert_eq!(loaded.root, snapshot.root);
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let dir =
+            std::env::temp_dir().join(format!("worms_ai_loop_snapshot_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("lobby.json");
+        let snapshot = snapshot();
+
+        snapshot.save(&path).unwrap();
+        assert_eq!(Snapshot::load(&path).unwrap().root, snapshot.root);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let snapshot = Snapshot {
+            version: SNAPSHOT_VERSION + 1,
+            ..snapshot()
+        };
+
+        let err = Snapshot::from_json(&snapshot.to_json().unwrap()).unwrap_err();
+        assert!(
+            matches!(err, SnapshotError::UnsupportedVersion(version) if version == SNAPSHOT_VERSION + 1),
+            "{err}"
+        );
+        assert!(matches!(
+            Snapshot::from_json("{\"version\": 1}"),
+            Err(SnapshotError::Format(_))
+        ));
+    }
+
+    #[test]
+    fn answers_selectors_as_a_backend() {
+        let (backend, root) = snapshot().to_backend();
+        let found = get_windows(&backend, root);
+        let select = |source: &str| -> Vec<WindowId> {
+            Selector::parse(source)
+                .unwrap()
+                .select_all(&backend, &found)
+                .iter()
+                .map(|found| found.value())
+                .collect()
+        };
+
+        assert_eq!(backend.text(root), "Worms Armageddon");
+        let [play] = select("> Button#1009")[..] else {
+            panic!("No play button");
+        };
+        assert_eq!(backend.text(play), "Play");
+        assert_eq!(backend.rect(play), Some(Rect::new(500, 400, 620, 460)));
+        let [list] = select("ListBox")[..] else {
+            panic!("No team list");
+        };
+        assert_eq!(backend.control_id(list), 1008);
+        assert_eq!(backend.class_name(list), "ListBox");
+        assert_eq!(select("ListBox > ScrollBar").len(), 1);
+        assert!(select("> ScrollBar").is_empty());
+    }
+}
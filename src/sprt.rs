@@ -0,0 +1,459 @@
+//! Decides whether one AI setup is stronger than another with a sequential probability ratio test,
+//! as chess engines are tested.
+//!
+//! The two teams play each other game after game, swapping which is added first, and so their
+//! colour, every game. Both games of a pair are on the same map. After each result the
+//! log-likelihood ratio of the candidate being `elo1` stronger rather than `elo0` is updated, and
+//! the test stops as soon as it crosses either bound, which are set by the error rates. The
+//! ratio is the generalised SPRT over wins, draws and losses that Fishtest uses, comparing the
+//! most likely outcome distributions with each expected score.
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    results::RoundResult,
+    tournament::{self, MatchOutcome, Pairing},
+};
+
+/// Outcomes that haven't happened yet count as this many games, so the likeliest distributions
+/// can still give them a chance.
+const REGULARISATION: f64 = 1e-3;
+/// Standard deviations either side of the estimate for a 95% confidence interval.
+const Z_95: f64 = 1.959964;
+
+/// The A/B test to play instead of the same lineup every round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SprtConfig {
+    /// The team of setup A, which the candidate is compared with. No test is played when missing.
+    pub baseline: Option<String>,
+    /// The team of setup B.
+    pub candidate: Option<String>,
+    /// The Elo difference the candidate is no better than when the test rejects it.
+    pub elo0: f64,
+    /// The Elo difference the candidate is at least as good as when the test accepts it.
+    pub elo1: f64,
+    /// The chance of accepting a candidate that is only `elo0` better.
+    pub alpha: f64,
+    /// The chance of rejecting a candidate that is `elo1` better.
+    pub beta: f64,
+    /// Stops without a decision after this many games, or plays on until there is one when missing.
+    pub max_games: Option<u32>,
+}
+
+impl Default for SprtConfig {
+    fn default() -> Self {
+        Self {
+            baseline: None,
+            candidate: None,
+            elo0: 0.0,
+            elo1: 50.0,
+            alpha: 0.05,
+            beta: 0.05,
+            max_games: None,
+        }
+    }
+}
+
+impl SprtConfig {
+    /// The test, or `None` if either team is missing.
+    pub fn sprt(&self) -> Option<Sprt> {
+        Some(Sprt::new(
+            self.baseline.as_deref()?.into(),
+            self.candidate.as_deref()?.into(),
+            self,
+        ))
+    }
+}
+
+/// Where the test has got to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SprtDecision {
+    Undecided,
+    /// The candidate is at least `elo1` stronger.
+    Accepted,
+    /// The candidate is no more than `elo0` stronger.
+    Rejected,
+    /// The maximum number of games was played without a decision.
+    Inconclusive,
+}
+
+impl Display for SprtDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SprtDecision::Undecided => write!(f, "undecided"),
+            SprtDecision::Accepted => write!(f, "accepted"),
+            SprtDecision::Rejected => write!(f, "rejected"),
+            SprtDecision::Inconclusive => write!(f, "inconclusive"),
+        }
+    }
+}
+
+/// A sequential probability ratio test of a candidate against a baseline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sprt {
+    pub baseline: Box<str>,
+    pub candidate: Box<str>,
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+    max_games: Option<u32>,
+    /// From the candidate's side.
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Sprt {
+    pub fn new(baseline: Box<str>, candidate: Box<str>, config: &SprtConfig) -> Self {
+        Self {
+            baseline,
+            candidate,
+            elo0: config.elo0,
+            elo1: config.elo1,
+            alpha: config.alpha,
+            beta: config.beta,
+            max_games: config.max_games,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+        }
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// The next game to play, or `None` once the test is over.
+    /// The baseline is added first in the first game of each pair and the candidate in the second.
+    pub fn next(&self) -> Option<Pairing> {
+        if self.decision() != SprtDecision::Undecided {
+            return None;
+        }
+
+        let game = self.games();
+        let (first, second) = match game % 2 {
+            0 => (&self.baseline, &self.candidate),
+            _ => (&self.candidate, &self.baseline),
+        };
+        Some(Pairing {
+            stage: game / 2 + 1,
+            first: first.clone(),
+            second: second.clone(),
+        })
+    }
+
+    /// Records the result of the next game.
+    /// Returns `false` if the round wasn't between the two teams or its winner couldn't be read,
+    /// so the game is played again.
+    pub fn record(&mut self, result: &RoundResult) -> bool {
+        let Some(pairing) = self.next() else {
+            return false;
+        };
+        if !pairing.played_in(result) {
+            log::warn!("Round {} wasn't between {pairing}", result.round);
+            return false;
+        }
+
+        match tournament::decide(&pairing, result) {
+            Some(MatchOutcome::Won(winner)) if winner == self.candidate => self.wins += 1,
+            Some(MatchOutcome::Won(_)) => self.losses += 1,
+            Some(MatchOutcome::Drawn) => self.draws += 1,
+            None => {
+                log::warn!(
+                    "Unable to read who won round {}, playing it again",
+                    result.round
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    /// How often the candidate has scored `1` for a win, `0.5` for a draw and `0` for a loss.
+    fn frequencies(&self) -> [(f64, f64); 3] {
+        let counts = [self.wins, self.draws, self.losses].map(|count| match count {
+            0 => REGULARISATION,
+            count => count as f64,
+        });
+        let games: f64 = counts.iter().sum();
+        [
+            (1.0, counts[0] / games),
+            (0.5, counts[1] / games),
+            (0.0, counts[2] / games),
+        ]
+    }
+
+    /// The candidate's average score per game and its variance.
+    fn score(&self) -> (f64, f64) {
+        let frequencies = self.frequencies();
+        let score: f64 = frequencies
+            .iter()
+            .map(|(points, frequency)| points * frequency)
+            .sum();
+        let variance = frequencies
+            .iter()
+            .map(|(points, frequency)| frequency * (points - score).powi(2))
+            .sum();
+        (score, variance)
+    }
+
+    /// The log-likelihood ratio of the candidate being `elo1` rather than `elo0` stronger.
+    pub fn llr(&self) -> f64 {
+        let frequencies = self.frequencies();
+        let (score0, score1) = (expected_score(self.elo0), expected_score(self.elo1));
+        self.games() as f64
+            * (log_likelihood(&frequencies, score0) - log_likelihood(&frequencies, score1))
+    }
+
+    /// The ratio the candidate is rejected at or below and accepted at or above.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    pub fn decision(&self) -> SprtDecision {
+        let llr = self.llr();
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtDecision::Accepted
+        } else if llr <= lower {
+            SprtDecision::Rejected
+        } else if self.max_games.is_some_and(|max| self.games() >= max) {
+            SprtDecision::Inconclusive
+        } else {
+            SprtDecision::Undecided
+        }
+    }
+
+    /// How much stronger the candidate has been, in Elo, with a 95% confidence interval.
+    pub fn elo(&self) -> (f64, f64, f64) {
+        let (score, variance) = self.score();
+        let margin = Z_95 * (variance / self.games().max(1) as f64).sqrt();
+        (
+            elo_difference(score),
+            elo_difference(score - margin),
+            elo_difference(score + margin),
+        )
+    }
+}
+
+impl Display for Sprt {
+    /// A summary of the test.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (elo, low, high) = self.elo();
+        let (lower, upper) = self.bounds();
+        writeln!(
+            f,
+            "SPRT of {} against {} with Elo bounds [{}, {}]: {}",
+            self.candidate,
+            self.baseline,
+            self.elo0,
+            self.elo1,
+            self.decision()
+        )?;
+        writeln!(
+            f,
+            "{} games: {} won, {} drawn, {} lost",
+            self.games(),
+            self.wins,
+            self.draws,
+            self.losses
+        )?;
+        writeln!(f, "Elo difference {elo:.1} (95% {low:.1} to {high:.1})")?;
+        write!(f, "LLR {:.2} ({lower:.2}, {upper:.2})", self.llr())
+    }
+}
+
+/// How much less likely the outcomes are, per game, under the most likely distribution that
+/// averages `score` than under their frequencies.
+///
+/// That distribution is each frequency divided by `1 + θ(points - score)`, for the `θ` that makes
+/// the frequencies still add up to one, which is found by bisection.
+fn log_likelihood(frequencies: &[(f64, f64)], score: f64) -> f64 {
+    let balance = |theta: f64| -> f64 {
+        frequencies
+            .iter()
+            .map(|(points, frequency)| {
+                frequency * (points - score) / (1.0 + theta * (points - score))
+            })
+            .sum()
+    };
+
+    // Every adjusted frequency has to stay positive
+    let (low, high) = frequencies.iter().map(|(points, _)| points - score).fold(
+        (f64::NEG_INFINITY, f64::INFINITY),
+        |(low, high), offset| match offset.total_cmp(&0.0) {
+            std::cmp::Ordering::Greater => (low.max(-1.0 / offset), high),
+            std::cmp::Ordering::Less => (low, high.min(-1.0 / offset)),
+            std::cmp::Ordering::Equal => (low, high),
+        },
+    );
+    let (mut low, mut high) = (low + 1e-12, high - 1e-12);
+    // The balance only falls as theta rises
+    for _ in 0..200 {
+        let middle = (low + high) / 2.0;
+        match balance(middle) > 0.0 {
+            true => low = middle,
+            false => high = middle,
+        }
+    }
+
+    let theta = (low + high) / 2.0;
+    frequencies
+        .iter()
+        .map(|(points, frequency)| frequency * (1.0 + theta * (points - score)).ln())
+        .sum()
+}
+
+/// The score a team that is `elo` stronger is expected to average.
+fn expected_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// The Elo difference that gives the average score.
+fn elo_difference(score: f64) -> f64 {
+    let score = score.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::{
+        game::{MapChoice, MapRotation},
+        results::RoundOutcome,
+        test_util::{assert_close, result, won},
+    };
+
+    fn sprt(wins: u32, draws: u32, losses: u32) -> Sprt {
+        Sprt {
+            wins,
+            draws,
+            losses,
+            ..Sprt::new(
+                "Baseline".into(),
+                "Candidate".into(),
+                &SprtConfig::default(),
+            )
+        }
+    }
+
+    #[test]
+    fn bounds_follow_the_error_rates() {
+        let (lower, upper) = sprt(0, 0, 0).bounds();
+        assert_close(lower, -2.944439, 1e-6);
+        assert_close(upper, 2.944439, 1e-6);
+
+        let sprt = Sprt::new(
+            "Baseline".into(),
+            "Candidate".into(),
+            &SprtConfig {
+                alpha: 0.05,
+                beta: 0.1,
+                ..SprtConfig::default()
+            },
+        );
+        let (lower, upper) = sprt.bounds();
+        assert_close(lower, -2.251292, 1e-6);
+        assert_close(upper, 2.890372, 1e-6);
+    }
+
+    #[test]
+    fn llr_matches_the_generalised_sprt() {
+        // Worked out separately from the most likely trinomial distributions for each Elo, as
+        // Fishtest does for logistic Elo bounds of [0, 50] and [0, 10]
+        assert_close(sprt(30, 20, 10).llr(), 3.443008, 1e-6);
+        assert_close(sprt(10, 20, 30).llr(), -5.147732, 1e-6);
+        assert_close(sprt(20, 0, 20).llr(), -0.412799, 1e-6);
+        let close = Sprt {
+            elo1: 10.0,
+            ..sprt(60, 30, 40)
+        };
+        assert_close(close.llr(), 0.678901, 1e-6);
+        assert_eq!(sprt(0, 0, 0).llr(), 0.0);
+    }
+
+    #[test]
+    fn decides_once_past_a_bound() {
+        assert_eq!(sprt(30, 20, 10).decision(), SprtDecision::Accepted);
+        assert_eq!(sprt(10, 20, 30).decision(), SprtDecision::Rejected);
+        assert_eq!(sprt(20, 0, 20).decision(), SprtDecision::Undecided);
+        let capped = Sprt {
+            max_games: Some(40),
+            ..sprt(20, 0, 20)
+        };
+        assert_eq!(capped.decision(), SprtDecision::Inconclusive);
+        assert_eq!(capped.next(), None);
+    }
+
+    #[test]
+    fn plays_both_games_of_a_pair_on_the_same_map() {
+        let maps = MapRotation::shuffled(
+            ["Arena", "Cavern", "Forest"]
+                .map(|map| MapChoice::File(map.into()))
+                .to_vec(),
+            42,
+        );
+        let mut sprt = sprt(0, 0, 0);
+        // The map and first team of each game played, by pair
+        let mut pairs: BTreeMap<u32, Vec<(MapChoice, Box<str>)>> = BTreeMap::new();
+        for round in 1..=9 {
+            let pairing = sprt.next().unwrap();
+            // The fourth round couldn't be read, so it is played again on the same map
+            let outcome = match round {
+                4 => RoundOutcome::default(),
+                _ => won(&pairing.first),
+            };
+            let counted = sprt.record(&result(round, &pairing, outcome));
+            assert_eq!(counted, round != 4);
+            if counted {
+                pairs
+                    .entry(pairing.stage)
+                    .or_default()
+                    .push((maps.map(pairing.stage).unwrap(), pairing.first));
+            }
+        }
+
+        assert_eq!(sprt.games(), 8);
+        assert_eq!(pairs.len(), 4);
+        for games in pairs.values() {
+            let [(map, first), (other_map, other_first)] = games.as_slice() else {
+                panic!("{games:?} isn't a pair");
+            };
+            assert_eq!(map, other_map);
+            assert_ne!(first, other_first);
+            assert_eq!(&**first, "Baseline");
+        }
+        // Each pass plays every map once
+        let first_pass: Vec<_> = pairs.values().take(3).map(|games| &games[0].0).collect();
+        assert!(
+            first_pass
+                .iter()
+                .enumerate()
+                .all(|(index, map)| !first_pass[..index].contains(map))
+        );
+    }
+
+    #[test]
+    fn ignores_rounds_of_other_teams() {
+        let mut sprt = sprt(0, 0, 0);
+        let other = Pairing {
+            stage: 1,
+            first: "Baseline".into(),
+            second: "Someone".into(),
+        };
+
+        assert!(!sprt.record(&result(1, &other, won("Baseline"))));
+        assert_eq!(sprt.games(), 0);
+        assert_eq!(sprt.next().unwrap().stage, 1);
+    }
+}
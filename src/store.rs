@@ -0,0 +1,500 @@
+//! Keeps the result of every round in a JSON Lines file that is only ever appended to, so results
+//! survive crashes and build up across sessions.
+//!
+//! Each line is a [`StoredResult`] tagged with the schema version it was written with. Older
+//! records are migrated to the current version when read, and [`ResultStore::load_migrated`]
+//! rewrites the file in the current version when it finds any, as is done at startup.
+
+use std::{
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::Config, experiment::VariantTag, results::RoundResult};
+
+/// The version written to new records.
+/// Increment this whenever the format changes, adding a step to `MIGRATIONS`.
+pub const STORE_VERSION: u32 = 1;
+
+/// What was running when a result was recorded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunInfo {
+    /// The version of this DLL.
+    pub dll_version: Box<str>,
+    /// The version of WA, or `None` if it couldn't be read.
+    pub wa_version: Option<Box<str>>,
+    /// Differs whenever any setting in the config differs.
+    pub config_hash: Box<str>,
+}
+
+impl RunInfo {
+    pub fn new(config: &Config, wa_version: Option<Box<str>>) -> Self {
+        Self {
+            dll_version: env!("CARGO_PKG_VERSION").into(),
+            wa_version,
+            config_hash: config_hash(config).into(),
+        }
+    }
+}
+
+/// The rounds played since the DLL was loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub id: Box<str>,
+    pub run: RunInfo,
+}
+
+impl Session {
+    /// A session with a new ID, made from the time and a random number so it is unique.
+    pub fn start(run: RunInfo) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        Self {
+            id: format!("{started:x}-{:08x}", fastrand::u32(..)).into(),
+            run,
+        }
+    }
+}
+
+/// A line of the store.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredResult {
+    pub version: u32,
+    pub session: Box<str>,
+    pub run: RunInfo,
+    pub result: RoundResult,
+    /// The experiment variant the round was played for, if any.
+    /// Left out when missing, so it needs no new version.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<VariantTag>,
+}
+
+impl StoredResult {
+    pub fn new(session: &Session, result: RoundResult) -> Self {
+        Self {
+            version: STORE_VERSION,
+            session: session.id.clone(),
+            run: session.run.clone(),
+            result,
+            variant: None,
+        }
+    }
+
+    /// Tags the result with the experiment variant it was played for.
+    pub fn with_variant(mut self, variant: VariantTag) -> Self {
+        self.variant = Some(variant);
+        self
+    }
+}
+
+/// A JSON Lines file of [`StoredResult`]s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResultStore {
+    path: PathBuf,
+}
+
+impl ResultStore {
+    /// The store at the path. The file is created on the first append.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Adds a record to the end of the store, syncing it to disk before returning.
+    pub fn append(&self, record: &StoredResult) -> Result<(), StoreError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        // Finish any line left unfinished by a crash, so it can't spoil this record
+        if file.metadata()?.len() > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last != *b"\n" {
+                line.insert(0, '\n');
+            }
+        }
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Reads every record, migrated to the current version. A missing store has no records.
+    ///
+    /// Lines that end part way through a record are skipped, as that is what a crash while
+    /// appending leaves behind. Any other line that can't be read is an error.
+    pub fn load(&self) -> Result<Vec<StoredResult>, StoreError> {
+        self.read(&MIGRATIONS).map(|(records, _)| records)
+    }
+
+    /// Reads every record like [`ResultStore::load`], first rewriting the store in the current
+    /// version when any record was written with an older one.
+    pub fn load_migrated(&self) -> Result<Vec<StoredResult>, StoreError> {
+        self.load_migrated_with(&MIGRATIONS)
+    }
+
+    /// Rewrites the store with every record in the current version.
+    /// The new file replaces the old one in a single step, so a crash leaves one or the other.
+    /// Returns the number of records.
+    pub fn migrate(&self) -> Result<usize, StoreError> {
+        let (records, _) = self.read(&MIGRATIONS)?;
+        self.rewrite(&records)?;
+        Ok(records.len())
+    }
+
+    fn load_migrated_with(&self, migrations: &Migrations) -> Result<Vec<StoredResult>, StoreError> {
+        let (records, outdated) = self.read(migrations)?;
+        if outdated {
+            self.rewrite(&records)?;
+            log::info!(
+                "Migrated {} results in {} to version {STORE_VERSION}",
+                records.len(),
+                self.path.display()
+            );
+        }
+        Ok(records)
+    }
+
+    /// Reads every record, and whether any was written with an older version.
+    fn read(&self, migrations: &Migrations) -> Result<(Vec<StoredResult>, bool), StoreError> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((Vec::new(), false));
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut records = Vec::with_capacity(lines.len());
+        let mut outdated = false;
+        for (index, line) in lines.iter().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(line, migrations) {
+                Ok((record, version)) => {
+                    outdated |= version < migrations.current();
+                    records.push(record);
+                }
+                Err(StoreError::Format(err)) if err.is_eof() => {
+                    log::warn!("Skipping unfinished line {} of the results", index + 1);
+                }
+                Err(err) => return Err(err.at_line(index + 1)),
+            }
+        }
+        Ok((records, outdated))
+    }
+
+    /// Replaces the store with the records, through a temporary file.
+    fn rewrite(&self, records: &[StoredResult]) -> Result<(), StoreError> {
+        let temporary = self.path.with_extension("migrating");
+
+        let mut text = String::new();
+        for record in records.iter() {
+            text.push_str(&serde_json::to_string(record)?);
+            text.push('\n');
+        }
+        let mut file = File::create(&temporary)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Reads a record, returning it along with the version it was written with.
+fn parse_line(line: &str, migrations: &Migrations) -> Result<(StoredResult, u32), StoreError> {
+    let record: Value = serde_json::from_str(line)?;
+    let version = record
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(StoreError::MissingVersion)? as u32;
+    if version < migrations.oldest || version > migrations.current() {
+        return Err(StoreError::UnsupportedVersion(version));
+    }
+
+    let record = serde_json::from_value(migrations.migrate(record, version))?;
+    Ok((record, version))
+}
+
+/// The steps that bring records written with older versions up to [`STORE_VERSION`].
+struct Migrations {
+    /// The oldest version that can be read, which the first step migrates from.
+    oldest: u32,
+    /// Each step changes a record from one version to the next.
+    steps: &'static [fn(Value) -> Value],
+}
+
+/// There are no steps yet, as only version 1 has been written.
+const MIGRATIONS: Migrations = Migrations {
+    oldest: 1,
+    steps: &[],
+};
+
+impl Migrations {
+    /// The version the last step migrates to.
+    fn current(&self) -> u32 {
+        self.oldest + self.steps.len() as u32
+    }
+
+    /// Brings a record written with an older version up to the current one, one version at a
+    /// time.
+    fn migrate(&self, record: Value, version: u32) -> Value {
+        let mut record = self.steps[(version - self.oldest) as usize..]
+            .iter()
+            .fold(record, |record, step| step(record));
+
+        if let Some(object) = record.as_object_mut() {
+            object.insert("version".to_owned(), self.current().into());
+        }
+        record
+    }
+}
+
+/// FNV-1a over the config as TOML, which unlike the std hasher is the same on every build.
+fn config_hash(config: &Config) -> String {
+    // Hashing an empty string would give every config the same hash.
+    let text = toml::to_string(config).unwrap_or_else(|err| {
+        log::warn!("Hashing the config's debug form, as it could not be written as TOML: {err}");
+        format!("{config:?}")
+    });
+    let hash = text.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// The reason the results could not be stored or read.
+#[derive(Debug)]
+pub enum StoreError {
+    Io(std::io::Error),
+    Format(serde_json::Error),
+    /// A record has no schema version.
+    MissingVersion,
+    /// A record was written by a newer version of this crate.
+    UnsupportedVersion(u32),
+    /// A record in the middle of the store couldn't be read.
+    Line {
+        line: usize,
+        err: Box<StoreError>,
+    },
+}
+
+impl StoreError {
+    fn at_line(self, line: usize) -> Self {
+        Self::Line {
+            line,
+            err: Box::new(self),
+        }
+    }
+}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::Io(err) => write!(f, "Unable to access the results: {err}"),
+            StoreError::Format(err) => write!(f, "Invalid result: {err}"),
+            StoreError::MissingVersion => write!(f, "Result has no version"),
+            StoreError::UnsupportedVersion(version) => write!(
+                f,
+                "Result version {version} is not supported, the current version is {STORE_VERSION}"
+            ),
+            StoreError::Line { line, err } => write!(f, "Line {line}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<std::io::Error> for StoreError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Format(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        results::RoundOutcome,
+        test_util::{result, stored},
+        tournament::Pairing,
+    };
+
+    /// An empty store in a directory of its own.
+    fn store(name: &str) -> ResultStore {
+        let dir =
+            std::env::temp_dir().join(format!("worms_ai_loop_store_{}_{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        ResultStore::new(dir.join("results.jsonl"))
+    }
+
+    fn record(round: u32) -> StoredResult {
+        let pairing = Pairing {
+            stage: 1,
+            first: "Alpha".into(),
+            second: "Bravo".into(),
+        };
+        stored(&result(round, &pairing, RoundOutcome::default()))
+    }
+
+    /// A format from before version 1, without the run info.
+    const V0: Migrations = Migrations {
+        oldest: 0,
+        steps: &[|mut record| {
+            record["run"] = json!({
+                "dll_version": "0.0.0",
+                "wa_version": null,
+                "config_hash": "",
+            });
+            record
+        }],
+    };
+
+    fn v0_line(round: u32) -> String {
+        let mut record = serde_json::to_value(record(round)).unwrap();
+        let object = record.as_object_mut().unwrap();
+        object.remove("run");
+        object.insert("version".to_owned(), 0.into());
+        record.to_string()
+    }
+
+    #[test]
+    fn a_missing_store_has_no_records() {
+        assert!(store("missing").load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn loads_what_was_appended() {
+        let store = store("round_trip");
+        let records = [record(1), record(2)];
+        for record in records.iter() {
+            store.append(record).unwrap();
+        }
+
+        assert_eq!(store.load().unwrap(), records);
+    }
+
+    #[test]
+    fn skips_a_torn_last_line() {
+        let store = store("torn");
+        store.append(&record(1)).unwrap();
+        let torn = serde_json::to_string(&record(2)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(store.path()).unwrap();
+        file.write_all(&torn.as_bytes()[..torn.len() / 2]).unwrap();
+
+        assert_eq!(store.load().unwrap(), [record(1)]);
+
+        // The next record starts on a line of its own
+        store.append(&record(3)).unwrap();
+        assert_eq!(store.load().unwrap(), [record(1), record(3)]);
+    }
+
+    #[test]
+    fn fails_on_a_broken_line_before_the_end() {
+        let store = store("broken");
+        store.append(&record(1)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(store.path()).unwrap();
+        file.write_all(b"{\"version\": 1, \"session\": 5}\n")
+            .unwrap();
+        store.append(&record(2)).unwrap();
+
+        assert!(matches!(
+            store.load(),
+            Err(StoreError::Line { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let store = store("newer");
+        let mut record = serde_json::to_value(record(1)).unwrap();
+        record["version"] = (STORE_VERSION + 1).into();
+        std::fs::create_dir_all(store.path().parent().unwrap()).unwrap();
+        std::fs::write(store.path(), format!("{record}\n")).unwrap();
+
+        match store.load() {
+            Err(StoreError::Line { line: 1, err }) => assert!(matches!(
+                *err,
+                StoreError::UnsupportedVersion(version) if version == STORE_VERSION + 1
+            )),
+            other => panic!("Expected an unsupported version, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn migrates_older_records_when_loaded() {
+        let store = store("migrate");
+        std::fs::create_dir_all(store.path().parent().unwrap()).unwrap();
+        std::fs::write(store.path(), format!("{}\n", v0_line(1))).unwrap();
+        store.append(&record(2)).unwrap();
+
+        // Unreadable until migrated
+        assert!(store.load().is_err());
+
+        let records = store.load_migrated_with(&V0).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].version, STORE_VERSION);
+        assert_eq!(&*records[0].run.dll_version, "0.0.0");
+        assert_eq!(records[0].result, record(1).result);
+        assert_eq!(records[1], record(2));
+
+        // Rewritten in the current version
+        assert_eq!(store.load().unwrap(), records);
+        assert!(!store.path().with_extension("migrating").exists());
+    }
+
+    #[test]
+    fn leaves_a_current_store_as_it_is() {
+        let store = store("current");
+        store.append(&record(1)).unwrap();
+        let mut file = OpenOptions::new().append(true).open(store.path()).unwrap();
+        file.write_all(b"{\"version\"").unwrap();
+        let before = std::fs::read_to_string(store.path()).unwrap();
+
+        assert_eq!(store.load_migrated().unwrap(), [record(1)]);
+        assert_eq!(std::fs::read_to_string(store.path()).unwrap(), before);
+    }
+
+    #[test]
+    fn hashes_each_config_differently() {
+        let config = Config::default();
+        let mut other = Config::default();
+        other.automation.num_teams += 1;
+
+        assert_eq!(config_hash(&config), config_hash(&Config::default()));
+        assert_ne!(config_hash(&config), config_hash(&other));
+        assert_eq!(config_hash(&config).len(), 16);
+    }
+}
@@ -0,0 +1,76 @@
+//! Builders and checks shared by the unit tests.
+
+use std::time::Duration;
+
+use crate::{
+    game::GameOptions,
+    lobby::{SelectedTeam, TeamColour},
+    results::{RoundOutcome, RoundResult},
+    store::{RunInfo, Session, StoredResult},
+    tournament::Pairing,
+};
+
+/// A session of the same run each time.
+pub(crate) fn session() -> Session {
+    Session {
+        id: "session".into(),
+        run: RunInfo {
+            dll_version: "0.1.0".into(),
+            wa_version: Some("3.8.1".into()),
+            config_hash: "0123456789abcdef".into(),
+        },
+    }
+}
+
+/// The result as stored during [`session`].
+pub(crate) fn stored(result: &RoundResult) -> StoredResult {
+    StoredResult::new(&session(), result.clone())
+}
+
+/// A round between the pairing, with the first team playing red and the second blue.
+pub(crate) fn result(round: u32, pairing: &Pairing, outcome: RoundOutcome) -> RoundResult {
+    let team = |name: &str, colour| SelectedTeam {
+        name: name.into(),
+        colour: Some(colour),
+        worms: None,
+        handicap: None,
+    };
+    RoundResult {
+        round,
+        finished_at: 1_700_000_000 + round as u64,
+        duration: Duration::from_secs(90),
+        lineup: vec![
+            team(&pairing.first, TeamColour::Red),
+            team(&pairing.second, TeamColour::Blue),
+        ],
+        scheme: None,
+        options: GameOptions::default(),
+        map: None,
+        outcome,
+    }
+}
+
+/// A round won by the team.
+pub(crate) fn won(team: &str) -> RoundOutcome {
+    RoundOutcome {
+        winner: Some(team.into()),
+        draw: false,
+        placements: Vec::new(),
+    }
+}
+
+/// A round that ended in a draw.
+pub(crate) fn drawn() -> RoundOutcome {
+    RoundOutcome {
+        winner: None,
+        draw: true,
+        placements: Vec::new(),
+    }
+}
+
+pub(crate) fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (actual - expected).abs() < tolerance,
+        "Expected {expected}, got {actual}"
+    );
+}
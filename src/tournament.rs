@@ -0,0 +1,910 @@
+//! Runs a tournament between a pool of teams, one head-to-head game at a time.
+//!
+//! A [`Tournament`] gives the pairing to play next and moves on once the result of that game is
+//! recorded, so it can drive [`crate::automation::play_rounds`] through a whole event. Round-robin
+//! rounds follow a fixed schedule, while each Swiss round and knockout stage is paired once the
+//! one before it has been played.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lobby::{Lineup, Teams},
+    rating::Standings,
+    results::RoundResult,
+};
+
+/// How teams are paired and knocked out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TournamentFormat {
+    /// Every team plays every other team once per leg, swapping which goes first each leg.
+    RoundRobin,
+    /// Teams play others with the same score, avoiding rematches where possible.
+    Swiss,
+    /// Teams are out after losing once.
+    SingleElimination,
+    /// Teams are out after losing twice. Teams that lose once carry on in a losers bracket, whose
+    /// winner meets the winner of the winners bracket in the final.
+    DoubleElimination,
+}
+
+impl TournamentFormat {
+    /// How many games a team can lose before it is out, or `None` if it is never out.
+    fn lives(&self) -> Option<u32> {
+        match self {
+            TournamentFormat::RoundRobin | TournamentFormat::Swiss => None,
+            TournamentFormat::SingleElimination => Some(1),
+            TournamentFormat::DoubleElimination => Some(2),
+        }
+    }
+}
+
+impl Display for TournamentFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TournamentFormat::RoundRobin => write!(f, "round-robin"),
+            TournamentFormat::Swiss => write!(f, "Swiss"),
+            TournamentFormat::SingleElimination => write!(f, "single elimination"),
+            TournamentFormat::DoubleElimination => write!(f, "double elimination"),
+        }
+    }
+}
+
+/// The tournament to play instead of the same lineup every round.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TournamentConfig {
+    /// No tournament is played when missing.
+    pub format: Option<TournamentFormat>,
+    /// The pool of teams, best seed first.
+    pub teams: Vec<String>,
+    /// How many times each pair of teams meets in a round-robin.
+    pub legs: u32,
+    /// The number of Swiss rounds, or enough to find a clear winner when missing.
+    pub swiss_rounds: Option<u32>,
+    /// How many times a game is played again when it has no winner in a knockout, or its result
+    /// can't be read. After that a knockout game goes to the higher seed and any other game is
+    /// drawn.
+    pub max_replays: u32,
+    /// The file the progress is saved to after every game, so the tournament carries on from
+    /// there when the DLL is next loaded.
+    pub checkpoint_file: String,
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        Self {
+            format: None,
+            teams: Vec::new(),
+            legs: 2,
+            swiss_rounds: None,
+            max_replays: 3,
+            checkpoint_file: "tournament.json".to_owned(),
+        }
+    }
+}
+
+impl TournamentConfig {
+    /// The tournament, or `None` if no format is set.
+    pub fn tournament(&self) -> Option<Tournament> {
+        Some(Tournament::new(
+            self.format?,
+            self.teams.iter().map(|name| name.as_str().into()).collect(),
+            self.legs,
+            self.swiss_rounds,
+            self.max_replays,
+        ))
+    }
+}
+
+/// Two teams to play a game against each other.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pairing {
+    /// The round-robin round, Swiss round, knockout stage or pair of test games the game is in,
+    /// counting from `1`.
+    pub stage: u32,
+    /// Added to the game first.
+    pub first: Box<str>,
+    pub second: Box<str>,
+}
+
+impl Pairing {
+    /// The two teams, each with its own colour.
+    pub fn lineup(&self) -> Lineup {
+        Lineup::free_for_all(Teams::Named(vec![self.first.clone(), self.second.clone()]))
+    }
+
+    /// Whether both teams were in the round.
+    pub fn played_in(&self, result: &RoundResult) -> bool {
+        [&self.first, &self.second].into_iter().all(|team| {
+            result
+                .lineup
+                .iter()
+                .any(|selected| selected.name.trim().eq_ignore_ascii_case(team.trim()))
+        })
+    }
+
+    fn other(&self, team: &str) -> &str {
+        match *self.first == *team {
+            true => &self.second,
+            false => &self.first,
+        }
+    }
+}
+
+impl Display for Pairing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} vs {}", self.first, self.second)
+    }
+}
+
+/// How a game between a pairing ended.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchOutcome {
+    Won(Box<str>),
+    Drawn,
+}
+
+impl Display for MatchOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchOutcome::Won(team) => write!(f, "won by {team}"),
+            MatchOutcome::Drawn => write!(f, "drawn"),
+        }
+    }
+}
+
+/// A game of the tournament that was decided.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Match {
+    pub pairing: Pairing,
+    pub outcome: MatchOutcome,
+}
+
+/// How a team has done in the tournament.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Record {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Stages the team went through without playing.
+    pub byes: u32,
+    /// Games the team was added to first.
+    pub firsts: u32,
+}
+
+impl Record {
+    /// A point for each win or bye and half a point for each draw.
+    pub fn points(&self) -> f64 {
+        (self.wins + self.byes) as f64 + self.draws as f64 / 2.0
+    }
+}
+
+impl Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} points ({} won, {} drawn, {} lost",
+            self.points(),
+            self.wins,
+            self.draws,
+            self.losses
+        )?;
+        if self.byes > 0 {
+            write!(f, ", {} byes", self.byes)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A place in a stage.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Slot {
+    /// The team goes through the stage without playing.
+    Bye(Box<str>),
+    /// `None` until the game is decided.
+    Game(Pairing, Option<MatchOutcome>),
+}
+
+/// A tournament in progress.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tournament {
+    format: TournamentFormat,
+    /// Best seed first.
+    teams: Vec<Box<str>>,
+    legs: u32,
+    swiss_rounds: u32,
+    max_replays: u32,
+    /// The stage being played, counting from `1`.
+    stage: u32,
+    slots: Vec<Slot>,
+    /// How many times the current game has been played without being decided.
+    replays: u32,
+    matches: Vec<Match>,
+    records: BTreeMap<Box<str>, Record>,
+    /// In a knockout, the teams still in by how many games they have lost, in bracket order.
+    brackets: Vec<Vec<Box<str>>>,
+    /// In a knockout, the teams that are out, first out first.
+    eliminated: Vec<Box<str>>,
+}
+
+impl Tournament {
+    /// A tournament between the teams, which are seeded in order with the best first.
+    /// Teams named more than once only take part once.
+    ///
+    /// Round-robins are played over `legs`, and Swiss tournaments over `swiss_rounds`, or enough
+    /// rounds for a single team to win every game when missing.
+    pub fn new(
+        format: TournamentFormat,
+        teams: Vec<Box<str>>,
+        legs: u32,
+        swiss_rounds: Option<u32>,
+        max_replays: u32,
+    ) -> Self {
+        let mut seeded: Vec<Box<str>> = Vec::with_capacity(teams.len());
+        for team in teams {
+            if !seeded.iter().any(|other| other.eq_ignore_ascii_case(&team)) {
+                seeded.push(team);
+            }
+        }
+
+        let mut tournament = Self {
+            format,
+            records: seeded
+                .iter()
+                .map(|team| (team.clone(), Record::default()))
+                .collect(),
+            swiss_rounds: swiss_rounds
+                .unwrap_or_else(|| seeded.len().next_power_of_two().trailing_zeros().max(1)),
+            teams: seeded,
+            legs,
+            max_replays,
+            stage: 0,
+            slots: Vec::new(),
+            replays: 0,
+            matches: Vec::new(),
+            brackets: Vec::new(),
+            eliminated: Vec::new(),
+        };
+        tournament.advance();
+        tournament
+    }
+
+    pub fn format(&self) -> TournamentFormat {
+        self.format
+    }
+
+    pub fn teams(&self) -> &[Box<str>] {
+        &self.teams
+    }
+
+    /// Every decided game, in the order they were played.
+    pub fn matches(&self) -> &[Match] {
+        &self.matches
+    }
+
+    /// The pairing to play next, or `None` once the tournament is over.
+    pub fn current(&self) -> Option<&Pairing> {
+        self.slots.iter().find_map(|slot| match slot {
+            Slot::Game(pairing, None) => Some(pairing),
+            _ => None,
+        })
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current().is_none()
+    }
+
+    /// The team that won the tournament, once it is over.
+    pub fn winner(&self) -> Option<&str> {
+        match self.is_finished() && !self.matches.is_empty() {
+            true => self.standings().first().map(|(team, _)| *team),
+            false => None,
+        }
+    }
+
+    /// Every team with its record, best first.
+    ///
+    /// Knockout teams that are still in come first, by fewest losses, followed by the teams
+    /// that are out, last out first. Other teams are ordered by points, then wins, then seed.
+    pub fn standings<'a>(&'a self) -> Vec<(&'a str, Record)> {
+        let record = |team: &'a str| (team, self.record_of(team));
+        match self.format.lives() {
+            Some(_) => self
+                .brackets
+                .iter()
+                .flatten()
+                .chain(self.eliminated.iter().rev())
+                .map(|team| record(team))
+                .collect(),
+            None => {
+                let mut standings: Vec<(&str, Record)> =
+                    self.teams.iter().map(|team| record(team)).collect();
+                standings.sort_by(|(_, a), (_, b)| {
+                    b.points().total_cmp(&a.points()).then(b.wins.cmp(&a.wins))
+                });
+                standings
+            }
+        }
+    }
+
+    /// Records the result of a game of the current pairing, moving on to the next pairing once
+    /// the game is decided.
+    ///
+    /// Returns `false` if the round wasn't between the current pairing, so it doesn't count.
+    pub fn record(&mut self, result: &RoundResult) -> bool {
+        let Some(pairing) = self.current().cloned() else {
+            return false;
+        };
+        if !pairing.played_in(result) {
+            log::warn!("Round {} wasn't between {pairing}", result.round);
+            return false;
+        }
+
+        let knockout = self.format.lives().is_some();
+        let outcome = match decide(&pairing, result) {
+            Some(MatchOutcome::Drawn) if knockout => None,
+            outcome => outcome,
+        };
+        let outcome = match outcome {
+            Some(outcome) => outcome,
+            None if self.replays < self.max_replays => {
+                self.replays += 1;
+                log::info!("{pairing} was undecided, playing it again");
+                return true;
+            }
+            // Give up replaying
+            None if knockout => MatchOutcome::Won(self.higher_seed(&pairing).into()),
+            None => MatchOutcome::Drawn,
+        };
+        log::info!("{pairing} {outcome}");
+        self.replays = 0;
+
+        self.records
+            .entry(pairing.first.clone())
+            .or_default()
+            .firsts += 1;
+        match &outcome {
+            MatchOutcome::Won(winner) => {
+                self.records.entry(winner.clone()).or_default().wins += 1;
+                let loser: Box<str> = pairing.other(winner).into();
+                self.records.entry(loser).or_default().losses += 1;
+            }
+            MatchOutcome::Drawn => {
+                for team in [&pairing.first, &pairing.second] {
+                    self.records.entry(team.clone()).or_default().draws += 1;
+                }
+            }
+        }
+
+        if let Some(Slot::Game(_, decided)) = self
+            .slots
+            .iter_mut()
+            .find(|slot| matches!(slot, Slot::Game(_, None)))
+        {
+            *decided = Some(outcome.clone());
+        }
+        self.matches.push(Match { pairing, outcome });
+        self.advance();
+        true
+    }
+
+    fn record_of(&self, team: &str) -> Record {
+        self.records.get(team).copied().unwrap_or_default()
+    }
+
+    fn higher_seed<'a>(&self, pairing: &'a Pairing) -> &'a str {
+        let seed = |team: &str| self.teams.iter().position(|seeded| **seeded == *team);
+        match seed(&pairing.second) < seed(&pairing.first) {
+            true => &pairing.second,
+            false => &pairing.first,
+        }
+    }
+
+    /// Pairs the next stage while every game of this one has been decided.
+    fn advance(&mut self) {
+        while self.current().is_none() {
+            let finished = std::mem::take(&mut self.slots);
+            if self.format.lives().is_some() && self.stage > 0 {
+                self.update_brackets(finished);
+            }
+
+            self.stage += 1;
+            self.slots = match self.teams.len() {
+                0 | 1 => Vec::new(),
+                _ => match self.format {
+                    TournamentFormat::RoundRobin => self.round_robin_stage(),
+                    TournamentFormat::Swiss => self.swiss_stage(),
+                    _ => self.knockout_stage(),
+                },
+            };
+            if self.slots.is_empty() {
+                return;
+            }
+        }
+    }
+
+    /// A round of the circle method, with the first team fixed and the others rotating around it.
+    fn round_robin_stage(&self) -> Vec<Slot> {
+        let mut teams: Vec<Option<&Box<str>>> = self.teams.iter().map(Some).collect();
+        if teams.len() % 2 == 1 {
+            // Whoever meets nobody sits the round out
+            teams.push(None);
+        }
+        let size = teams.len();
+        let index = self.stage as usize - 1;
+        let (leg, round) = (index / (size - 1), index % (size - 1));
+        if leg >= self.legs as usize {
+            return Vec::new();
+        }
+
+        let at = |position: usize| match position {
+            0 => teams[0],
+            _ => teams[1 + (position - 1 + round) % (size - 1)],
+        };
+        (0..size / 2)
+            .filter_map(|position| {
+                let (mut first, mut second) = (at(position)?, at(size - 1 - position)?);
+                // The fixed team would otherwise always go first
+                if position == 0 && round % 2 == 1 {
+                    std::mem::swap(&mut first, &mut second);
+                }
+                if leg % 2 == 1 {
+                    std::mem::swap(&mut first, &mut second);
+                }
+                Some(Slot::Game(
+                    Pairing {
+                        stage: self.stage,
+                        first: first.clone(),
+                        second: second.clone(),
+                    },
+                    None,
+                ))
+            })
+            .collect()
+    }
+
+    /// Pairs teams from the top of the standings down, giving any bye to the lowest team that
+    /// hasn't had one.
+    fn swiss_stage(&mut self) -> Vec<Slot> {
+        if self.stage > self.swiss_rounds {
+            return Vec::new();
+        }
+
+        let mut ranked: Vec<Box<str>> = self
+            .standings()
+            .into_iter()
+            .map(|(team, _)| team.into())
+            .collect();
+        let mut slots = Vec::new();
+        if ranked.len() % 2 == 1 {
+            let bye = ranked
+                .iter()
+                .rposition(|team| self.record_of(team).byes == 0)
+                .unwrap_or(ranked.len() - 1);
+            let team = ranked.remove(bye);
+            self.records.entry(team.clone()).or_default().byes += 1;
+            slots.push(Slot::Bye(team));
+        }
+
+        let played: BTreeSet<(&str, &str)> = self
+            .matches
+            .iter()
+            .flat_map(|game| {
+                let (first, second) = (&*game.pairing.first, &*game.pairing.second);
+                [(first, second), (second, first)]
+            })
+            .collect();
+        let pairs = pair_without_rematches(&ranked, &|a, b| played.contains(&(a, b)))
+            .unwrap_or_else(|| {
+                ranked
+                    .chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect()
+            });
+
+        let games: Vec<Slot> = pairs.into_iter().map(|(a, b)| self.game(a, b)).collect();
+        games.into_iter().chain(slots).collect()
+    }
+
+    /// Seeds the first stage so the best teams meet as late as possible and any byes go to
+    /// them, then pairs neighbours in each bracket.
+    fn knockout_stage(&self) -> Vec<Slot> {
+        if self.stage == 1 {
+            let size = self.teams.len().next_power_of_two();
+            return bracket_order(size)
+                .chunks(2)
+                .filter_map(
+                    |pair| match (self.teams.get(pair[0]), self.teams.get(pair[1])) {
+                        (Some(a), Some(b)) => Some(self.game(a.clone(), b.clone())),
+                        (Some(team), None) | (None, Some(team)) => Some(Slot::Bye(team.clone())),
+                        (None, None) => None,
+                    },
+                )
+                .collect();
+        }
+
+        let active: Vec<&Box<str>> = self.brackets.iter().flatten().collect();
+        if active.len() < 2 {
+            return Vec::new();
+        }
+        // The last team of each bracket meets the other in the final
+        if self.brackets.iter().all(|bracket| bracket.len() <= 1) {
+            return vec![self.game(active[0].clone(), active[1].clone())];
+        }
+
+        self.brackets
+            .iter()
+            .flat_map(|bracket| bracket.chunks(2))
+            .map(|pair| match pair {
+                [a, b] => self.game(a.clone(), b.clone()),
+                _ => Slot::Bye(pair[0].clone()),
+            })
+            .collect()
+    }
+
+    /// A game between the teams, with the one that has been added first less often going first.
+    fn game(&self, a: Box<str>, b: Box<str>) -> Slot {
+        let (first, second) = match self.record_of(&b).firsts < self.record_of(&a).firsts {
+            true => (b, a),
+            false => (a, b),
+        };
+        Slot::Game(
+            Pairing {
+                stage: self.stage,
+                first,
+                second,
+            },
+            None,
+        )
+    }
+
+    /// Moves the winners of a knockout stage on in their bracket and the losers down to the next
+    /// bracket, or out. Teams that drop down are spread among the teams already there, so they
+    /// don't play each other straight away.
+    fn update_brackets(&mut self, slots: Vec<Slot>) {
+        let lives = self.format.lives().unwrap_or(1) as usize;
+        let mut staying = vec![Vec::new(); lives];
+        let mut dropping = vec![Vec::new(); lives];
+        for slot in slots {
+            match slot {
+                Slot::Bye(team) => {
+                    let losses = self.record_of(&team).losses as usize;
+                    staying[losses.min(lives - 1)].push(team);
+                }
+                Slot::Game(pairing, Some(MatchOutcome::Won(winner))) => {
+                    let loser: Box<str> = pairing.other(&winner).into();
+                    let losses = self.record_of(&winner).losses as usize;
+                    staying[losses.min(lives - 1)].push(winner);
+                    match self.record_of(&loser).losses as usize {
+                        losses if losses < lives => dropping[losses].push(loser),
+                        _ => self.eliminated.push(loser),
+                    }
+                }
+                // Knockout games are replayed until they have a winner
+                Slot::Game(..) => {}
+            }
+        }
+
+        self.brackets = staying
+            .into_iter()
+            .zip(dropping)
+            .map(|(staying, dropping)| {
+                let mut bracket = Vec::with_capacity(staying.len() + dropping.len());
+                let mut staying = staying.into_iter();
+                let mut dropping = dropping.into_iter().rev();
+                loop {
+                    match (staying.next(), dropping.next()) {
+                        (None, None) => break,
+                        (a, b) => bracket.extend(a.into_iter().chain(b)),
+                    }
+                }
+                bracket
+            })
+            .collect();
+    }
+}
+
+impl Display for Tournament {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.is_finished() {
+            true => writeln!(f, "{} tournament finished:", self.format)?,
+            false => writeln!(f, "{} tournament, stage {}:", self.format, self.stage)?,
+        }
+        for (place, (team, record)) in self.standings().iter().enumerate() {
+            writeln!(f, "{}. {team}: {record}", place + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Who won a game between the pairing, or `None` if the result couldn't be read.
+pub(crate) fn decide(pairing: &Pairing, result: &RoundResult) -> Option<MatchOutcome> {
+    let standings = Standings::from_result(result)?;
+    let rank = |team: &str| {
+        standings.ranks.iter().position(|group| {
+            group
+                .iter()
+                .any(|name| name.trim().eq_ignore_ascii_case(team.trim()))
+        })
+    };
+    let (first, second) = (rank(&pairing.first)?, rank(&pairing.second)?);
+    Some(match first.cmp(&second) {
+        std::cmp::Ordering::Less => MatchOutcome::Won(pairing.first.clone()),
+        std::cmp::Ordering::Greater => MatchOutcome::Won(pairing.second.clone()),
+        std::cmp::Ordering::Equal => MatchOutcome::Drawn,
+    })
+}
+
+/// Pairs each team with the highest team below it that it hasn't played, backtracking when the
+/// rest can't be paired. Returns `None` if there is no way without a rematch.
+fn pair_without_rematches(
+    teams: &[Box<str>],
+    played: &dyn Fn(&str, &str) -> bool,
+) -> Option<Vec<(Box<str>, Box<str>)>> {
+    let Some((team, rest)) = teams.split_first() else {
+        return Some(Vec::new());
+    };
+    rest.iter().enumerate().find_map(|(index, opponent)| {
+        if played(team, opponent) {
+            return None;
+        }
+        let mut others = rest.to_vec();
+        others.remove(index);
+        let mut pairs = pair_without_rematches(&others, played)?;
+        pairs.insert(0, (team.clone(), opponent.clone()));
+        Some(pairs)
+    })
+}
+
+/// The seeds of a knockout bracket of `size` places from top to bottom, so that seed `0` and
+/// seed `1` can only meet in the final.
+fn bracket_order(size: usize) -> Vec<usize> {
+    let mut order = vec![0];
+    while order.len() < size {
+        let places = order.len() * 2;
+        order = order
+            .iter()
+            .flat_map(|&seed| [seed, places - 1 - seed])
+            .collect();
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{drawn, result, won};
+
+    const TEAMS: [&str; 5] = ["A", "B", "C", "D", "E"];
+
+    fn tournament(format: TournamentFormat, teams: usize) -> Tournament {
+        let teams = TEAMS[..teams].iter().map(|team| (*team).into()).collect();
+        Tournament::new(format, teams, 2, None, 1)
+    }
+
+    /// Plays the tournament out, with `winner` deciding each game. Returns the pairing of every
+    /// stage.
+    fn play(
+        tournament: &mut Tournament,
+        mut winner: impl FnMut(&Tournament, &Pairing) -> Option<Box<str>>,
+    ) -> Vec<Pairing> {
+        let mut pairings = Vec::new();
+        while let Some(pairing) = tournament.current().cloned() {
+            assert!(pairings.len() < 100, "The tournament never ends");
+            let winner = winner(tournament, &pairing);
+            let outcome = winner.as_deref().map_or_else(drawn, won);
+            assert!(tournament.record(&result(pairings.len() as u32 + 1, &pairing, outcome)));
+            pairings.push(pairing);
+        }
+        pairings
+    }
+
+    /// The better seed of the pairing always wins.
+    fn seeds_win(tournament: &Tournament, pairing: &Pairing) -> Option<Box<str>> {
+        Some(tournament.higher_seed(pairing).into())
+    }
+
+    /// The teams of the pairing in alphabetical order.
+    fn sides(pairing: &Pairing) -> (&str, &str) {
+        let (a, b) = (&*pairing.first, &*pairing.second);
+        (a.min(b), a.max(b))
+    }
+
+    /// Each pair of teams that played, and how often.
+    fn meetings(pairings: &[Pairing]) -> BTreeMap<(&str, &str), usize> {
+        let mut meetings = BTreeMap::new();
+        for pairing in pairings {
+            *meetings.entry(sides(pairing)).or_default() += 1;
+        }
+        meetings
+    }
+
+    /// No team plays twice in a stage.
+    fn assert_one_game_per_stage(pairings: &[Pairing]) {
+        for stage in pairings.iter().map(|pairing| pairing.stage) {
+            let teams: Vec<&str> = pairings
+                .iter()
+                .filter(|pairing| pairing.stage == stage)
+                .flat_map(|pairing| [&*pairing.first, &*pairing.second])
+                .collect();
+            let unique: BTreeSet<&str> = teams.iter().copied().collect();
+            assert_eq!(teams.len(), unique.len(), "Stage {stage}: {teams:?}");
+        }
+    }
+
+    #[test]
+    fn round_robin_pairs_everyone_once_each_way() {
+        for teams in 3..=5 {
+            let mut tournament = tournament(TournamentFormat::RoundRobin, teams);
+            let pairings = play(&mut tournament, seeds_win);
+
+            assert_eq!(pairings.len(), teams * (teams - 1), "{teams} teams");
+            let meetings = meetings(&pairings);
+            assert_eq!(meetings.len(), teams * (teams - 1) / 2);
+            assert!(meetings.values().all(|count| *count == 2));
+            // Once each way round
+            let ordered: BTreeSet<(&str, &str)> = pairings
+                .iter()
+                .map(|pairing| (&*pairing.first, &*pairing.second))
+                .collect();
+            assert_eq!(ordered.len(), pairings.len());
+            assert_one_game_per_stage(&pairings);
+
+            // An odd team out sits out one round per leg
+            let stages = pairings.last().unwrap().stage as usize;
+            assert_eq!(stages, 2 * (teams + teams % 2 - 1));
+            assert_eq!(tournament.winner(), Some("A"));
+        }
+    }
+
+    #[test]
+    fn swiss_gives_byes_to_different_teams() {
+        let mut tournament = tournament(TournamentFormat::Swiss, 5);
+        let pairings = play(&mut tournament, seeds_win);
+
+        // Three rounds of two games each, with one team out each round
+        assert_eq!(pairings.len(), 6);
+        assert_one_game_per_stage(&pairings);
+        let standings = tournament.standings();
+        let byes: u32 = standings.iter().map(|(_, record)| record.byes).sum();
+        assert_eq!(byes, 3);
+        assert!(standings.iter().all(|(_, record)| record.byes <= 1));
+        assert!(
+            standings
+                .iter()
+                .all(|(_, record)| record.wins + record.losses + record.byes == 3)
+        );
+    }
+
+    #[test]
+    fn swiss_avoids_rematches() {
+        for teams in 4..=5 {
+            let mut tournament = Tournament::new(
+                TournamentFormat::Swiss,
+                TEAMS[..teams].iter().map(|team| (*team).into()).collect(),
+                1,
+                Some(3),
+                1,
+            );
+            let pairings = play(&mut tournament, seeds_win);
+
+            let meetings = meetings(&pairings);
+            assert!(meetings.values().all(|count| *count == 1), "{meetings:?}");
+            assert_eq!(tournament.winner(), Some("A"));
+        }
+    }
+
+    #[test]
+    fn single_elimination_gives_byes_to_the_best_seeds() {
+        for teams in 3..=5 {
+            let mut tournament = tournament(TournamentFormat::SingleElimination, teams);
+            let mut byes: Vec<&str> = tournament
+                .slots
+                .iter()
+                .filter_map(|slot| match slot {
+                    Slot::Bye(team) => Some(&**team),
+                    Slot::Game(..) => None,
+                })
+                .collect();
+            byes.sort();
+            assert_eq!(byes, TEAMS[..teams.next_power_of_two() - teams]);
+
+            let pairings = play(&mut tournament, seeds_win);
+            assert_eq!(pairings.len(), teams - 1, "{teams} teams");
+            assert_one_game_per_stage(&pairings);
+            assert_eq!(tournament.winner(), Some("A"));
+            // The top two seeds only meet in the final
+            let last = pairings.last().unwrap();
+            assert_eq!(sides(last), ("A", "B"));
+        }
+    }
+
+    #[test]
+    fn double_elimination_without_a_reset() {
+        for teams in 3..=5 {
+            let mut tournament = tournament(TournamentFormat::DoubleElimination, teams);
+            let pairings = play(&mut tournament, seeds_win);
+
+            // Every team but the winner loses twice, and the winner never does
+            assert_eq!(pairings.len(), 2 * teams - 2, "{teams} teams");
+            assert_one_game_per_stage(&pairings);
+            assert_eq!(tournament.winner(), Some("A"));
+            let standings = tournament.standings();
+            assert_eq!(standings[0].1.losses, 0);
+            assert!(standings[1..].iter().all(|(_, record)| record.losses == 2));
+        }
+    }
+
+    #[test]
+    fn double_elimination_resets_the_grand_final() {
+        for teams in 3..=5 {
+            let mut tournament = tournament(TournamentFormat::DoubleElimination, teams);
+            let mut grand_final = None;
+            let pairings = play(&mut tournament, |tournament, pairing| {
+                let losses = |team: &str| tournament.record_of(team).losses;
+                // One team left in each bracket
+                let is_final = tournament.brackets.len() == 2
+                    && tournament.brackets.iter().all(|bracket| bracket.len() == 1);
+                // The team from the losers bracket wins the grand final, forcing a reset
+                if is_final && grand_final.is_none() {
+                    grand_final = Some(pairing.clone());
+                    let (first, second) = (&pairing.first, &pairing.second);
+                    return Some(match losses(first) > losses(second) {
+                        true => first.clone(),
+                        false => second.clone(),
+                    });
+                }
+                seeds_win(tournament, pairing)
+            });
+
+            assert_eq!(pairings.len(), 2 * teams - 1, "{teams} teams");
+            let grand_final = grand_final.unwrap();
+            let reset = pairings.last().unwrap();
+            assert_eq!(
+                sides(&grand_final),
+                sides(reset),
+                "The reset is a rematch of the grand final"
+            );
+            assert_eq!(sides(reset), ("A", "B"));
+            assert_eq!(tournament.winner(), Some("A"));
+            let standings = tournament.standings();
+            assert_eq!(standings[0].1.losses, 1);
+            assert!(standings[1..].iter().all(|(_, record)| record.losses == 2));
+        }
+    }
+
+    #[test]
+    fn replays_undecided_knockout_games() {
+        let mut tournament = tournament(TournamentFormat::SingleElimination, 2);
+        let pairing = tournament.current().unwrap().clone();
+
+        // A draw is played again, then goes to the higher seed once out of replays
+        assert!(tournament.record(&result(1, &pairing, drawn())));
+        assert_eq!(tournament.current(), Some(&pairing));
+        assert!(tournament.record(&result(2, &pairing, drawn())));
+        assert!(tournament.is_finished());
+        assert_eq!(tournament.winner(), Some("A"));
+    }
+
+    #[test]
+    fn ignores_rounds_of_other_teams() {
+        let mut tournament = tournament(TournamentFormat::RoundRobin, 4);
+        let pairing = tournament.current().unwrap().clone();
+        let other = Pairing {
+            stage: 1,
+            first: "A".into(),
+            second: "Z".into(),
+        };
+
+        assert!(!tournament.record(&result(1, &other, won("A"))));
+        assert_eq!(tournament.current(), Some(&pairing));
+        assert!(tournament.matches().is_empty());
+    }
+}
@@ -0,0 +1,166 @@
+//! Deadlines, cancellation and the errors for waiting on windows.
+
+use std::{
+    fmt::Display,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{backend::WindowId, selector::SelectError};
+
+/// Allows a running automation to be stopped from another thread.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops every wait using this token, or a clone of it.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns [`WaitError::Cancelled`] if this token has been cancelled.
+    pub fn check(&self) -> Result<(), WaitError> {
+        match self.is_cancelled() {
+            true => Err(WaitError::Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The point in time a wait gives up at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    started: Instant,
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    /// A deadline the given duration from now.
+    pub fn after(timeout: Duration) -> Self {
+        let started = Instant::now();
+        Self {
+            started,
+            at: started.checked_add(timeout),
+        }
+    }
+
+    /// A deadline that never passes.
+    pub fn never() -> Self {
+        Self {
+            started: Instant::now(),
+            at: None,
+        }
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at)
+    }
+
+    /// The time left until the deadline, or `None` if it never passes.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.at
+            .map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Returns [`WaitError::TimedOut`] if the deadline has passed.
+    pub fn check(&self, waiting_for: impl Display) -> Result<(), WaitError> {
+        if self.has_passed() {
+            return Err(WaitError::TimedOut {
+                waiting_for: waiting_for.to_string().into(),
+                after: self.started.elapsed(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The reason waiting for a window failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitError {
+    /// A window that should already exist could not be found.
+    NotFound(SelectError),
+    TimedOut {
+        waiting_for: Box<str>,
+        after: Duration,
+    },
+    Cancelled,
+    /// The window being waited on was closed.
+    WindowDestroyed(WindowId),
+}
+
+impl Display for WaitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaitError::NotFound(err) => write!(f, "{err}"),
+            WaitError::TimedOut { waiting_for, after } => {
+                write!(f, "Timed out after {after:.1?} waiting for {waiting_for}")
+            }
+            WaitError::Cancelled => write!(f, "Cancelled"),
+            WaitError::WindowDestroyed(id) => write!(f, "Window {id:?} was destroyed"),
+        }
+    }
+}
+
+impl std::error::Error for WaitError {}
+
+impl From<SelectError> for WaitError {
+    fn from(value: SelectError) -> Self {
+        Self::NotFound(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_pass_after_their_timeout() {
+        let deadline = Deadline::after(Duration::from_millis(20));
+        assert!(!deadline.has_passed());
+        assert!(deadline.remaining().unwrap() <= Duration::from_millis(20));
+        assert_eq!(deadline.check("the lobby"), Ok(()));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(deadline.has_passed());
+        assert_eq!(deadline.remaining(), Some(Duration::ZERO));
+        let Err(WaitError::TimedOut { waiting_for, after }) = deadline.check("the lobby") else {
+            panic!("The deadline hasn't passed");
+        };
+        assert_eq!(&*waiting_for, "the lobby");
+        assert!(after >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn some_deadlines_never_pass() {
+        for deadline in [Deadline::never(), Deadline::after(Duration::MAX)] {
+            assert!(!deadline.has_passed());
+            assert_eq!(deadline.remaining(), None);
+            assert_eq!(deadline.check("the lobby"), Ok(()));
+        }
+        assert!(Deadline::after(Duration::ZERO).has_passed());
+    }
+
+    #[test]
+    fn cancelling_stops_every_clone() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(!clone.is_cancelled());
+        assert_eq!(clone.check(), Ok(()));
+
+        std::thread::spawn(move || token.cancel()).join().unwrap();
+        assert!(clone.is_cancelled());
+        assert_eq!(clone.check(), Err(WaitError::Cancelled));
+        assert!(!CancelToken::new().is_cancelled());
+    }
+}
@@ -1,68 +1,111 @@
-use std::time::Duration;
+use std::fmt::Display;
 
-use windows::Win32::{
-    Foundation::{HWND, LPARAM, WPARAM},
-    UI::WindowsAndMessaging::{FindWindowExA, SendMessageA, WM_LBUTTONDOWN, WM_LBUTTONUP},
+use crate::{
+    backend::{Backend, WindowId},
+    events::ChangeWaiter,
+    selector::{SelectError, Selector},
+    wait::{CancelToken, Deadline, WaitError},
 };
 
 /// Gets all child windows from a parent window.
-pub fn get_windows(parent: HWND) -> Found {
+pub fn get_windows(backend: &dyn Backend, parent: WindowId) -> Found {
     let mut found = Found::new(parent);
+    fill_windows(backend, parent, &mut found);
+    found
+}
 
-    let mut child_id = None;
-
-    loop {
-        child_id = unsafe { FindWindowExA(Some(parent), child_id, None, None) }.ok();
-
-        let Some(id) = child_id else {
-            break;
-        };
+/// Gets the other top level windows of the window's process, such as its dialogs, with all of their
+/// children. They are gathered under the window, which selectors never match, so a selector for a
+/// dialog starts from the dialog itself.
+pub fn get_process_windows(backend: &dyn Backend, window: WindowId) -> Found {
+    let mut found = Found::new(window);
+    for id in backend.process_windows(window) {
+        found.add_child(get_windows(backend, id));
+    }
+    found
+}
 
+fn fill_windows(backend: &dyn Backend, parent: WindowId, found: &mut Found) {
+    for id in backend.children(parent) {
         let mut sub_found = Found::new(id);
-        fill_windows(id, &mut sub_found);
+        fill_windows(backend, id, &mut sub_found);
         found.add_child(sub_found);
     }
-
-    found
 }
 
-fn fill_windows(parent: HWND, found: &mut Found) {
-    let mut child_id = None;
-
+/// Waits until the callback returns `Some` for the windows under parent.
+/// The windows are only checked again once the backend reports a change, or polled if it can't.
+///
+/// `waiting_for` describes what is being waited for, for the error if the deadline passes.
+pub fn wait_for_window<T>(
+    backend: &dyn Backend,
+    parent: WindowId,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+    waiting_for: impl Display,
+    callback: impl Fn(&Found) -> Option<T>,
+) -> Result<T, WaitError> {
+    let mut waiter = ChangeWaiter::new(backend);
     loop {
-        child_id = unsafe { FindWindowExA(Some(parent), child_id, None, None) }.ok();
+        cancel.check()?;
+        if !backend.exists(parent) {
+            return Err(WaitError::WindowDestroyed(parent));
+        }
 
-        let Some(id) = child_id else {
-            break;
+        if let Some(value) = callback(&get_windows(backend, parent)) {
+            return Ok(value);
         };
 
-        let mut sub_found = Found::new(id);
-        fill_windows(id, &mut sub_found);
-        found.add_child(sub_found);
+        deadline.check(&waiting_for)?;
+        waiter.wait(deadline, cancel);
     }
 }
 
-/// The first value of the callback should return `Some(HWND)` for the desired window.
-pub fn wait_for_window<ItrReturned: Iterator<Item = HWND>>(
-    parent: HWND,
-    callback: impl Fn(std::vec::IntoIter<HWND>) -> ItrReturned,
-) -> HWND {
-    loop {
-        if let Some(id) = callback(get_windows(parent).into_iter()).next() {
-            return id;
-        };
-        std::thread::sleep(Duration::from_millis(100));
-    }
+/// Gets the single window under parent matching the selector.
+pub fn select(
+    backend: &dyn Backend,
+    parent: WindowId,
+    selector: &Selector,
+) -> Result<WindowId, SelectError> {
+    selector
+        .select_one(backend, &get_windows(backend, parent))
+        .map(|found| found.value())
+}
+
+/// Waits until a single window under parent matches the selector.
+pub fn wait_for_selector(
+    backend: &dyn Backend,
+    parent: WindowId,
+    selector: &Selector,
+    deadline: &Deadline,
+    cancel: &CancelToken,
+) -> Result<WindowId, WaitError> {
+    wait_for_window(
+        backend,
+        parent,
+        deadline,
+        cancel,
+        format_args!("'{selector}'"),
+        |found| match selector.select_one(backend, found) {
+            Ok(found) => Some(found.value()),
+            Err(err @ SelectError::Ambiguous { .. }) => {
+                log::warn!("{err}");
+                None
+            }
+            Err(SelectError::NoMatch { .. }) => None,
+        },
+    )
 }
 
 /// Recursively contains windows that are contained by a parent window.
+#[derive(Debug, Clone)]
 pub struct Found {
     children: Vec<Found>,
-    value: HWND,
+    value: WindowId,
 }
 
 impl Found {
-    pub fn new(value: HWND) -> Self {
+    pub fn new(value: WindowId) -> Self {
         Self {
             children: Default::default(),
             value,
@@ -73,7 +116,7 @@ impl Found {
         self.children.push(found);
     }
 
-    pub fn value(&self) -> HWND {
+    pub fn value(&self) -> WindowId {
         self.value
     }
 
@@ -86,9 +129,9 @@ impl Found {
     }
 }
 
-impl Into<Vec<HWND>> for Found {
-    fn into(self) -> Vec<HWND> {
-        let Self { children, value } = self;
+impl From<Found> for Vec<WindowId> {
+    fn from(found: Found) -> Self {
+        let Found { children, value } = found;
 
         let mut values = Vec::new();
         for child in children {
@@ -100,73 +143,10 @@ impl Into<Vec<HWND>> for Found {
 }
 
 impl IntoIterator for Found {
-    type Item = HWND;
+    type Item = WindowId;
     type IntoIter = std::vec::IntoIter<Self::Item>;
 
     fn into_iter(self) -> Self::IntoIter {
-        Into::<Vec<HWND>>::into(self).into_iter()
-    }
-}
-
-pub trait GetFound {
-    fn get(&self, index: usize) -> Option<&Found>;
-
-    fn value(&self) -> Option<HWND>;
-}
-
-impl GetFound for Option<&Found> {
-    fn get(&self, index: usize) -> Option<&Found> {
-        self.and_then(|found| found.get(index))
-    }
-
-    fn value(&self) -> Option<HWND> {
-        self.map(|found| found.value())
-    }
-}
-
-impl GetFound for Option<Found> {
-    fn get(&self, index: usize) -> Option<&Found> {
-        self.as_ref().and_then(|found| found.get(index))
-    }
-
-    fn value(&self) -> Option<HWND> {
-        self.as_ref().map(|found| found.value())
-    }
-}
-
-/// Provides a helper method to left click on a window.
-pub trait Click {
-    /// Performs a left click on this window.
-    fn click(self);
-}
-
-impl Click for HWND {
-    fn click(self) {
-        // SAFETY, I'm passing the expected arguments to the function. The windows API docs did not mention any edge cases that i have to handle
-        unsafe { SendMessageA(self, WM_LBUTTONDOWN, WPARAM(0 as usize), LPARAM(0isize)) };
-        unsafe { SendMessageA(self, WM_LBUTTONUP, WPARAM(0 as usize), LPARAM(0isize)) };
-    }
-}
-
-/// Provides a helper method to get the text from a window.
-pub trait Text {
-    /// Gets the text attribute for this window.
-    /// This will return an empty string if the windows is not part of the current process.
-    fn text(self) -> String;
-}
-
-impl Text for HWND {
-    fn text(self) -> String {
-        use windows::Win32::UI::WindowsAndMessaging::{GetWindowTextA, GetWindowTextLengthA};
-
-        let min_buff_len = unsafe { GetWindowTextLengthA(self) }.abs() /* It doesn't make sense for the text buffer to have a negative length */ as usize
-            + 1; // The given length is 1 indexed
-
-        let mut window_text = vec![0u8; min_buff_len];
-        unsafe { GetWindowTextA(self, window_text.as_mut_slice()) };
-        String::from_utf8(window_text.to_vec())
-            .expect("Unable to get window text")
-            .trim()
-            .to_owned()
+        Vec::<WindowId>::from(self).into_iter()
     }
 }
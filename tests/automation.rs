@@ -0,0 +1,338 @@
+//! Plays rounds from end to end against the scripted fake frontend.
+
+mod common;
+
+use common::{FakeWa, RoundEnd, init, timeouts};
+use worms_ai_loop::{
+    automation::{play_round, play_rounds},
+    backend::fake::FakeAction,
+    config::Config,
+    game::{GameOptions, GameSetup, MapChoice},
+    lobby::{Handicap, Lineup, TeamColour, Teams},
+    results::RoundResult,
+    wait::CancelToken,
+};
+
+const TEAMS: [&str; 4] = ["Alpha", "Bravo", "Team 17", "Cats"];
+
+fn wa() -> FakeWa {
+    init();
+    FakeWa::new(&TEAMS, &["Beginner", "Intermediate"], &["Arena", "Cavern"])
+}
+
+fn setup(teams: &[&str], map: MapChoice) -> GameSetup {
+    GameSetup {
+        lineup: Lineup::free_for_all(Teams::Named(
+            teams.iter().map(|team| (*team).into()).collect(),
+        ))
+        .with_worms(3)
+        .with_handicap(Handicap::None),
+        scheme: Some("Intermediate".into()),
+        options: GameOptions {
+            turn_time_secs: Some(30),
+            round_time_mins: None,
+            worm_energy: Some(150),
+        },
+        map: Some(map),
+    }
+}
+
+fn names(result: &RoundResult) -> Vec<&str> {
+    result.lineup.iter().map(|team| &*team.name).collect()
+}
+
+#[test]
+fn plays_a_round_from_the_main_menu() {
+    let wa = wa();
+    let setup = setup(&["Alpha", "Bravo"], MapChoice::Seeded(1234));
+
+    let result = wa
+        .run(
+            vec![RoundEnd::Won(vec!["Bravo", "Alpha"])],
+            |backend, root| play_round(backend, root, &setup, &timeouts(), &CancelToken::new()),
+        )
+        .unwrap();
+
+    assert_eq!(result.round, 1);
+    assert_eq!(names(&result), ["Alpha", "Bravo"]);
+    for (team, colour) in result
+        .lineup
+        .iter()
+        .zip([TeamColour::Red, TeamColour::Blue])
+    {
+        assert_eq!(team.colour, Some(colour));
+        assert_eq!(team.worms, Some(3));
+        assert_eq!(team.handicap, Some(Handicap::None));
+    }
+    assert_eq!(result.scheme.as_deref(), Some("Intermediate"));
+    assert_eq!(result.map, Some(MapChoice::Seeded(1234)));
+    assert_eq!(result.options, setup.options);
+    assert_eq!(result.outcome.winner.as_deref(), Some("Bravo"));
+    assert!(!result.outcome.draw);
+    let placements: Vec<(&str, usize, &str)> = result
+        .outcome
+        .placements
+        .iter()
+        .map(|placement| {
+            (
+                &*placement.team,
+                placement.place,
+                &*placement.stats["Kills"],
+            )
+        })
+        .collect();
+    assert_eq!(placements, [("Bravo", 1, "1"), ("Alpha", 2, "0")]);
+
+    assert_eq!(
+        wa.scheme(),
+        ("Intermediate".to_owned(), [30, common::ROUND_TIMES[1], 150])
+    );
+    // Back in the lobby, ready for the next round
+    assert_eq!(wa.selected(), ["Alpha", "Bravo"]);
+}
+
+#[test]
+fn logs_the_actions_taken() {
+    let wa = wa();
+    let setup = setup(&["Team 17", "Cats"], MapChoice::File("Cavern".into()));
+
+    wa.run(vec![RoundEnd::Drawn], |backend, root| {
+        play_round(backend, root, &setup, &timeouts(), &CancelToken::new())
+    })
+    .unwrap();
+
+    // Runs of the same kind of action
+    let mut runs: Vec<(&str, usize)> = Vec::new();
+    for action in wa.backend.actions() {
+        let kind = match action {
+            FakeAction::Click(_) => "click",
+            FakeAction::ClickAt(_, position) => {
+                // Each team is scrolled to the top of the list before it is clicked
+                assert_eq!(position, (100, 8));
+                "click item"
+            }
+            FakeAction::Select(_, index) => match index {
+                1 => "select Intermediate",
+                2 => "select Cavern",
+                index => panic!("Selected item {index}"),
+            },
+            action => panic!("Unexpected {action:?}"),
+        };
+        match runs.last_mut() {
+            Some((last, count)) if *last == kind => *count += 1,
+            _ => runs.push((kind, 1)),
+        }
+    }
+
+    assert_eq!(
+        runs,
+        [
+            // Create game, then the reset takes the turn time from 45 round to the default 20 on
+            // the Beginner scheme, which is already selected
+            ("click", 5),
+            // Adding both teams, as the reset found none to remove
+            ("click item", 2),
+            // The worms of both teams from 4 round to 3
+            ("click", 14),
+            ("select Intermediate", 1),
+            // The turn time from 45 round to 30, and the worm energy from 100 to 150
+            ("click", 6),
+            ("select Cavern", 1),
+            // Play and leave the results
+            ("click", 2),
+        ]
+    );
+}
+
+#[test]
+fn resets_the_lobby_left_from_an_earlier_match() {
+    let wa = wa();
+    wa.preselect(&[
+        ("Bravo", TeamColour::Red, 8),
+        ("Alpha", TeamColour::Red, 8),
+        ("Cats", TeamColour::Green, 2),
+    ]);
+    // Only the teams, so anything left that isn't reset carries into the round
+    let setup = GameSetup::new(Lineup::new(Teams::Named(vec![
+        "Alpha".into(),
+        "Bravo".into(),
+    ])));
+
+    let result = wa
+        .run(vec![RoundEnd::Drawn], |backend, root| {
+            play_round(backend, root, &setup, &timeouts(), &CancelToken::new())
+        })
+        .unwrap();
+
+    assert_eq!(names(&result), ["Alpha", "Bravo"]);
+    // Added again with their old alliance undone, and the settings of newly added teams
+    assert_eq!(
+        wa.settings(),
+        [(TeamColour::Red, 4, "None"), (TeamColour::Blue, 4, "None"),]
+    );
+    let colours: Vec<_> = result.lineup.iter().map(|team| team.colour).collect();
+    assert_eq!(colours, [Some(TeamColour::Red), Some(TeamColour::Blue)]);
+    assert_eq!(result.scheme.as_deref(), Some(common::DEFAULT_SCHEME));
+    assert_eq!(wa.scheme().1[0], common::DEFAULT_TURN_TIME);
+}
+
+#[test]
+fn plays_rounds_in_turn_and_reports_each() {
+    let wa = wa();
+    let setups = [
+        setup(&["Alpha", "Bravo"], MapChoice::Seeded(1234)),
+        setup(&["Alpha", "Bravo", "Cats"], MapChoice::File("Arena".into())),
+        setup(&["Team 17", "Alpha"], MapChoice::Random),
+    ];
+    let mut reported = Vec::new();
+
+    let played = wa
+        .run(
+            vec![
+                RoundEnd::Won(vec!["Alpha", "Bravo"]),
+                RoundEnd::Drawn,
+                RoundEnd::Won(vec!["Team 17", "Alpha"]),
+            ],
+            |backend, root| {
+                play_rounds(
+                    backend,
+                    root,
+                    &timeouts(),
+                    &CancelToken::new(),
+                    |round| setups.get(round as usize - 1).cloned(),
+                    |result| reported.push(result.clone()),
+                )
+            },
+        )
+        .unwrap();
+
+    assert_eq!(played, reported);
+    let rounds: Vec<u32> = played.iter().map(|result| result.round).collect();
+    assert_eq!(rounds, [1, 2, 3]);
+    assert_eq!(names(&played[0]), ["Alpha", "Bravo"]);
+    assert_eq!(names(&played[1]), ["Alpha", "Bravo", "Cats"]);
+    assert_eq!(names(&played[2]), ["Team 17", "Alpha"]);
+
+    assert_eq!(played[0].outcome.winner.as_deref(), Some("Alpha"));
+    assert!(played[1].outcome.draw);
+    assert_eq!(played[1].outcome.winner, None);
+    assert_eq!(played[1].outcome.placements.len(), 3);
+    assert_eq!(played[2].outcome.winner.as_deref(), Some("Team 17"));
+
+    assert_eq!(played[0].map, Some(MapChoice::Seeded(1234)));
+    assert_eq!(played[1].map, Some(MapChoice::File("Arena".into())));
+    // A random map is recorded by the seed it was generated from
+    assert_eq!(played[2].map, Some(MapChoice::Seeded(1000)));
+    assert_eq!(wa.selected(), ["Team 17", "Alpha"]);
+}
+
+#[test]
+fn stops_when_cancelled() {
+    let wa = wa();
+    let setup = setup(&["Alpha", "Bravo"], MapChoice::Seeded(7));
+    let cancel = CancelToken::new();
+
+    let played = wa
+        .run(
+            vec![
+                RoundEnd::Won(vec!["Alpha", "Bravo"]),
+                RoundEnd::Won(vec!["Bravo", "Alpha"]),
+            ],
+            |backend, root| {
+                play_rounds(
+                    backend,
+                    root,
+                    &timeouts(),
+                    &cancel,
+                    |_| Some(setup.clone()),
+                    |result| {
+                        if result.round == 2 {
+                            cancel.cancel();
+                        }
+                    },
+                )
+            },
+        )
+        .unwrap();
+
+    assert_eq!(played.len(), 2);
+    assert_eq!(played[1].outcome.winner.as_deref(), Some("Bravo"));
+}
+
+#[test]
+fn fails_for_missing_teams() {
+    let wa = wa();
+    let setup = setup(&["Alpha", "Nobody"], MapChoice::Seeded(7));
+
+    let err = wa
+        .run(Vec::new(), |backend, root| {
+            play_round(backend, root, &setup, &timeouts(), &CancelToken::new())
+        })
+        .unwrap_err();
+
+    assert!(err.to_string().contains("No teams named Nobody"), "{err}");
+    assert!(wa.selected().is_empty());
+}
+
+#[test]
+fn plays_a_failed_round_again() {
+    let wa = wa();
+    let good = setup(&["Alpha", "Bravo"], MapChoice::Seeded(7));
+    let bad = setup(&["Alpha", "Nobody"], MapChoice::Seeded(7));
+    let mut asked = Vec::new();
+
+    let played = wa
+        .run(
+            vec![RoundEnd::Won(vec!["Bravo", "Alpha"])],
+            |backend, root| {
+                play_rounds(
+                    backend,
+                    root,
+                    &timeouts(),
+                    &CancelToken::new(),
+                    |round| {
+                        asked.push(round);
+                        match asked.len() {
+                            // The first two tries of the round fail
+                            1 | 2 => Some(bad.clone()),
+                            3 => Some(good.clone()),
+                            _ => None,
+                        }
+                    },
+                    |_| {},
+                )
+            },
+        )
+        .unwrap();
+
+    assert_eq!(asked, [1, 1, 1, 2]);
+    assert_eq!(played.len(), 1);
+    assert_eq!(played[0].round, 1);
+    assert_eq!(played[0].outcome.winner.as_deref(), Some("Bravo"));
+}
+
+#[test]
+fn stops_after_too_many_failures_in_a_row() {
+    let wa = wa();
+    let bad = setup(&["Alpha", "Nobody"], MapChoice::Seeded(7));
+    let mut tries = 0;
+
+    let err = wa
+        .run(Vec::new(), |backend, root| {
+            play_rounds(
+                backend,
+                root,
+                &timeouts(),
+                &CancelToken::new(),
+                |_| {
+                    tries += 1;
+                    Some(bad.clone())
+                },
+                |_| {},
+            )
+        })
+        .unwrap_err();
+
+    assert!(err.to_string().contains("No teams named Nobody"), "{err}");
+    assert_eq!(tries, Config::default().automation.max_failed_rounds);
+}
@@ -0,0 +1,542 @@
+//! A scripted fake of the WA frontend for driving the automation from end to end: the main menu,
+//! the multiplayer lobby with its team lists, per team controls, scheme, options and map, the
+//! game itself and the round results.
+//!
+//! Every screen is built from scratch when it is shown, with the same child indexes as WA, and
+//! the lobby keeps its teams and settings between rounds as WA does. A team added again keeps the
+//! colour it had, so alliances have to be undone rather than just removing the teams.
+
+#![allow(dead_code)]
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+    time::Duration,
+};
+
+use worms_ai_loop::{
+    automation::{ADD_TEAM_MESSAGE, CREATE_GAME_MENU, ROUND_RESULTS_TEXT, Timeouts, WINDOW_TITLE},
+    backend::{
+        Rect, WindowId,
+        fake::{FakeBackend, FakeDesktop, FakeWindow, ITEM_HEIGHT},
+    },
+    config::{self, Config},
+    lobby::TeamColour,
+};
+
+/// The values each option button goes through, and how they are shown.
+pub const TURN_TIMES: [u32; 6] = [15, 20, 30, 45, 60, 90];
+pub const ROUND_TIMES: [u32; 4] = [5, 10, 15, 20];
+pub const WORM_ENERGIES: [u32; 4] = [50, 100, 150, 200];
+/// The option values a scheme starts with, as indexes into the values above.
+const SCHEME_OPTIONS: [usize; 3] = [3, 1, 1];
+const HANDICAPS: [&str; 3] = ["None", "+", "-"];
+const MAX_SLOTS: usize = 6;
+/// Stands for the generated map at the top of the map list.
+pub const GENERATED_MAP: &str = "(Generated map)";
+
+/// The scheme and turn time the lobby is reset to.
+pub const DEFAULT_SCHEME: &str = "Beginner";
+pub const DEFAULT_TURN_TIME: u32 = 20;
+
+/// Makes changes in the lobby instant, as the fake needs no delay, and gives the lobby defaults to
+/// reset to. Every test uses this config.
+pub fn init() {
+    let mut config = Config::default();
+    config.automation.add_team_delay_ms = 0;
+    config.automation.scheme = Some(DEFAULT_SCHEME.to_owned());
+    config.automation.options.turn_time_secs = Some(DEFAULT_TURN_TIME);
+    config::set(config);
+}
+
+/// Short enough that a test that goes wrong fails rather than hangs.
+pub fn timeouts() -> Timeouts {
+    Timeouts {
+        screen: Duration::from_secs(5),
+        round: Duration::from_secs(5),
+    }
+}
+
+/// How a round the automation plays ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundEnd {
+    /// The teams in finishing order, with the winner first.
+    Won(Vec<&'static str>),
+    Drawn,
+}
+
+#[derive(Debug, Clone)]
+struct Team {
+    name: String,
+    colour: usize,
+    worms: u8,
+    handicap: usize,
+}
+
+#[derive(Debug, Clone)]
+struct LobbyWindows {
+    team_list: WindowId,
+    selected_list: WindowId,
+    /// The alliance, worms and handicap controls of each slot.
+    slots: Vec<[WindowId; 3]>,
+    scheme_list: WindowId,
+    options: [WindowId; 3],
+    map_list: WindowId,
+    seed: WindowId,
+    new_map: WindowId,
+    play: WindowId,
+    back: WindowId,
+}
+
+#[derive(Debug, Clone)]
+enum Shown {
+    MainMenu { create: WindowId },
+    Lobby(LobbyWindows),
+    InGame,
+    Results { exit: WindowId },
+}
+
+#[derive(Debug)]
+struct State {
+    root: WindowId,
+    frame: Option<WindowId>,
+    shown: Shown,
+    teams: Vec<String>,
+    selected: Vec<Team>,
+    schemes: Vec<String>,
+    scheme: usize,
+    options: [usize; 3],
+    /// The generated map first, then the saved maps.
+    maps: Vec<String>,
+    map: usize,
+    seed: String,
+    next_seed: u32,
+    /// The colour of each team that has been in the game.
+    colours: HashMap<String, usize>,
+    /// The error dialog and its OK button, if one is open.
+    dialog: Option<(WindowId, WindowId)>,
+}
+
+/// The fake frontend, starting on the main menu.
+pub struct FakeWa {
+    pub backend: FakeBackend,
+    pub root: WindowId,
+    state: Arc<Mutex<State>>,
+}
+
+impl FakeWa {
+    pub fn new(teams: &[&str], schemes: &[&str], maps: &[&str]) -> Self {
+        let mut desktop = FakeDesktop::new();
+        let root = desktop.add_root(FakeWindow::new("Worms2D").text(WINDOW_TITLE));
+        let mut state = State {
+            root,
+            frame: None,
+            shown: Shown::InGame,
+            teams: teams.iter().map(|team| team.to_string()).collect(),
+            selected: Vec::new(),
+            schemes: schemes.iter().map(|scheme| scheme.to_string()).collect(),
+            scheme: 0,
+            options: SCHEME_OPTIONS,
+            maps: std::iter::once(GENERATED_MAP)
+                .chain(maps.iter().copied())
+                .map(str::to_owned)
+                .collect(),
+            map: 0,
+            seed: "1".to_owned(),
+            next_seed: 1000,
+            colours: HashMap::new(),
+            dialog: None,
+        };
+        state.show_main_menu(&mut desktop);
+
+        let backend = FakeBackend::new(desktop);
+        let state = Arc::new(Mutex::new(state));
+        let clicked = state.clone();
+        backend.on_any_click(move |desktop, id| lock(&clicked).click(desktop, id));
+        let item_clicked = state.clone();
+        backend.on_any_item_click(move |desktop, id, index| {
+            lock(&item_clicked).item_click(desktop, id, index)
+        });
+
+        Self {
+            backend,
+            root,
+            state,
+        }
+    }
+
+    /// Runs the automation on another thread, ending each round it starts with the next of
+    /// `ends` once the game has been shown.
+    pub fn run<T: Send>(
+        &self,
+        ends: Vec<RoundEnd>,
+        automation: impl FnOnce(&FakeBackend, WindowId) -> T + Send,
+    ) -> T {
+        let mut ends = ends.into_iter();
+        thread::scope(|scope| {
+            let running = scope.spawn(|| automation(&self.backend, self.root));
+            while !running.is_finished() {
+                {
+                    let desktop = self.backend.desktop();
+                    let mut state = lock(&self.state);
+                    if matches!(state.shown, Shown::InGame) {
+                        let end = ends.next().expect("A round was played without an end");
+                        let mut desktop = desktop;
+                        state.show_results(&mut desktop, &end);
+                    }
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            running.join().expect("The automation panicked")
+        })
+    }
+
+    /// Opens an error dialog over whichever screen is shown, blocking it until OK is clicked.
+    pub fn show_error(&self, message: &str) {
+        let mut desktop = self.backend.desktop();
+        let dialog = desktop.add_root(FakeWindow::new("#32770").text("Error"));
+        desktop.add_child(dialog, FakeWindow::new("Static").text(message));
+        let ok = desktop.add_child(dialog, FakeWindow::new("Button").text("OK"));
+        lock(&self.state).dialog = Some((dialog, ok));
+    }
+
+    /// Whether an error dialog is open.
+    pub fn has_error(&self) -> bool {
+        lock(&self.state).dialog.is_some()
+    }
+
+    /// The teams in the game, in order.
+    pub fn selected(&self) -> Vec<String> {
+        lock(&self.state)
+            .selected
+            .iter()
+            .map(|team| team.name.clone())
+            .collect()
+    }
+
+    /// The colour, worms and handicap text of each team in the game.
+    pub fn settings(&self) -> Vec<(TeamColour, u8, &'static str)> {
+        lock(&self.state)
+            .selected
+            .iter()
+            .map(|team| {
+                (
+                    TeamColour::ALL[team.colour],
+                    team.worms,
+                    HANDICAPS[team.handicap],
+                )
+            })
+            .collect()
+    }
+
+    /// The selected scheme, turn time, round time and worm energy.
+    pub fn scheme(&self) -> (String, [u32; 3]) {
+        let state = lock(&self.state);
+        (
+            state.schemes[state.scheme].clone(),
+            [
+                TURN_TIMES[state.options[0]],
+                ROUND_TIMES[state.options[1]],
+                WORM_ENERGIES[state.options[2]],
+            ],
+        )
+    }
+
+    /// Adds the teams to the game and gives them settings, as left over from an earlier match.
+    pub fn preselect(&self, teams: &[(&str, TeamColour, u8)]) {
+        let mut desktop = self.backend.desktop();
+        let mut state = lock(&self.state);
+        state.selected = teams
+            .iter()
+            .map(|(name, colour, worms)| Team {
+                name: name.to_string(),
+                colour: TeamColour::ALL
+                    .iter()
+                    .position(|other| other == colour)
+                    .unwrap(),
+                worms: *worms,
+                handicap: 1,
+            })
+            .collect();
+        state.options = [0, 0, 0];
+        state.render(&mut desktop);
+    }
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    state.lock().expect("Fake WA lock poisoned")
+}
+
+impl State {
+    fn click(&mut self, desktop: &mut FakeDesktop, id: WindowId) {
+        if let Some((dialog, ok)) = self.dialog {
+            if id == ok {
+                desktop.remove(dialog);
+                self.dialog = None;
+            }
+            return;
+        }
+
+        match self.shown.clone() {
+            Shown::MainMenu { create } if id == create => self.show_lobby(desktop),
+            Shown::Lobby(lobby) => {
+                if id == lobby.play && self.selected.len() >= 2 {
+                    self.leave_lobby(desktop, &lobby);
+                    self.shown = Shown::InGame;
+                } else if id == lobby.back {
+                    self.leave_lobby(desktop, &lobby);
+                    self.show_main_menu(desktop);
+                } else if id == lobby.new_map {
+                    self.seed = self.next_seed.to_string();
+                    self.next_seed += 1;
+                    self.map = 0;
+                    desktop.set_text(lobby.seed, &self.seed);
+                } else if let Some(option) = lobby.options.iter().position(|other| *other == id) {
+                    let values = [TURN_TIMES.len(), ROUND_TIMES.len(), WORM_ENERGIES.len()];
+                    self.options[option] = (self.options[option] + 1) % values[option];
+                } else if let Some((slot, control)) =
+                    lobby.slots.iter().enumerate().find_map(|(slot, controls)| {
+                        controls
+                            .iter()
+                            .position(|other| *other == id)
+                            .map(|control| (slot, control))
+                    })
+                    && let Some(team) = self.selected.get_mut(slot)
+                {
+                    match control {
+                        0 => team.colour = (team.colour + 1) % TeamColour::ALL.len(),
+                        1 => team.worms = team.worms % 8 + 1,
+                        _ => team.handicap = (team.handicap + 1) % HANDICAPS.len(),
+                    }
+                }
+                self.render(desktop);
+            }
+            Shown::Results { exit } if id == exit => self.show_lobby(desktop),
+            _ => (),
+        }
+    }
+
+    fn item_click(&mut self, desktop: &mut FakeDesktop, id: WindowId, index: usize) {
+        if self.dialog.is_some() {
+            return;
+        }
+        let Shown::Lobby(lobby) = self.shown.clone() else {
+            return;
+        };
+        if id == lobby.team_list {
+            if self.selected.len() < MAX_SLOTS
+                && let Some(name) = self.available().get(index)
+            {
+                let colour = self.colours.get(name).copied();
+                self.selected.push(Team {
+                    name: name.clone(),
+                    colour: colour.unwrap_or(self.selected.len() % TeamColour::ALL.len()),
+                    worms: 4,
+                    handicap: 0,
+                });
+            }
+        } else if id == lobby.selected_list {
+            if index < self.selected.len() {
+                let team = self.selected.remove(index);
+                self.colours.insert(team.name, team.colour);
+            }
+        } else if id == lobby.scheme_list {
+            self.scheme = index;
+            self.options = SCHEME_OPTIONS;
+        } else if id == lobby.map_list {
+            self.map = index;
+        }
+        self.render(desktop);
+    }
+
+    /// The teams that aren't in the game, in list order.
+    fn available(&self) -> Vec<String> {
+        self.teams
+            .iter()
+            .filter(|team| !self.selected.iter().any(|selected| selected.name == **team))
+            .cloned()
+            .collect()
+    }
+
+    fn replace_frame(&mut self, desktop: &mut FakeDesktop) -> WindowId {
+        if let Some(frame) = self.frame.take() {
+            desktop.remove(frame);
+        }
+        let frame = desktop.add_child(
+            self.root,
+            FakeWindow::new("Frame").rect(Rect::new(0, 0, 640, 480)),
+        );
+        self.frame = Some(frame);
+        frame
+    }
+
+    fn show_main_menu(&mut self, desktop: &mut FakeDesktop) {
+        let frame = self.replace_frame(desktop);
+        desktop.add_child(frame, FakeWindow::new("Static").text("Main menu"));
+        let create = desktop.add_child(frame, FakeWindow::new("Button").text(CREATE_GAME_MENU));
+        self.shown = Shown::MainMenu { create };
+    }
+
+    /// Builds the lobby with the controls at the child indexes WA has them at.
+    fn show_lobby(&mut self, desktop: &mut FakeDesktop) {
+        let frame = self.replace_frame(desktop);
+        let mut children = Vec::new();
+        for index in 0..=38 {
+            let window = match index {
+                2 => FakeWindow::new("Button").text("Start game"),
+                3 => FakeWindow::new("Button").text("Back"),
+                9 => FakeWindow::new("Static").text(ADD_TEAM_MESSAGE),
+                35..=38 => FakeWindow::new("Panel"),
+                _ => FakeWindow::new("Static"),
+            };
+            children.push(desktop.add_child(frame, window));
+        }
+
+        let list = |desktop: &mut FakeDesktop, panel: WindowId| {
+            desktop.add_child(panel, FakeWindow::new("Static").text("Teams"));
+            desktop.add_child(
+                panel,
+                FakeWindow::new("ListBox").rect(Rect::new(0, 0, 200, ITEM_HEIGHT * 6)),
+            )
+        };
+        let team_list = list(desktop, children[38]);
+        let selected_list = list(desktop, children[37]);
+        let slots = (0..MAX_SLOTS)
+            .map(|_| [(); 3].map(|_| desktop.add_child(children[37], FakeWindow::new("Button"))))
+            .collect();
+
+        let scheme_list = desktop.add_child(
+            children[36],
+            FakeWindow::new("ComboBox").items(self.schemes.iter()),
+        );
+        let options = [(); 3].map(|_| desktop.add_child(children[36], FakeWindow::new("Button")));
+
+        let map_list = desktop.add_child(
+            children[35],
+            FakeWindow::new("ComboBox").items(self.maps.iter()),
+        );
+        let seed = desktop.add_child(children[35], FakeWindow::new("Edit").text(&self.seed));
+        let new_map = desktop.add_child(children[35], FakeWindow::new("Button").text("New map"));
+
+        self.shown = Shown::Lobby(LobbyWindows {
+            team_list,
+            selected_list,
+            slots,
+            scheme_list,
+            options,
+            map_list,
+            seed,
+            new_map,
+            play: children[2],
+            back: children[3],
+        });
+        self.render(desktop);
+    }
+
+    /// Keeps what was typed into the lobby, then takes it down.
+    fn leave_lobby(&mut self, desktop: &mut FakeDesktop, lobby: &LobbyWindows) {
+        if let Some(seed) = desktop.window(lobby.seed) {
+            self.seed = seed.text.clone();
+        }
+        if let Some(frame) = self.frame.take() {
+            desktop.remove(frame);
+        }
+    }
+
+    /// Shows the state in the lobby's controls.
+    fn render(&self, desktop: &mut FakeDesktop) {
+        let Shown::Lobby(lobby) = &self.shown else {
+            return;
+        };
+
+        let available = self.available();
+        if let Some(list) = desktop.window_mut(lobby.team_list) {
+            list.items = available;
+            list.top_index = 0;
+        }
+        if let Some(list) = desktop.window_mut(lobby.selected_list) {
+            list.items = self.selected.iter().map(|team| team.name.clone()).collect();
+            list.item_colours = self
+                .selected
+                .iter()
+                .map(|team| TeamColour::ALL[team.colour].rgb())
+                .collect();
+            list.top_index = 0;
+        }
+        for (slot, controls) in lobby.slots.iter().enumerate() {
+            let texts = match self.selected.get(slot) {
+                Some(team) => [
+                    String::new(),
+                    team.worms.to_string(),
+                    HANDICAPS[team.handicap].to_owned(),
+                ],
+                None => Default::default(),
+            };
+            for (control, text) in controls.iter().zip(texts) {
+                desktop.set_text(*control, text);
+            }
+        }
+
+        if let Some(list) = desktop.window_mut(lobby.scheme_list) {
+            list.selected = Some(self.scheme);
+        }
+        let texts = [
+            format!("{} secs", TURN_TIMES[self.options[0]]),
+            format!("{} mins", ROUND_TIMES[self.options[1]]),
+            format!("{}", WORM_ENERGIES[self.options[2]]),
+        ];
+        for (button, text) in lobby.options.iter().zip(texts) {
+            desktop.set_text(*button, text);
+        }
+
+        if let Some(list) = desktop.window_mut(lobby.map_list) {
+            list.selected = Some(self.map);
+        }
+    }
+
+    /// Shows the round results as a table of the teams under a header, with the outcome below.
+    fn show_results(&mut self, desktop: &mut FakeDesktop, end: &RoundEnd) {
+        let frame = self.replace_frame(desktop);
+        let cell = |text: &str, column: i32, row: i32| {
+            FakeWindow::new("Static").text(text).rect(Rect::new(
+                column * 100,
+                row * 20,
+                column * 100 + 90,
+                row * 20 + 18,
+            ))
+        };
+
+        desktop.add_child(frame, cell(ROUND_RESULTS_TEXT, 0, 0));
+        let exit = desktop.add_child(
+            frame,
+            FakeWindow::new("Button")
+                .text("OK")
+                .rect(Rect::new(0, 400, 90, 418)),
+        );
+        desktop.add_child(frame, cell("Team", 0, 2));
+        desktop.add_child(frame, cell("Kills", 1, 2));
+
+        let (order, banner) = match end {
+            RoundEnd::Won(order) => (
+                order
+                    .iter()
+                    .map(|team| team.to_string())
+                    .collect::<Vec<_>>(),
+                format!("{} wins", order[0]),
+            ),
+            RoundEnd::Drawn => (
+                self.selected.iter().map(|team| team.name.clone()).collect(),
+                "Draw".to_owned(),
+            ),
+        };
+        for (row, team) in order.iter().enumerate() {
+            let kills = order.len() - 1 - row;
+            desktop.add_child(frame, cell(team, 0, row as i32 + 3));
+            desktop.add_child(frame, cell(&kills.to_string(), 1, row as i32 + 3));
+        }
+        desktop.add_child(frame, cell(&banner, 0, 15));
+
+        self.shown = Shown::Results { exit };
+    }
+}
@@ -0,0 +1,80 @@
+//! Moves between screens of the scripted fake frontend, including past error dialogs.
+
+mod common;
+
+use common::{FakeWa, init, timeouts};
+use worms_ai_loop::{
+    backend::fake::FakeAction,
+    navigation::{Goal, Navigator},
+    screen::{Recognizer, Screen},
+    wait::CancelToken,
+    window::get_windows,
+};
+
+fn wa() -> FakeWa {
+    init();
+    FakeWa::new(&["Alpha", "Bravo"], &["Beginner"], &["Arena"])
+}
+
+fn screen(wa: &FakeWa) -> Screen {
+    Recognizer::default()
+        .recognise(&wa.backend, &get_windows(&wa.backend, wa.root))
+        .screen
+}
+
+fn navigate(wa: &FakeWa, goal: Screen) -> Screen {
+    wa.run(Vec::new(), |backend, root| {
+        Navigator::default().navigate(
+            backend,
+            root,
+            &Goal::Screen(goal),
+            &timeouts(),
+            &CancelToken::new(),
+        )
+    })
+    .unwrap()
+    .screen
+}
+
+#[test]
+fn recognises_an_error_dialog_over_the_frontend() {
+    let wa = wa();
+    assert_eq!(screen(&wa), Screen::MainMenu);
+
+    navigate(&wa, Screen::MultiplayerLobby);
+    wa.show_error("An error occurred while loading the scheme");
+    assert_eq!(screen(&wa), Screen::ErrorDialog);
+}
+
+#[test]
+fn dismisses_an_error_before_moving_on() {
+    let wa = wa();
+    wa.show_error("Error: the map could not be loaded");
+
+    assert_eq!(
+        navigate(&wa, Screen::MultiplayerLobby),
+        Screen::MultiplayerLobby
+    );
+    assert!(!wa.has_error());
+    // OK, then create game
+    let actions = wa.backend.actions();
+    assert_eq!(actions.len(), 2);
+    assert!(
+        actions
+            .iter()
+            .all(|action| matches!(action, FakeAction::Click(_)))
+    );
+}
+
+#[test]
+fn dismisses_an_error_on_the_goal_screen() {
+    let wa = wa();
+    navigate(&wa, Screen::MultiplayerLobby);
+    wa.show_error("Error: too many teams");
+
+    assert_eq!(
+        navigate(&wa, Screen::MultiplayerLobby),
+        Screen::MultiplayerLobby
+    );
+    assert!(!wa.has_error());
+}
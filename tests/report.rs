@@ -0,0 +1,117 @@
+//! Summarises a small hand-written results store.
+
+use std::time::Duration;
+
+use worms_ai_loop::{
+    report::{Breakdown, Report, Tally},
+    store::ResultStore,
+};
+
+const BOTS: &str = "<Bots & \"Co\">";
+const TEAM_17: &str = "Team|17";
+
+fn report() -> Report {
+    let path = format!(
+        "{}/tests/fixtures/results_store.jsonl",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    Report::new(&ResultStore::new(path).load().unwrap())
+}
+
+fn tally(wins: u32, draws: u32, losses: u32) -> Tally {
+    Tally {
+        wins,
+        draws,
+        losses,
+    }
+}
+
+#[test]
+fn tallies_every_round() {
+    let report = report();
+
+    assert_eq!(report.rounds.rounds, 5);
+    assert_eq!(report.rounds.draws, 1);
+    assert_eq!(report.rounds.average_duration(), Duration::from_secs(104));
+    // The fourth round has no winner, so only counts as a round
+    assert_eq!(report.unread, 1);
+    let teams: Vec<(&str, Tally)> = report
+        .teams
+        .iter()
+        .map(|(team, tally)| (&**team, *tally))
+        .collect();
+    assert_eq!(
+        teams,
+        [
+            (BOTS, tally(2, 1, 1)),
+            ("Cats", tally(1, 1, 0)),
+            (TEAM_17, tally(1, 1, 2)),
+        ]
+    );
+
+    let rounds = |breakdown: &Breakdown| -> Vec<(String, u32)> {
+        breakdown
+            .rounds
+            .iter()
+            .map(|(group, summary)| (group.to_string(), summary.rounds))
+            .collect()
+    };
+    assert_eq!(
+        rounds(&report.schemes),
+        [
+            ("Beginner".to_owned(), 2),
+            ("Intermediate".to_owned(), 2),
+            ("unknown".to_owned(), 1),
+        ]
+    );
+    assert_eq!(
+        rounds(&report.maps),
+        [
+            ("Arena".to_owned(), 2),
+            ("seed 7".to_owned(), 2),
+            ("unknown".to_owned(), 1),
+        ]
+    );
+    assert_eq!(report.maps.teams["Arena"][BOTS], tally(1, 1, 0));
+    assert!(report.variants.is_empty());
+}
+
+#[test]
+fn head_to_head_is_symmetric() {
+    let report = report();
+
+    assert_eq!(
+        report.head_to_head[&(BOTS.into(), TEAM_17.into())],
+        tally(2, 1, 1)
+    );
+    // Allies in the last round, so they only met in the draw
+    assert_eq!(
+        report.head_to_head[&(BOTS.into(), "Cats".into())],
+        tally(0, 1, 0)
+    );
+    for ((team, opponent), tally) in report.head_to_head.iter() {
+        let reversed = report.head_to_head[&(opponent.clone(), team.clone())];
+        assert_eq!(
+            (tally.wins, tally.draws, tally.losses),
+            (reversed.losses, reversed.draws, reversed.wins),
+            "{team} against {opponent}"
+        );
+    }
+}
+
+#[test]
+fn escapes_team_names() {
+    let report = report();
+
+    let html = report.html();
+    assert!(
+        html.contains("<td>&lt;Bots &amp; &quot;Co&quot;&gt;</td>"),
+        "{html}"
+    );
+    assert!(!html.contains(BOTS));
+    assert!(html.contains("<td>Team|17</td>"));
+
+    let markdown = report.markdown();
+    assert!(markdown.contains("| Team\\|17 |"), "{markdown}");
+    assert!(markdown.contains(BOTS));
+}
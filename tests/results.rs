@@ -0,0 +1,167 @@
+//! Reads the outcome of rounds from snapshots of the round results screen.
+//!
+//! The `synthetic_round_results_*.json` fixtures are written by hand in the snapshot format, with
+//! the layout the parser expects, to cover cases such as a column whose heading contains the draw
+//! text. Real screens saved with the analyser's "Save Snapshot" go in `tests/fixtures/captured/`,
+//! each `NAME.json` next to a `NAME.expected.json` holding the `lineup` of the round and the
+//! `winner` and `draw` it should be read as.
+
+use std::path::Path;
+
+use serde_json::Value;
+use worms_ai_loop::{
+    lobby::{Handicap, SelectedTeam, TeamColour},
+    results::{RoundOutcome, parse},
+    snapshot::Snapshot,
+    window::get_windows,
+};
+
+fn parse_snapshot(path: impl AsRef<Path>, teams: &[SelectedTeam]) -> RoundOutcome {
+    let (backend, root) = Snapshot::load(path).unwrap().to_backend();
+    parse(&backend, &get_windows(&backend, root), teams)
+}
+
+fn outcome(fixture: &str, teams: &[(&str, TeamColour)]) -> RoundOutcome {
+    let path = format!(
+        "{}/tests/fixtures/synthetic_round_results_{fixture}.json",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let teams: Vec<SelectedTeam> = teams
+        .iter()
+        .map(|(name, colour)| SelectedTeam {
+            name: (*name).into(),
+            colour: Some(*colour),
+            worms: Some(4),
+            handicap: Some(Handicap::None),
+        })
+        .collect();
+    parse_snapshot(path, &teams)
+}
+
+/// Each team's place and the stat in the column.
+fn column<'a>(outcome: &'a RoundOutcome, heading: &str) -> Vec<(&'a str, usize, &'a str)> {
+    outcome
+        .placements
+        .iter()
+        .map(|placement| {
+            (
+                &*placement.team,
+                placement.place,
+                &*placement.stats[heading],
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn reads_a_win() {
+    // A team named after another, and a team and a column containing the draw text
+    let outcome = outcome(
+        "win",
+        &[
+            ("Bot", TeamColour::Red),
+            ("Bot 2", TeamColour::Blue),
+            ("Quickdraw", TeamColour::Green),
+        ],
+    );
+
+    assert_eq!(outcome.winner.as_deref(), Some("Bot 2"));
+    assert!(!outcome.draw);
+    assert_eq!(
+        column(&outcome, "Kills"),
+        [("Bot 2", 1, "3"), ("Bot", 2, "1"), ("Quickdraw", 3, "0")]
+    );
+    assert_eq!(
+        column(&outcome, "Draws"),
+        [("Bot 2", 1, "1"), ("Bot", 2, "1"), ("Quickdraw", 3, "1")]
+    );
+    assert_eq!(outcome.placements[0].stats["Damage"].as_ref(), "310");
+}
+
+#[test]
+fn reads_a_draw() {
+    let outcome = outcome(
+        "draw",
+        &[("Bot", TeamColour::Red), ("Quickdraw", TeamColour::Blue)],
+    );
+
+    assert!(outcome.draw);
+    assert_eq!(outcome.winner, None);
+    assert_eq!(
+        column(&outcome, "Kills"),
+        [("Quickdraw", 1, "1"), ("Bot", 2, "1")]
+    );
+}
+
+#[test]
+fn reads_an_alliance_round() {
+    let outcome = outcome(
+        "alliance",
+        &[
+            ("Alpha", TeamColour::Red),
+            ("Bravo", TeamColour::Red),
+            ("Charlie", TeamColour::Blue),
+            ("Delta", TeamColour::Blue),
+        ],
+    );
+
+    assert_eq!(outcome.winner.as_deref(), Some("Bravo"));
+    assert!(!outcome.draw);
+    assert_eq!(
+        column(&outcome, "Damage"),
+        [
+            ("Bravo", 1, "250"),
+            ("Alpha", 2, "120"),
+            ("Charlie", 3, "90"),
+            ("Delta", 4, "40"),
+        ]
+    );
+}
+
+#[test]
+fn ignores_teams_missing_from_the_lineup() {
+    // The winner shown isn't in the game, so it can't be the winner
+    let outcome = outcome(
+        "win",
+        &[("Bot", TeamColour::Red), ("Quickdraw", TeamColour::Blue)],
+    );
+
+    assert_eq!(outcome.winner, None);
+    assert!(!outcome.draw);
+    let teams: Vec<&str> = outcome
+        .placements
+        .iter()
+        .map(|placement| &*placement.team)
+        .collect();
+    assert_eq!(teams, ["Bot", "Quickdraw"]);
+}
+
+#[test]
+fn reads_real_captures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/captured");
+    let Ok(entries) = std::fs::read_dir(&dir) else {
+        return;
+    };
+    for path in entries.map(|entry| entry.unwrap().path()) {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        if !name.ends_with(".json") || name.ends_with(".expected.json") {
+            continue;
+        }
+
+        let expected = path.with_extension("expected.json");
+        let expected: Value =
+            serde_json::from_str(&std::fs::read_to_string(&expected).unwrap()).unwrap();
+        let teams: Vec<SelectedTeam> = serde_json::from_value(expected["lineup"].clone()).unwrap();
+        let outcome = parse_snapshot(&path, &teams);
+
+        assert_eq!(
+            outcome.winner.as_deref(),
+            expected["winner"].as_str(),
+            "{name}"
+        );
+        assert_eq!(outcome.draw, expected["draw"].as_bool().unwrap(), "{name}");
+        assert_eq!(outcome.placements.len(), teams.len(), "{name}");
+    }
+}